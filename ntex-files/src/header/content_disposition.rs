@@ -8,9 +8,11 @@
 
 use super::error;
 use super::parsing::{self, ExtendedValue};
-use super::{Header, RawLike};
+use super::{Charset, Header, HttpDate, RawLike};
 use crate::standard_header;
+use ntex::http::header::HeaderValue;
 use regex::Regex;
+use std::borrow::Cow;
 use std::fmt;
 use std::sync::LazyLock;
 
@@ -33,6 +35,8 @@ pub enum DispositionType {
     Ext(String),
 }
 
+/// Maps `inline`, `attachment` and `form-data` case-insensitively, anything else to
+/// [`Ext`](DispositionType::Ext) as is.
 impl<'a> From<&'a str> for DispositionType {
     fn from(origin: &'a str) -> DispositionType {
         if unicase::eq_ascii(origin, "inline") {
@@ -47,6 +51,15 @@ impl<'a> From<&'a str> for DispositionType {
     }
 }
 
+/// Parses like `From<&str>`, never failing.
+impl std::str::FromStr for DispositionType {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
 /// A parameter to the disposition type.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DispositionParam {
@@ -209,6 +222,39 @@ pub struct ContentDisposition {
 }
 
 impl ContentDisposition {
+    /// Create an *attachment* disposition, prompting the recipient to save the content as
+    /// `filename`.
+    ///
+    /// An ASCII filename is set as a plain *filename*, any other, or one with control
+    /// characters, as a UTF-8 *filename\**, so the header stays valid.
+    pub fn attachment(filename: impl Into<String>) -> Self {
+        ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![filename_param(filename.into())],
+        }
+    }
+
+    /// Create an *inline* disposition without parameters.
+    pub fn inline() -> Self {
+        ContentDisposition { disposition: DispositionType::Inline, parameters: Vec::new() }
+    }
+
+    /// Create a *form-data* disposition for the form field `name`.
+    ///
+    /// The name is written as a quoted string, so it may contain spaces, `;` or `"`. Fails
+    /// with [`Error::Header`](error::Error::Header) if it contains control characters, which
+    /// a quoted string can not carry.
+    pub fn form_data(name: impl Into<String>) -> error::Result<Self> {
+        let name = name.into();
+        if name.contains(char::is_control) {
+            return Err(error::Error::Header);
+        }
+        Ok(ContentDisposition {
+            disposition: DispositionType::FormData,
+            parameters: vec![DispositionParam::Name(name)],
+        })
+    }
+
     /// Returns `true` if type is [`Inline`](DispositionType::Inline).
     pub fn is_inline(&self) -> bool {
         matches!(self.disposition, DispositionType::Inline)
@@ -247,6 +293,71 @@ impl ContentDisposition {
         self.parameters.iter().find_map(DispositionParam::as_filename_ext)
     }
 
+    /// Return the value of *filename\** if exists, decoded into a `String`.
+    ///
+    /// Supports the same charsets as [`get_unknown_ext_decoded`](Self::get_unknown_ext_decoded).
+    pub fn get_filename_ext_decoded(&self) -> Option<String> {
+        decode_ext_value(self.get_filename_ext()?)
+    }
+
+    /// Return the best available filename, preferring *filename\** over *filename*.
+    ///
+    /// The extended value is decoded like
+    /// [`get_filename_ext_decoded`](Self::get_filename_ext_decoded), falling back to a lossy
+    /// UTF-8 conversion for other charsets or invalid octets. The plain *filename* is returned
+    /// as is, without allocating.
+    pub fn get_filename_decoded(&self) -> Option<Cow<'_, str>> {
+        if let Some(ext_value) = self.get_filename_ext() {
+            let filename = decode_ext_value(ext_value)
+                .unwrap_or_else(|| String::from_utf8_lossy(&ext_value.value).into_owned());
+            return Some(Cow::Owned(filename));
+        }
+        self.get_filename().map(Cow::Borrowed)
+    }
+
+    /// Return the filename of [`get_filename_decoded`](Self::get_filename_decoded) made safe
+    /// to use as the name of a file in a directory of your choosing.
+    ///
+    /// Only the last path segment is kept, splitting on both `/` and `\`, NUL bytes are
+    /// removed and leading dots are stripped, so the result is never a path component like
+    /// `..` nor a hidden file. Returns `None` if nothing usable remains.
+    pub fn sanitized_filename(&self) -> Option<String> {
+        let filename = self.get_filename_decoded()?;
+        let segment = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+        let name: String =
+            segment.chars().filter(|&c| c != '\0').skip_while(|&c| c == '.').collect();
+
+        if name.is_empty() { None } else { Some(name) }
+    }
+
+    /// Return the value of the `size` parameter, the approximate size of the file in bytes
+    /// (RFC 2183).
+    ///
+    /// Returns `None` if the parameter does not exist or is not a decimal number.
+    pub fn get_size(&self) -> Option<u64> {
+        let size = self.get_unknown("size")?;
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        size.parse().ok()
+    }
+
+    /// Return the value of the `creation-date` parameter (RFC 2183).
+    ///
+    /// Returns `None` if the parameter does not exist or is not in one of the HTTP date
+    /// formats, see [`HttpDate`].
+    pub fn get_creation_date(&self) -> Option<HttpDate> {
+        self.get_unknown("creation-date")?.parse().ok()
+    }
+
+    /// Return the value of the `modification-date` parameter (RFC 2183).
+    ///
+    /// Returns `None` if the parameter does not exist or is not in one of the HTTP date
+    /// formats, see [`HttpDate`].
+    pub fn get_modification_date(&self) -> Option<HttpDate> {
+        self.get_unknown("modification-date")?.parse().ok()
+    }
+
     /// Return the value of the parameter which the `name` matches.
     pub fn get_unknown(&self, name: impl AsRef<str>) -> Option<&str> {
         let name = name.as_ref();
@@ -258,6 +369,221 @@ impl ContentDisposition {
         let name = name.as_ref();
         self.parameters.iter().find_map(|p| p.as_unknown_ext(name))
     }
+
+    /// Return the value of the extended parameter which the `name` matches, decoded into a
+    /// `String`.
+    ///
+    /// Values in UTF-8, US-ASCII and ISO-8859-1 are supported. Returns `None` if the parameter
+    /// does not exist, uses another charset or is not valid in its charset.
+    pub fn get_unknown_ext_decoded(&self, name: impl AsRef<str>) -> Option<String> {
+        decode_ext_value(self.get_unknown_ext(name)?)
+    }
+}
+
+impl ContentDisposition {
+    /// Render the header into a [`HeaderValue`], ready to be set on a response.
+    ///
+    /// Fails with [`Error::Header`](error::Error::Header) if the rendered header is not a
+    /// valid header value, e.g. when a plain *filename* holds control or non-ASCII characters,
+    /// or when the disposition type or a parameter name is not a token. Such filenames are
+    /// carried by *filename\** instead, as [`attachment`](Self::attachment) does.
+    pub fn to_header_value(&self) -> error::Result<HeaderValue> {
+        // values are quoted or percent-encoded, names are written as they are
+        let ext_type = match self.disposition {
+            DispositionType::Ext(ref disp_type) => Some(disp_type.as_str()),
+            _ => None,
+        };
+        let names = self.parameters.iter().filter_map(|param| match param {
+            DispositionParam::Unknown(name, _) | DispositionParam::UnknownExt(name, _) => {
+                Some(name.as_str())
+            }
+            _ => None,
+        });
+        if !ext_type.into_iter().chain(names).all(is_token) {
+            return Err(error::Error::Header);
+        }
+
+        // `HeaderValue` accepts obs-text, only visible ASCII is allowed here
+        match HeaderValue::from_str(&self.to_string()) {
+            Ok(value) if value.to_str().is_ok() => Ok(value),
+            _ => Err(error::Error::Header),
+        }
+    }
+
+    /// Parse a header like [`Header::parse_header`], also returning the non-fatal anomalies
+    /// found in it.
+    ///
+    /// The parsed value is the same as the one `parse_header` returns, see
+    /// [`warnings`](Self::warnings) for what is reported. Meant for servers that want to log or
+    /// reject suspicious uploads by their own policy.
+    pub fn parse_with_warnings<'a, T>(
+        raw: &'a T,
+    ) -> error::Result<(ContentDisposition, Vec<DispositionWarning>)>
+    where
+        T: RawLike<'a>,
+    {
+        let cd = Self::parse_header(raw)?;
+        let warnings = cd.warnings();
+        Ok((cd, warnings))
+    }
+
+    /// Returns the anomalies of this header that do not prevent parsing it, in the order of
+    /// the parameters they were found in.
+    pub fn warnings(&self) -> Vec<DispositionWarning> {
+        let mut warnings = Vec::new();
+        let mut seen = Vec::<String>::new();
+
+        for param in &self.parameters {
+            let key = param_key(param);
+            let lower = key.to_ascii_lowercase();
+            // report a repeated parameter once, on its second occurrence
+            if seen.iter().filter(|k| **k == lower).count() == 1 {
+                warnings.push(DispositionWarning::DuplicateParameter(key.clone()));
+            }
+            seen.push(lower);
+
+            let (value, has_control) = match param {
+                DispositionParam::Name(value)
+                | DispositionParam::Filename(value)
+                | DispositionParam::Unknown(_, value) => {
+                    (Some(value.clone()), value.chars().any(char::is_control))
+                }
+                DispositionParam::FilenameExt(value)
+                | DispositionParam::UnknownExt(_, value) => match decode_ext_value(value) {
+                    Some(decoded) => {
+                        let has_control = decoded.chars().any(char::is_control);
+                        (Some(decoded), has_control)
+                    }
+                    None => (None, value.value.iter().any(u8::is_ascii_control)),
+                },
+            };
+            if has_control {
+                warnings.push(DispositionWarning::ControlCharacters(key));
+            }
+
+            if matches!(param, DispositionParam::Filename(_) | DispositionParam::FilenameExt(_))
+                && value.is_some_and(|value| value.contains(['/', '\\']))
+            {
+                warnings.push(DispositionWarning::PathInFilename);
+            }
+
+            if param.is_filename_ext() && self.is_form_data() {
+                warnings.push(DispositionWarning::FilenameExtInFormData);
+            }
+        }
+
+        warnings
+    }
+}
+
+/// A suspicious but valid part of a Content-Disposition, see
+/// [`ContentDisposition::parse_with_warnings`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DispositionWarning {
+    /// A filename contains a path separator, `/` or `\`.
+    PathInFilename,
+
+    /// A *filename\** parameter is used on a *form-data* disposition, which
+    /// [RFC 7578](https://datatracker.ietf.org/doc/html/rfc7578#section-4.2) forbids.
+    FilenameExtInFormData,
+
+    /// The value of the named parameter contains control characters.
+    ControlCharacters(String),
+
+    /// The named parameter appears more than once, only the first one is used.
+    DuplicateParameter(String),
+}
+
+impl fmt::Display for DispositionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DispositionWarning::PathInFilename => {
+                write!(f, "filename contains a path separator")
+            }
+            DispositionWarning::FilenameExtInFormData => {
+                write!(f, "filename* used on a form-data disposition")
+            }
+            DispositionWarning::ControlCharacters(key) => {
+                write!(f, "{} contains control characters", key)
+            }
+            DispositionWarning::DuplicateParameter(key) => write!(f, "{} is repeated", key),
+        }
+    }
+}
+
+/// Returns the name of a parameter as it appears in the header.
+fn param_key(param: &DispositionParam) -> String {
+    match param {
+        DispositionParam::Name(_) => "name".to_owned(),
+        DispositionParam::Filename(_) => "filename".to_owned(),
+        DispositionParam::FilenameExt(_) => "filename*".to_owned(),
+        DispositionParam::Unknown(key, _) => key.clone(),
+        DispositionParam::UnknownExt(key, _) => format!("{}*", key),
+    }
+}
+
+/// Returns a *filename* parameter for an ASCII `filename` without control characters, or a
+/// UTF-8 *filename\** otherwise.
+fn filename_param(filename: String) -> DispositionParam {
+    if filename.is_ascii() && !filename.contains(|c: char| c.is_ascii_control()) {
+        DispositionParam::Filename(filename)
+    } else {
+        DispositionParam::FilenameExt(ExtendedValue::new(
+            Charset::Ext(String::from("UTF-8")),
+            None,
+            filename,
+        ))
+    }
+}
+
+/// Returns `true` if `s` is a token as defined in
+/// [RFC 9110 §5.6.2](https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.2).
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Split a header value on the semicolons separating its parameters, leaving those inside
+/// quoted strings alone.
+fn split_sections(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(s);
+    std::iter::from_fn(move || {
+        let s = rest?;
+        let mut quoted = false;
+        let mut escaping = false;
+
+        for (idx, c) in s.char_indices() {
+            if escaping {
+                escaping = false;
+            } else if quoted && c == '\\' {
+                escaping = true;
+            } else if c == '"' {
+                quoted = !quoted;
+            } else if c == ';' && !quoted {
+                rest = Some(&s[idx + 1..]);
+                return Some(&s[..idx]);
+            }
+        }
+
+        rest = None;
+        Some(s)
+    })
+}
+
+/// Decode the octets of an extended value according to its charset.
+fn decode_ext_value(ext_value: &ExtendedValue) -> Option<String> {
+    match ext_value.charset {
+        Charset::Ext(ref cs) if unicase::eq_ascii(cs.as_str(), "UTF-8") => {
+            String::from_utf8(ext_value.value.clone()).ok()
+        }
+        Charset::Us_Ascii if ext_value.value.is_ascii() => {
+            Some(ext_value.value.iter().map(|&b| char::from(b)).collect())
+        }
+        // ISO-8859-1 maps every octet to the code point of the same value
+        Charset::Iso_8859_1 => Some(ext_value.value.iter().map(|&b| char::from(b)).collect()),
+        _ => None,
+    }
 }
 
 impl Header for ContentDisposition {
@@ -271,7 +597,7 @@ impl Header for ContentDisposition {
         T: RawLike<'a>,
     {
         parsing::from_one_raw_str(raw).and_then(|s: String| {
-            let mut sections = s.split(';');
+            let mut sections = split_sections(&s);
             let disposition = match sections.next() {
                 Some(s) => s.trim(),
                 None => return Err(error::Error::Header),
@@ -375,11 +701,15 @@ impl fmt::Display for ContentDisposition {
         }
 
         static RE: LazyLock<Regex> =
-            LazyLock::new(|| Regex::new("[\x00-\x08\x10-\x1F\x7F\"\\\\]").unwrap());
+            LazyLock::new(|| Regex::new("[\x00-\x08\x0A-\x1F\x7F\"\\\\]").unwrap());
 
         for param in &self.parameters {
+            f.write_str("; ")?;
+
             match *param {
-                DispositionParam::Name(ref value) => write!(f, "name={}", value)?,
+                DispositionParam::Name(ref value) => {
+                    write!(f, "name=\"{}\"", RE.replace_all(value, "\\$0").as_ref())?
+                }
 
                 DispositionParam::Filename(ref value) => {
                     write!(f, "filename=\"{}\"", RE.replace_all(value, "\\$0").as_ref())?
@@ -404,9 +734,27 @@ impl fmt::Display for ContentDisposition {
 
 #[cfg(test)]
 mod tests {
-    use super::{ContentDisposition, DispositionParam, DispositionType, Header};
+    use super::{
+        ContentDisposition, DispositionParam, DispositionType, DispositionWarning, Header,
+    };
     use crate::header::parsing::ExtendedValue;
     use crate::header::{Charset, Raw};
+    use std::borrow::Cow;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_disposition_type_from_str() {
+        let cases = [
+            ("inline", DispositionType::Inline),
+            ("Attachment", DispositionType::Attachment),
+            ("FORM-DATA", DispositionType::FormData),
+            ("X-Preview", DispositionType::Ext("X-Preview".to_owned())),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(s.parse::<DispositionType>(), Ok(expected.clone()));
+            assert_eq!(DispositionType::from(s), expected);
+        }
+    }
 
     #[test]
     fn test_parse_header() {
@@ -449,6 +797,307 @@ mod tests {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn test_filename_ext_round_trip() {
+        let a: Raw = "attachment; filename*=UTF-8''%e2%82%ac.txt".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        let ext = a.get_filename_ext().unwrap();
+        assert_eq!(ext.value, "\u{20ac}.txt".as_bytes());
+        assert_eq!(a.get_filename_ext_decoded().as_deref(), Some("\u{20ac}.txt"));
+        assert_eq!(a.get_filename(), None);
+
+        let rendered = a.to_string();
+        assert_eq!(rendered, "attachment; filename*=UTF-8''%E2%82%AC.txt");
+        let b = ContentDisposition::parse_header(&Raw::from(rendered.as_str())).unwrap();
+        assert_eq!(a, b);
+
+        let a: Raw = "form-data; name=upload; filename=\"EUR rates.txt\"; \
+                      filename*=UTF-8''%e2%82%ac%20rates.txt; note*=UTF-8''caf%c3%a9"
+            .into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.parameters.len(), 4);
+        assert_eq!(a.get_name(), Some("upload"));
+        assert_eq!(a.get_filename(), Some("EUR rates.txt"));
+        assert_eq!(a.get_filename_ext_decoded().as_deref(), Some("\u{20ac} rates.txt"));
+        assert!(a.parameters[3].is_unknown_ext("note"));
+
+        let rendered = a.to_string();
+        assert_eq!(
+            rendered,
+            "form-data; name=\"upload\"; filename=\"EUR rates.txt\"; \
+             filename*=UTF-8''%E2%82%AC%20rates.txt; note*=UTF-8''caf%C3%A9"
+        );
+        let b = ContentDisposition::parse_header(&Raw::from(rendered.as_str())).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_parse_with_warnings() {
+        let a: Raw = "form-data; name=upload; filename=\"a.png\"".into();
+        let (cd, warnings) = ContentDisposition::parse_with_warnings(&a).unwrap();
+        assert_eq!(cd.get_filename(), Some("a.png"));
+        assert!(warnings.is_empty());
+
+        let a: Raw = "form-data; name=upload; NAME=other; filename=\"..\\\\etc/passwd\"; \
+                      filename*=UTF-8''a%0Ab.txt; x=1; x=2; x=3"
+            .into();
+        let (cd, warnings) = ContentDisposition::parse_with_warnings(&a).unwrap();
+        assert_eq!(cd.get_name(), Some("upload"));
+        assert_eq!(
+            warnings,
+            [
+                DispositionWarning::DuplicateParameter("name".to_owned()),
+                DispositionWarning::PathInFilename,
+                DispositionWarning::ControlCharacters("filename*".to_owned()),
+                DispositionWarning::FilenameExtInFormData,
+                DispositionWarning::DuplicateParameter("x".to_owned()),
+            ]
+        );
+        assert_eq!(warnings[1].to_string(), "filename contains a path separator");
+
+        let a: Raw = "attachment; filename*=UTF-8''%e2%82%ac.txt".into();
+        let (_, warnings) = ContentDisposition::parse_with_warnings(&a).unwrap();
+        assert!(warnings.is_empty());
+
+        let a: Raw = "".into();
+        assert!(ContentDisposition::parse_with_warnings(&a).is_err());
+    }
+
+    #[test]
+    fn test_parse_quoted_separators() {
+        let a: Raw = "attachment; filename=\"summary; final.txt\"; size=3".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename(), Some("summary; final.txt"));
+        assert_eq!(a.get_unknown("size"), Some("3"));
+
+        let a: Raw = "form-data; name=\"a=b;c\"; filename=\"x=1;y=2\"".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_name(), Some("a=b;c"));
+        assert_eq!(a.get_filename(), Some("x=1;y=2"));
+
+        let a: Raw = "attachment; filename=\"say \\\"hi;\\\" now.txt\"; x=\"\\\\\"; y=1".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename(), Some("say \"hi;\" now.txt"));
+        assert_eq!(a.get_unknown("x"), Some("\\"));
+        assert_eq!(a.get_unknown("y"), Some("1"));
+
+        let a: Raw = "attachment; filename=\"a;b\";".into();
+        assert!(ContentDisposition::parse_header(&a).is_err());
+    }
+
+    #[test]
+    fn test_parse_quoted_name() {
+        let a: Raw = "form-data; name=\"upload\"; filename=\"sample.png\"".into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_name(), Some("upload"));
+        assert_eq!(a.get_filename(), Some("sample.png"));
+
+        let a: Raw = "form-data; name=\"\\\"up\\\"load\"".into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_name(), Some("\"up\"load"));
+
+        let a: Raw = "form-data; name=upload".into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_name(), Some("upload"));
+    }
+
+    #[test]
+    fn test_get_unknown_ext_decoded() {
+        let a: Raw = "attachment; title*=UTF-8'en'%C2%A3%20rates; \
+                      legacy*=ISO-8859-1''caf%E9; plain*=US-ASCII''a%20b; \
+                      broken*=UTF-8''%FF; other*=KOI8-R''%C1"
+            .into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_unknown_ext_decoded("title").as_deref(), Some("\u{a3} rates"));
+        assert_eq!(a.get_unknown_ext_decoded("TITLE").as_deref(), Some("\u{a3} rates"));
+        assert_eq!(a.get_unknown_ext_decoded("legacy").as_deref(), Some("caf\u{e9}"));
+        assert_eq!(a.get_unknown_ext_decoded("plain").as_deref(), Some("a b"));
+        assert_eq!(a.get_unknown_ext_decoded("broken"), None);
+        assert_eq!(a.get_unknown_ext_decoded("other"), None);
+        assert_eq!(a.get_unknown_ext_decoded("missing"), None);
+    }
+
+    #[test]
+    fn test_get_filename_ext_decoded() {
+        let a: Raw =
+            "attachment; filename=\"rates.txt\"; filename*=ISO-8859-1''caf%E9.txt".into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_ext_decoded().as_deref(), Some("caf\u{e9}.txt"));
+
+        let a: Raw = "attachment; filename=\"rates.txt\"".into();
+        let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_ext_decoded(), None);
+    }
+
+    #[test]
+    fn test_get_filename_decoded() {
+        let a: Raw = "attachment; filename*=UTF-8''%e2%82%ac%20rates.txt".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_decoded().as_deref(), Some("\u{20ac} rates.txt"));
+
+        let a: Raw = "attachment; filename=\"rates.txt\"".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert!(matches!(a.get_filename_decoded(), Some(Cow::Borrowed("rates.txt"))));
+
+        let a: Raw =
+            "attachment; filename=\"EUR rates.txt\"; filename*=UTF-8''%e2%82%ac%20rates.txt"
+                .into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_decoded().as_deref(), Some("\u{20ac} rates.txt"));
+
+        // not valid in the declared charset, decoded lossily
+        let a: Raw = "attachment; filename*=UTF-8''caf%e9.txt".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_decoded().as_deref(), Some("caf\u{fffd}.txt"));
+
+        let a: Raw = "attachment".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_filename_decoded(), None);
+    }
+
+    #[test]
+    fn test_rfc2183_params() {
+        let a: Raw = "attachment; filename=genome.jpeg; size=2048; \
+                      creation-date=\"Wed, 12 Feb 1997 16:29:51 GMT\"; \
+                      modification-date=\"Thu, 13 Feb 1997 16:29:51 GMT\""
+            .into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_size(), Some(2048));
+        let created = a.get_creation_date().unwrap();
+        assert_eq!(created.to_string(), "Wed, 12 Feb 1997 16:29:51 GMT");
+        let modified = a.get_modification_date().unwrap();
+        assert_eq!(
+            SystemTime::from(modified).duration_since(created.into()).unwrap(),
+            Duration::from_secs(86400)
+        );
+
+        let a: Raw = "attachment; filename=genome.jpeg".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_size(), None);
+        assert_eq!(a.get_creation_date(), None);
+        assert_eq!(a.get_modification_date(), None);
+
+        for size in ["large", "-1", "+1", "\"\"", "99999999999999999999"] {
+            let a: Raw = format!("attachment; size={size}").as_str().into();
+            let a = ContentDisposition::parse_header(&a).unwrap();
+            assert_eq!(a.get_size(), None, "{size}");
+        }
+
+        let a: Raw = "attachment; creation-date=yesterday".into();
+        let a = ContentDisposition::parse_header(&a).unwrap();
+        assert_eq!(a.get_creation_date(), None);
+    }
+
+    #[test]
+    fn test_constructors() {
+        let a = ContentDisposition::attachment("report \"final\".pdf");
+        assert!(a.is_attachment());
+        assert_eq!(a.to_string(), "attachment; filename=\"report \\\"final\\\".pdf\"");
+
+        let a = ContentDisposition::attachment("\u{20ac} rates.txt");
+        assert_eq!(a.get_filename(), None);
+        assert_eq!(a.to_string(), "attachment; filename*=UTF-8''%E2%82%AC%20rates.txt");
+        let b = ContentDisposition::parse_header(&Raw::from(a.to_string().as_str())).unwrap();
+        assert_eq!(a, b);
+
+        assert_eq!(ContentDisposition::inline().to_string(), "inline");
+
+        let a = ContentDisposition::form_data("upload").unwrap();
+        assert_eq!(a.get_name(), Some("upload"));
+        assert_eq!(a.to_string(), "form-data; name=\"upload\"");
+
+        // the name can not add parameters of its own
+        let cases = [
+            ("a b", "form-data; name=\"a b\""),
+            ("x; filename=evil.sh", "form-data; name=\"x; filename=evil.sh\""),
+            ("x\"; filename=\"evil.sh", "form-data; name=\"x\\\"; filename=\\\"evil.sh\""),
+            ("a\\b", "form-data; name=\"a\\\\b\""),
+        ];
+        for (name, expected) in cases {
+            let a = ContentDisposition::form_data(name).unwrap();
+            assert_eq!(a.to_string(), expected);
+            let b = ContentDisposition::parse_header(&Raw::from(expected)).unwrap();
+            assert_eq!(b.get_name(), Some(name));
+            assert_eq!(b.parameters.len(), 1);
+        }
+
+        assert!(ContentDisposition::form_data("a\r\nX-Evil: 1").is_err());
+        assert!(ContentDisposition::form_data("a\0b").is_err());
+    }
+
+    #[test]
+    fn test_sanitized_filename() {
+        let cases = [
+            ("attachment; filename=\"../../x\"", Some("x")),
+            ("attachment; filename=\"C:\\\\windows\\\\evil.exe\"", Some("evil.exe")),
+            ("attachment; filename=\"....//foo\"", Some("foo")),
+            ("attachment; filename=\"..bashrc\"", Some("bashrc")),
+            ("attachment; filename*=UTF-8''..%2F%E2%82%AC%00.txt", Some("\u{20ac}.txt")),
+            ("attachment; filename=\"uploads/..\"", None),
+            ("attachment; filename=\"\"", None),
+            ("attachment", None),
+        ];
+
+        for (raw, expected) in cases {
+            let a = ContentDisposition::parse_header(&Raw::from(raw)).unwrap();
+            assert_eq!(a.sanitized_filename().as_deref(), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_to_header_value() {
+        let a = ContentDisposition::attachment("report.pdf");
+        assert_eq!(a.to_header_value().unwrap(), "attachment; filename=\"report.pdf\"");
+
+        let a = ContentDisposition::attachment("\u{20ac}.txt");
+        assert_eq!(a.to_header_value().unwrap(), "attachment; filename*=UTF-8''%E2%82%AC.txt");
+
+        // a newline can not be smuggled into the response headers
+        let a = ContentDisposition::attachment("evil.txt\r\nSet-Cookie: a=b");
+        assert_eq!(
+            a.to_header_value().unwrap(),
+            "attachment; filename*=UTF-8''evil.txt%0D%0ASet%2DCookie%3A%20a%3Db"
+        );
+
+        let a = ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("evil.txt\r\nX: y".to_owned())],
+        };
+        assert!(a.to_header_value().is_err());
+
+        let a = ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("\u{20ac}.txt".to_owned())],
+        };
+        assert!(a.to_header_value().is_err());
+
+        // a name quoted by Display can not add parameters
+        let a = ContentDisposition::form_data("x\"; filename=\"evil.sh").unwrap();
+        let value = a.to_header_value().unwrap();
+        let b = ContentDisposition::parse_header(&Raw::from(value.to_str().unwrap())).unwrap();
+        assert_eq!(b.get_filename(), None);
+        assert_eq!(b.get_name(), Some("x\"; filename=\"evil.sh"));
+
+        // names are not quoted, so they must be tokens
+        let a = ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Unknown("x;y".to_owned(), "a".to_owned())],
+        };
+        assert!(a.to_header_value().is_err());
+
+        let a = ContentDisposition {
+            disposition: DispositionType::Ext("inline; filename=evil.sh".to_owned()),
+            parameters: Vec::new(),
+        };
+        assert!(a.to_header_value().is_err());
+
+        let a = ContentDisposition {
+            disposition: DispositionType::Ext("x-preview".to_owned()),
+            parameters: vec![DispositionParam::Unknown("size".to_owned(), "3".to_owned())],
+        };
+        assert_eq!(a.to_header_value().unwrap(), "x-preview; size=\"3\"");
+    }
+
     #[test]
     fn test_display() {
         let as_string = "attachment; filename*=UTF-8'en'%C2%A3%20and%20%E2%82%AC%20rates";
@@ -460,7 +1109,10 @@ mod tests {
         let a: Raw = "attachment; filename*=UTF-8''black%20and%20white.csv".into();
         let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
         let display_rendered = format!("{}", a);
-        assert_eq!("attachment; filename=\"black and white.csv\"".to_owned(), display_rendered);
+        assert_eq!(
+            "attachment; filename*=UTF-8''black%20and%20white.csv".to_owned(),
+            display_rendered
+        );
 
         let a: Raw = "attachment; filename=colourful.csv".into();
         let a: ContentDisposition = ContentDisposition::parse_header(&a).unwrap();
@@ -16,6 +16,7 @@ pub use content_disposition::*;
 pub use entity::EntityTag;
 use http::HeaderValue;
 pub use http_date::HttpDate;
+pub use parsing::ExtendedValue;
 pub use raw::{Raw, RawLike};
 
 use self::sealed::HeaderClone;
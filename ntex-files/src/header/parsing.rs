@@ -137,12 +137,38 @@ pub fn parse_extended_value(val: &str) -> error::Result<ExtendedValue> {
     Ok(ExtendedValue { charset, language_tag: lang, value })
 }
 
+impl ExtendedValue {
+    /// Create an extended value from `value`, the octets of the text in `charset`.
+    ///
+    /// The value is kept as given, text in a charset other than UTF-8 has to be encoded by the
+    /// caller.
+    pub fn new(
+        charset: Charset,
+        language_tag: Option<LanguageTag>,
+        value: impl Into<Vec<u8>>,
+    ) -> Self {
+        ExtendedValue { charset, language_tag, value: value.into() }
+    }
+
+    /// Returns the `value` percent-encoded as `value-chars`, every octet that is not an
+    /// `attr-char` being escaped.
+    pub fn encoded_value(&self) -> impl Display + '_ {
+        percent_encoding::percent_encode(&self.value, percent_encoding_http::HTTP_VALUE)
+    }
+}
+
+impl FromStr for ExtendedValue {
+    type Err = error::Error;
+
+    /// Parse an `ext-value`, see [`parse_extended_value`].
+    fn from_str(s: &str) -> error::Result<ExtendedValue> {
+        parse_extended_value(s)
+    }
+}
+
 impl Display for ExtendedValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let encoded_value = percent_encoding::percent_encode(
-            &self.value[..],
-            percent_encoding_http::HTTP_VALUE,
-        );
+        let encoded_value = self.encoded_value();
         if let Some(ref lang) = self.language_tag {
             write!(f, "{}'{}'{}", self.charset, lang, encoded_value)
         } else {
@@ -180,8 +206,10 @@ mod percent_encoding_http {
         .add(b';')
         .add(b'<')
         .add(b'-')
+        .add(b'=')
         .add(b'>')
         .add(b'?')
+        .add(b'@')
         .add(b'[')
         .add(b'\\')
         .add(b']')
@@ -193,6 +221,7 @@ mod percent_encoding_http {
 mod tests {
     use super::{Charset, ExtendedValue, parse_extended_value};
     use language_tags::LanguageTag;
+    use std::str::FromStr;
 
     #[test]
     fn test_parse_extended_value_with_encoding_and_language_tag() {
@@ -268,4 +297,42 @@ mod tests {
         };
         assert_eq!("UTF-8''%C2%A3%20and%20%E2%82%AC%20rates", format!("{}", extended_value));
     }
+
+    #[test]
+    fn test_extended_value_round_trip() {
+        let utf8 = || Charset::Ext("UTF-8".to_owned());
+        let inputs: Vec<Vec<u8>> = vec![
+            b"".to_vec(),
+            b"plain.txt".to_vec(),
+            b"with spaces and\ttabs".to_vec(),
+            "\u{a3} and \u{20ac} rates".into(),
+            "\u{65e5}\u{672c}\u{8a9e}.pdf".into(),
+            b"reserved ;,/?:@&=+$#%'\"*()<>[]{}\\|^`~!".to_vec(),
+            b"%41 is not decoded twice".to_vec(),
+            (0..=255).collect(),
+        ];
+
+        for value in inputs {
+            for lang in [None, Some("en".parse::<LanguageTag>().unwrap())] {
+                let ext = ExtendedValue::new(utf8(), lang, value.clone());
+                let encoded = ext.to_string();
+
+                let value_chars = encoded.splitn(3, '\'').nth(2).unwrap();
+                assert!(
+                    value_chars
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~%".contains(&b)),
+                    "{encoded}"
+                );
+                assert_eq!(value_chars, ext.encoded_value().to_string());
+
+                let parsed = ExtendedValue::from_str(&encoded).unwrap();
+                assert_eq!(parsed, ext, "{encoded}");
+            }
+        }
+
+        let ext = ExtendedValue::new(Charset::Iso_8859_1, None, vec![163]);
+        assert_eq!(ext.to_string(), "ISO-8859-1''%A3");
+        assert_eq!(ext.to_string().parse::<ExtendedValue>().unwrap(), ext);
+    }
 }
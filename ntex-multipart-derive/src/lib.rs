@@ -44,7 +44,7 @@ struct ParsedField<'t> {
 /// Each field type should implement the `FieldReader` trait:
 ///
 /// ```
-/// use ntex_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
+/// use ntex_multipart::{form::{temp_file::TempFile, text::Text}, MultipartForm};
 ///
 /// #[derive(MultipartForm)]
 /// struct ImageUpload {
@@ -62,7 +62,7 @@ struct ParsedField<'t> {
 /// name](https://www.rfc-editor.org/rfc/rfc7578#section-4.3).
 ///
 /// ```
-/// use ntex_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
+/// use ntex_multipart::{form::{temp_file::TempFile, text::Text}, MultipartForm};
 ///
 /// #[derive(MultipartForm)]
 /// struct Form {
@@ -71,12 +71,25 @@ struct ParsedField<'t> {
 /// }
 /// ```
 ///
+/// Use `MaybeTempFile` to keep small uploads in memory and only write larger ones to disk. The
+/// size threshold is set with `MaybeTempFileConfig` in app data.
+///
+/// ```
+/// use ntex_multipart::{form::temp_file::MaybeTempFile, MultipartForm};
+///
+/// #[derive(MultipartForm)]
+/// struct Form {
+///     avatar: Option<MaybeTempFile>,
+///     attachments: Vec<MaybeTempFile>,
+/// }
+/// ```
+///
 /// # Field Renaming
 ///
 /// You can use the `#[multipart(rename = "foo")]` attribute to receive a field by a different name.
 ///
 /// ```
-/// use ntex_multipart::form::{tempfile::TempFile, MultipartForm};
+/// use ntex_multipart::{form::temp_file::TempFile, MultipartForm};
 ///
 /// #[derive(MultipartForm)]
 /// struct Form {
@@ -93,7 +106,7 @@ struct ParsedField<'t> {
 /// Note: the form is also subject to the global limits configured using `MultipartFormConfig`.
 ///
 /// ```
-/// use ntex_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
+/// use ntex_multipart::{form::{temp_file::TempFile, text::Text}, MultipartForm};
 ///
 /// #[derive(MultipartForm)]
 /// struct Form {
@@ -111,7 +124,7 @@ struct ParsedField<'t> {
 /// `#[multipart(deny_unknown_fields)]` attribute:
 ///
 /// ```
-/// # use ntex_multipart::form::MultipartForm;
+/// # use ntex_multipart::MultipartForm;
 /// #[derive(MultipartForm)]
 /// #[multipart(deny_unknown_fields)]
 /// struct Form { }
@@ -129,7 +142,7 @@ struct ParsedField<'t> {
 /// Note that `Vec` fields will ignore this option.
 ///
 /// ```
-/// # use ntex_multipart::form::MultipartForm;
+/// # use ntex_multipart::MultipartForm;
 /// #[derive(MultipartForm)]
 /// #[multipart(duplicate_field = "deny")]
 /// struct Form { }
@@ -209,7 +222,7 @@ pub fn impl_multipart_form(input: TokenStream) -> TokenStream {
     // Return value when a field name is not supported by the form
     let unknown_field_result = if attrs.deny_unknown_fields {
         quote!(::std::result::Result::Err(::ntex_multipart::MultipartError::UnknownField(
-            field.name().unwrap().to_string()
+            field.form_field_name().to_owned()
         )))
     } else {
         quote!(::std::result::Result::Ok(()))
@@ -233,6 +246,16 @@ pub fn impl_multipart_form(input: TokenStream) -> TokenStream {
         }
     }
 
+    // field_kind() implementation
+    let mut field_kind_impl = quote!();
+    for field in &parsed {
+        let name = &field.serialization_name;
+        let ty = &field.ty;
+        field_kind_impl.extend(quote!(
+            #name => <#ty as ::ntex_multipart::form::FieldGroupReader>::KIND,
+        ));
+    }
+
     // handle_field() implementation
     let mut handle_field_impl = quote!();
     for field in &parsed {
@@ -241,7 +264,7 @@ pub fn impl_multipart_form(input: TokenStream) -> TokenStream {
 
         handle_field_impl.extend(quote!(
             #name => ::std::boxed::Box::pin(
-                <#ty as ::ntex_multipart::form::FieldGroupReader>::handle_field(req, field, limits, state, #duplicate_field)
+                <#ty as ::ntex_multipart::form::FieldGroupReader>::handle_field(ctx, field, limits, state, #duplicate_field)
             ),
         ));
     }
@@ -266,13 +289,20 @@ pub fn impl_multipart_form(input: TokenStream) -> TokenStream {
                 }
             }
 
+            fn field_kind(field_name: &str) -> ::ntex_multipart::form::FieldKind {
+                match field_name {
+                    #field_kind_impl
+                    _ => ::ntex_multipart::form::FieldKind::Any,
+                }
+            }
+
             fn handle_field<'t>(
-                req: &'t ::ntex::web::HttpRequest,
+                ctx: &'t ::ntex_multipart::form::FormContext,
                 field: ::ntex_multipart::Field,
                 limits: &'t mut ::ntex_multipart::form::Limits,
                 state: &'t mut ::ntex_multipart::form::State,
             ) -> ::std::pin::Pin<::std::boxed::Box<dyn ::std::future::Future<Output = ::std::result::Result<(), ::ntex_multipart::MultipartError>> + 't>> {
-                match field.name().unwrap() {
+                match field.form_field_name() {
                     #handle_field_impl
                     _ => return ::std::boxed::Box::pin(::std::future::ready(#unknown_field_result)),
                 }
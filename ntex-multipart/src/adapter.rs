@@ -0,0 +1,220 @@
+//! Stream adapters for [`Field`].
+
+use crate::{Field, MultipartError};
+#[cfg(feature = "digest")]
+use digest::Digest;
+use futures::Stream;
+use ntex::util::{Bytes, BytesMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A [`Field`] stream that feeds every chunk into a hasher.
+///
+/// Chunks are passed through unchanged. Created by [`Field::hashed`].
+#[cfg(feature = "digest")]
+pub struct HashingField<D> {
+    field: Field,
+    hasher: D,
+}
+
+#[cfg(feature = "digest")]
+impl<D: Digest> HashingField<D> {
+    pub(crate) fn new(field: Field, hasher: D) -> Self {
+        HashingField { field, hasher }
+    }
+
+    /// Returns the wrapped field.
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    /// Returns the digest of the data read so far.
+    ///
+    /// Call this after the stream has ended to get the digest of the whole field.
+    pub fn finish(self) -> Vec<u8> {
+        self.hasher.finalize().to_vec()
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D: Digest + Unpin> Stream for HashingField<D> {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = Pin::new(&mut this.field).poll_next(cx);
+        if let Poll::Ready(Some(Ok(ref chunk))) = item {
+            this.hasher.update(chunk);
+        }
+        item
+    }
+}
+
+/// A transformation applied by [`MapChunksField`] to every chunk of a field.
+///
+/// Implemented for closures taking and returning a chunk, and by the transformations behind
+/// [`Field::inspect`] and [`Field::with_max_chunks`].
+pub trait ChunkMap {
+    /// Transform `chunk`, an empty result is not yielded.
+    fn map(&mut self, chunk: Bytes) -> Result<Bytes, MultipartError>;
+}
+
+impl<F> ChunkMap for F
+where
+    F: FnMut(Bytes) -> Result<Bytes, MultipartError>,
+{
+    fn map(&mut self, chunk: Bytes) -> Result<Bytes, MultipartError> {
+        self(chunk)
+    }
+}
+
+/// A [`Field`] stream that transforms every chunk before yielding it.
+///
+/// If the transformation returns an error, the error is yielded and the stream ends. Chunks
+/// that come out empty are skipped. Created by [`Field::map_chunks`].
+///
+/// This is the extension point for adapters that map one chunk to at most one chunk. The
+/// [`HashingField`] and [`ChunkedField`] adapters are separate, the first has to hand its
+/// state back once the stream ended and the second turns one chunk into several.
+pub struct MapChunksField<F> {
+    field: Field,
+    map: F,
+    aborted: bool,
+}
+
+impl<F: ChunkMap> MapChunksField<F> {
+    pub(crate) fn new(field: Field, map: F) -> Self {
+        MapChunksField { field, map, aborted: false }
+    }
+
+    /// Returns the wrapped field.
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    /// Apply `g` to the chunks this stream yields, after the current transformation.
+    pub fn map_chunks<G>(
+        self,
+        mut g: G,
+    ) -> MapChunksField<impl FnMut(Bytes) -> Result<Bytes, MultipartError>>
+    where
+        G: FnMut(Bytes) -> Result<Bytes, MultipartError>,
+    {
+        let mut map = self.map;
+        MapChunksField::new(self.field, move |chunk| match map.map(chunk)? {
+            chunk if chunk.is_empty() => Ok(chunk),
+            chunk => g(chunk),
+        })
+    }
+}
+
+impl<F: ChunkMap + Unpin> Stream for MapChunksField<F> {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.aborted {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            return match Pin::new(&mut this.field).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => match this.map.map(chunk) {
+                    Ok(chunk) if chunk.is_empty() => continue,
+                    Ok(chunk) => Poll::Ready(Some(Ok(chunk))),
+                    Err(err) => {
+                        this.aborted = true;
+                        Poll::Ready(Some(Err(err)))
+                    }
+                },
+                item => item,
+            };
+        }
+    }
+}
+
+/// A [`Field`] stream that passes every chunk to an inspector before yielding it.
+///
+/// If the inspector returns an error, the error is yielded and the stream ends. Created by
+/// [`Field::inspect`].
+pub type InspectField<F> = MapChunksField<Inspect<F>>;
+
+/// Passes chunks through unchanged after showing them to an inspector, see [`InspectField`].
+pub struct Inspect<F>(pub(crate) F);
+
+impl<F> ChunkMap for Inspect<F>
+where
+    F: FnMut(&[u8]) -> Result<(), MultipartError>,
+{
+    fn map(&mut self, chunk: Bytes) -> Result<Bytes, MultipartError> {
+        (self.0)(&chunk)?;
+        Ok(chunk)
+    }
+}
+
+/// A [`Field`] stream that fails once the field yields more than a set number of chunks.
+///
+/// Created by [`Field::with_max_chunks`].
+pub type MaxChunksField = MapChunksField<MaxChunks>;
+
+/// Counts chunks against a maximum, see [`MaxChunksField`].
+pub struct MaxChunks {
+    pub(crate) remaining: usize,
+}
+
+impl ChunkMap for MaxChunks {
+    fn map(&mut self, chunk: Bytes) -> Result<Bytes, MultipartError> {
+        self.remaining = self.remaining.checked_sub(1).ok_or(MultipartError::TooManyChunks)?;
+        Ok(chunk)
+    }
+}
+
+/// A [`Field`] stream that re-chunks the field into pieces of a fixed size.
+///
+/// Every chunk is exactly `size` bytes long, except the last one which holds the rest.
+/// Created by [`Field::chunked`].
+pub struct ChunkedField {
+    field: Field,
+    size: usize,
+    buf: BytesMut,
+    eof: bool,
+}
+
+impl ChunkedField {
+    pub(crate) fn new(field: Field, size: usize) -> Self {
+        assert!(size > 0, "chunk size must be greater than zero");
+        ChunkedField { field, size, buf: BytesMut::new(), eof: false }
+    }
+
+    /// Returns the wrapped field.
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+}
+
+impl Stream for ChunkedField {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.buf.len() >= this.size {
+                return Poll::Ready(Some(Ok(this.buf.split_to(this.size))));
+            }
+            if this.eof {
+                return if this.buf.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Ok(std::mem::take(&mut this.buf).freeze())))
+                };
+            }
+
+            match Pin::new(&mut this.field).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.buf.extend_from_slice(&chunk),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => this.eof = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Decompression of a whole multipart body sent with a request-level Content-Encoding
+
+use crate::MultipartError;
+use futures::{Stream, StreamExt, stream::LocalBoxStream};
+use ntex::http::HeaderMap;
+use ntex::http::encoding::Decoder;
+use ntex::http::error::PayloadError;
+use ntex::http::header::{self, ContentEncoding};
+use ntex::util::Bytes;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Default for [`Multipart::max_decompressed_size`].
+///
+/// [`Multipart::max_decompressed_size`]: crate::Multipart::max_decompressed_size
+pub(crate) const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// State of the compressed stream feeding the decoder.
+#[derive(Debug, Default)]
+pub(crate) struct Source {
+    /// Compressed bytes received so far.
+    pub(crate) wire_bytes: Cell<usize>,
+    /// Set when the compressed stream itself failed, rather than its decoding.
+    failed: Cell<bool>,
+}
+
+/// Size accounting of a body decoded by ntex.
+pub(crate) struct Decompressed {
+    pub(crate) source: Rc<Source>,
+    /// Decompressed bytes produced so far.
+    size: usize,
+    pub(crate) limit: usize,
+}
+
+impl Decompressed {
+    /// Count a chunk produced by the decoder.
+    pub(crate) fn consume(&mut self, bytes: usize) -> Result<(), MultipartError> {
+        self.size += bytes;
+        if self.size > self.limit {
+            return Err(PayloadError::Overflow.into());
+        }
+        Ok(())
+    }
+
+    /// Convert an error of the decoded stream, telling decoding errors apart from the errors
+    /// of the compressed stream.
+    pub(crate) fn error(&self, err: PayloadError) -> MultipartError {
+        match err {
+            PayloadError::Io(err) if !self.source.failed.get() => {
+                MultipartError::Decompress(err)
+            }
+            err => err.into(),
+        }
+    }
+}
+
+/// Wrap `stream` with the ntex decoder for the Content-Encoding of `headers`.
+///
+/// Returns the stream as is and no accounting if the body is not compressed.
+pub(crate) fn decode<S>(
+    headers: &HeaderMap,
+    stream: S,
+) -> (LocalBoxStream<'static, Result<Bytes, PayloadError>>, Option<Decompressed>)
+where
+    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
+{
+    let compressed = headers
+        .get(&header::CONTENT_ENCODING)
+        .and_then(|hv| hv.to_str().ok())
+        .is_some_and(|encoding| ContentEncoding::from(encoding).is_compressed());
+    if !compressed {
+        return (stream.boxed_local(), None);
+    }
+
+    let source = Rc::new(Source::default());
+    let state = source.clone();
+    let stream = stream.map(move |item| {
+        match item {
+            Ok(ref chunk) => state.wire_bytes.set(state.wire_bytes.get() + chunk.len()),
+            Err(_) => state.failed.set(true),
+        }
+        item
+    });
+    let decompressed = Decompressed { source, size: 0, limit: DEFAULT_MAX_DECOMPRESSED_SIZE };
+
+    (Decoder::from_headers(stream, headers).boxed_local(), Some(decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Multipart, MultipartError};
+    use flate2::Compression;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use futures::{StreamExt, stream};
+    use ntex::http::error::PayloadError;
+    use ntex::http::header::{self, HeaderMap, HeaderValue};
+    use ntex::util::Bytes;
+    use std::io::Write;
+
+    const BODY: &[u8] = b"--abbc\r\n\
+        Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+        compressed\r\n\
+        --abbc--\r\n";
+
+    fn headers(encoding: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+        );
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        headers
+    }
+
+    fn encoded(encoding: &'static str, body: Vec<u8>) -> Multipart {
+        let chunks: Vec<_> = body.chunks(5).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        Multipart::new(&headers(encoding), stream::iter(chunks))
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[ntex::test]
+    async fn test_decompress() {
+        let mut multipart = encoded("gzip", gzip(BODY));
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("a"));
+        assert_eq!(field.next().await.unwrap().unwrap(), "compressed");
+        assert!(field.next().await.is_none());
+        drop(field);
+        assert!(multipart.next().await.is_none());
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        let mut multipart = encoded("deflate", encoder.finish().unwrap());
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.next().await.unwrap().unwrap(), "compressed");
+    }
+
+    #[ntex::test]
+    async fn test_decompress_errors() {
+        let mut multipart = encoded("gzip", BODY.to_vec());
+        let res = multipart.next().await.unwrap();
+        assert!(matches!(res, Err(MultipartError::Decompress(_))));
+
+        // errors of the compressed stream are not decoding errors
+        let chunks = vec![Err(PayloadError::Io(std::io::Error::other("reset")))];
+        let mut multipart = Multipart::new(&headers("gzip"), stream::iter(chunks));
+        let res = multipart.next().await.unwrap();
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Io(_)))));
+
+        let mut body = b"--abbc\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n".to_vec();
+        body.extend(std::iter::repeat_n(b'x', 4096));
+        body.extend(b"\r\n--abbc--\r\n");
+        let mut multipart = encoded("gzip", gzip(&body)).max_decompressed_size(1024);
+        let mut res = Ok(());
+        while let Some(item) = multipart.next().await {
+            match item {
+                Ok(mut field) => {
+                    while let Some(chunk) = field.next().await {
+                        if let Err(err) = chunk {
+                            res = Err(err);
+                            break;
+                        }
+                    }
+                }
+                Err(err) => res = Err(err),
+            }
+            if res.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
+}
@@ -32,14 +32,94 @@ pub enum MultipartError {
     #[display("Content-Disposition header was not found when parsing a \"form-data\" field")]
     ContentDispositionNameMissing,
 
-    /// Nested multipart is not supported
+    /// Field name is not valid UTF-8 or contains control characters, see
+    /// [`Multipart::validate_field_names`]
+    ///
+    /// [`Multipart::validate_field_names`]: crate::Multipart::validate_field_names
+    #[display("Field name contains control characters or invalid UTF-8")]
+    InvalidFieldName,
+
+    /// Part has a nested multipart body, which is only parsed with
+    /// [`Multipart::allow_nested`]
+    ///
+    /// [`Multipart::allow_nested`]: crate::Multipart::allow_nested
     #[display("Nested multipart is not supported")]
     Nested,
 
+    /// Part declared a multipart content type without a boundary parameter
+    #[display("Nested multipart part has no boundary")]
+    NestedBoundaryMissing,
+
+    /// Nested multipart exceeds [`Multipart::max_nesting_depth`]
+    ///
+    /// [`Multipart::max_nesting_depth`]: crate::Multipart::max_nesting_depth
+    #[display("Nested multipart is too deep")]
+    NestingTooDeep,
+
+    /// Part body contains the boundary within its declared `Content-Length`, see
+    /// [`Multipart::reject_boundary_in_body`]
+    ///
+    /// [`Multipart::reject_boundary_in_body`]: crate::Multipart::reject_boundary_in_body
+    #[display("Part body contains the multipart boundary")]
+    BoundaryInBody,
+
+    /// Multipart stream did not contain any part
+    #[display("Multipart stream has no parts")]
+    Empty,
+
     /// Multipart stream is incomplete
     #[display("Multipart stream is incomplete")]
     Incomplete,
 
+    /// Part has more headers than allowed by [`Multipart::max_headers_per_part`]
+    ///
+    /// [`Multipart::max_headers_per_part`]: crate::Multipart::max_headers_per_part
+    #[display("Part header count exceeds the configured limit")]
+    HeaderCountExceeded,
+
+    /// Part has more headers than the parser has room for, 32 unless raised with
+    /// [`Multipart::max_headers_per_part`]
+    ///
+    /// [`Multipart::max_headers_per_part`]: crate::Multipart::max_headers_per_part
+    #[display("Part has too many headers")]
+    TooManyPartHeaders,
+
+    /// Part header value is longer than allowed by [`Multipart::max_header_value_len`]
+    ///
+    /// [`Multipart::max_header_value_len`]: crate::Multipart::max_header_value_len
+    #[display("Part header value exceeds the configured length limit")]
+    HeaderValueTooLong,
+
+    /// Part header has a quoted parameter value that is not closed before the end of its line
+    #[display("Part header has an unterminated quoted value")]
+    UnterminatedQuote,
+
+    /// Field yielded more chunks than allowed by [`Field::with_max_chunks`]
+    ///
+    /// [`Field::with_max_chunks`]: crate::Field::with_max_chunks
+    #[display("Field exceeded the maximum number of chunks")]
+    TooManyChunks,
+
+    /// Form had more empty parts than allowed by [`MultipartFormConfig::max_empty_parts`]
+    ///
+    /// [`MultipartFormConfig::max_empty_parts`]: crate::MultipartFormConfig::max_empty_parts
+    #[display("Too many empty parts")]
+    TooManyEmptyParts,
+
+    /// Part declared a `Content-Length` larger than the remaining form limits
+    #[display("Field {name} declares {declared} bytes but only {remaining} are allowed")]
+    #[from(ignore)]
+    FieldSizeExceeded {
+        /// The form field name.
+        name: String,
+
+        /// The declared length.
+        declared: u64,
+
+        /// The bytes left within the total and field limits.
+        remaining: usize,
+    },
+
     /// Error during field parsing
     #[display("{}", _0)]
     Decode(DecodeError),
@@ -52,6 +132,22 @@ pub enum MultipartError {
     #[display("Multipart stream is not consumed")]
     NotConsumed,
 
+    /// Multipart stream was not parsed within the configured timeout
+    #[display("Multipart stream timed out")]
+    Timeout,
+
+    /// Request body could not be decompressed, see [`Multipart::max_decompressed_size`]
+    ///
+    /// [`Multipart::max_decompressed_size`]: crate::Multipart::max_decompressed_size
+    #[display("Failed to decompress the request body: {}", _0)]
+    #[from(ignore)]
+    Decompress(std::io::Error),
+
+    /// Writing a field to its destination failed
+    #[display("Failed to write field: {}", _0)]
+    #[from(ignore)]
+    Write(std::io::Error),
+
     /// Form field handler raised error.
     #[display("An error occurred processing field: {}", name)]
     Field { name: String, source: ntex::web::Error },
@@ -66,16 +162,57 @@ pub enum MultipartError {
     #[from(ignore)]
     MissingField(#[error(not(source))] String),
 
+    /// Processed field does not have the type expected by its form field.
+    #[display("Field has an unexpected type: {}", _0)]
+    #[from(ignore)]
+    FieldTypeMismatch(#[error(not(source))] String),
+
+    /// Part does not match the kind of its form field, see
+    /// [`MultipartFormConfig::reject_inconsistent_parts`]
+    ///
+    /// [`MultipartFormConfig::reject_inconsistent_parts`]: crate::MultipartFormConfig::reject_inconsistent_parts
+    #[display("Field {name} {reason}")]
+    #[from(ignore)]
+    InconsistentPart {
+        /// The form field name.
+        name: String,
+
+        /// What does not match.
+        reason: &'static str,
+    },
+
     /// Unknown field (for structure that opted-in to denying unknown fields).
     #[display("Unknown field: {}", _0)]
     #[from(ignore)]
     UnknownField(#[error(not(source))] String),
 }
 
-/// Return `BadRequest` for `MultipartError`
+impl MultipartError {
+    /// Convert into a web error, keeping the status code of [`WebResponseError::status_code`].
+    ///
+    /// Useful in handlers returning `Result<_, ntex::web::Error>` of apps with a custom error
+    /// renderer, where `?` would otherwise not pick up the refined status.
+    pub fn into_web_error(self) -> ntex::web::Error {
+        ntex::web::Error::new(self)
+    }
+}
+
+/// Return `PayloadTooLarge` for exceeded limits, `UnsupportedMediaType` for an unusable
+/// Content-Type, `RequestTimeout` for timeouts, `InternalServerError` for write errors, the
+/// status of the source for field errors and `BadRequest` otherwise.
 impl WebResponseError<DefaultError> for MultipartError {
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        match self {
+            MultipartError::Payload(PayloadError::Overflow)
+            | MultipartError::FieldSizeExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            MultipartError::NoContentType
+            | MultipartError::ParseContentType
+            | MultipartError::IncompatibleContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            MultipartError::Timeout => StatusCode::REQUEST_TIMEOUT,
+            MultipartError::Write(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MultipartError::Field { source, .. } => source.as_response_error().status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
     }
 }
 
@@ -91,4 +228,40 @@ mod tests {
         let resp: HttpResponse = MultipartError::Boundary.error_response(&req);
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
+
+    #[test]
+    fn test_multipart_error_status() {
+        let cases = [
+            (MultipartError::Payload(PayloadError::Overflow), StatusCode::PAYLOAD_TOO_LARGE),
+            (MultipartError::IncompatibleContentType, StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (MultipartError::NoContentType, StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (MultipartError::Timeout, StatusCode::REQUEST_TIMEOUT),
+            (
+                MultipartError::Write(std::io::ErrorKind::BrokenPipe.into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                MultipartError::FieldSizeExceeded {
+                    name: "file".to_owned(),
+                    declared: 2,
+                    remaining: 1,
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            (MultipartError::Incomplete, StatusCode::BAD_REQUEST),
+            (
+                MultipartError::Field {
+                    name: "file".to_owned(),
+                    source: MultipartError::Payload(PayloadError::Overflow).into(),
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ];
+
+        let req = TestRequest::default().to_http_request();
+        for (err, status) in cases {
+            let resp = err.into_web_error().as_response_error().error_response(&req);
+            assert_eq!(resp.status(), status);
+        }
+    }
 }
@@ -6,11 +6,18 @@ use ntex::web::{ErrorRenderer, FromRequest, HttpRequest};
 use std::convert::Infallible;
 #[cfg(feature = "form")]
 use {
-    crate::form::{Limits, State},
-    crate::multipart_form::MultipartFormConfig,
-    crate::{MultipartCollect, MultipartError, MultipartForm},
-    futures::TryStreamExt,
-    std::collections::HashMap,
+    crate::form::{FieldKind, FormContext, Limits, State},
+    crate::multipart_form::{FieldOrder, FormStats, MultipartFormConfig, TruncatedFields},
+    crate::{
+        Field, MultipartCollect, MultipartError, MultipartForm, MultipartFormWithRaw,
+        MultipartFormWithStats, field::BodySize,
+    },
+    futures::{Stream, TryStreamExt},
+    ntex::http::HeaderMap,
+    ntex::http::error::PayloadError,
+    ntex::http::header::{self, HeaderValue},
+    ntex::util::{Bytes as NtexBytes, BytesMut},
+    std::collections::{BTreeSet, HashMap},
 };
 
 /// Get request's payload as multipart stream
@@ -66,45 +73,1021 @@ where
         req: &HttpRequest,
         payload: &mut Payload,
     ) -> Result<Self, Self::Error> {
-        let mut multipart = Multipart::new(req.headers(), payload.take());
+        collect_form(&FormContext::from_req(req), req.headers(), payload, None, None)
+            .await
+            .map(MultipartForm)
+    }
+}
+
+#[cfg(feature = "form")]
+impl<T, Err> FromRequest<Err> for MultipartFormWithRaw<T>
+where
+    T: MultipartCollect + 'static,
+    Err: ErrorRenderer,
+{
+    type Error = MultipartError;
+
+    #[inline]
+    async fn from_request(
+        req: &HttpRequest,
+        payload: &mut Payload,
+    ) -> Result<Self, Self::Error> {
+        let mut raw = Vec::new();
+        let ctx = FormContext::from_req(req);
+        let form = collect_form(&ctx, req.headers(), payload, Some(&mut raw), None).await?;
+        Ok(MultipartFormWithRaw { form, raw })
+    }
+}
+
+#[cfg(feature = "form")]
+impl<T, Err> FromRequest<Err> for MultipartFormWithStats<T>
+where
+    T: MultipartCollect + 'static,
+    Err: ErrorRenderer,
+{
+    type Error = MultipartError;
+
+    #[inline]
+    async fn from_request(
+        req: &HttpRequest,
+        payload: &mut Payload,
+    ) -> Result<Self, Self::Error> {
+        let mut stats = FormStats::default();
+        let ctx = FormContext::from_req(req);
+        let form = collect_form(&ctx, req.headers(), payload, None, Some(&mut stats)).await?;
+        Ok(MultipartFormWithStats { form, stats })
+    }
+}
+
+/// Parse a multipart form from `body` outside of a request handler.
+///
+/// Runs the same collection as the [`struct@MultipartForm`] extractor, e.g. for background
+/// jobs processing stored uploads. `content_type` is the full Content-Type of the body
+/// including its boundary, and `config` is applied as if it was registered as app state.
+/// Use [`parse_form_with`] to also pass configs of field readers.
+///
+/// No request is involved, so the configs are only taken from the arguments and nothing is
+/// reported in request extensions, such as [`TruncatedFields`].
+///
+/// ```rust,ignore
+/// let form: Upload = ntex_multipart::parse_form(&content_type, stream, config).await?;
+/// ```
+#[cfg(feature = "form")]
+pub async fn parse_form<T, S>(
+    content_type: &str,
+    body: S,
+    config: MultipartFormConfig,
+) -> Result<T, MultipartError>
+where
+    T: MultipartCollect,
+    S: Stream<Item = Result<NtexBytes, PayloadError>> + 'static,
+{
+    parse_form_with(content_type, body, FormContext::new().with_config(config)).await
+}
+
+/// Parse a multipart form from `body` with the configs of `ctx`, see [`parse_form`].
+///
+/// A [`MultipartFormConfig`] in `ctx` configures the form collection, other configs are used
+/// by the field readers, e.g. a [`TextConfig`](crate::form::text::TextConfig).
+#[cfg(feature = "form")]
+pub async fn parse_form_with<T, S>(
+    content_type: &str,
+    body: S,
+    ctx: FormContext,
+) -> Result<T, MultipartError>
+where
+    T: MultipartCollect,
+    S: Stream<Item = Result<NtexBytes, PayloadError>> + 'static,
+{
+    let mut headers = HeaderMap::new();
+    let content_type =
+        HeaderValue::from_str(content_type).map_err(|_| MultipartError::ParseContentType)?;
+    headers.insert(header::CONTENT_TYPE, content_type);
+
+    let mut payload = Payload::from_stream(body);
+    collect_form(&ctx, &headers, &mut payload, None, None).await
+}
+
+/// Run the form collection, also keeping the content of every part in `raw` and recording
+/// part sizes in `stats` if given.
+#[cfg(feature = "form")]
+async fn collect_form<T: MultipartCollect>(
+    ctx: &FormContext,
+    headers: &HeaderMap,
+    payload: &mut Payload,
+    raw: Option<&mut Vec<(String, NtexBytes)>>,
+    stats: Option<&mut FormStats>,
+) -> Result<T, MultipartError> {
+    let res = collect_parts(ctx, headers, payload, raw, stats).await;
 
-        let content_type = match multipart.content_type() {
-            Ok(content_type) => content_type,
+    // errors are counted here once, whether they come from the stream or the form
+    #[cfg(feature = "metrics")]
+    if let Err(ref err) = res
+        && let Some(ref metrics) = MultipartFormConfig::from_ctx(ctx).metrics
+    {
+        metrics.record_error(err);
+    }
+
+    res
+}
+
+#[cfg(feature = "form")]
+async fn collect_parts<T: MultipartCollect>(
+    ctx: &FormContext,
+    headers: &HeaderMap,
+    payload: &mut Payload,
+    mut raw: Option<&mut Vec<(String, NtexBytes)>>,
+    mut stats: Option<&mut FormStats>,
+) -> Result<T, MultipartError> {
+    let config = MultipartFormConfig::from_ctx(ctx);
+    let mut multipart = config.multipart(headers, payload);
+
+    let content_type = match multipart.content_type() {
+        Ok(content_type) => content_type,
+        Err(err) => return Err(err),
+    };
+
+    if content_type.subtype() != mime::FORM_DATA {
+        // this extractor only supports multipart/form-data
+        return Err(MultipartError::IncompatibleContentType);
+    };
+
+    let mut limits = config.limits(&multipart);
+    let mut state = if config.ordered_state { State::ordered() } else { State::default() };
+
+    // ensure limits are shared for all fields with this name
+    let mut field_limits = HashMap::<String, Option<usize>>::new();
+    let mut truncated = Vec::<String>::new();
+    let mut empty_parts = 0;
+    let mut finish_part = |name: String, body_size: BodySize| {
+        let size = body_size.get();
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.parts += 1;
+            stats.total_bytes += size as usize;
+            stats.field_sizes.push((name, size as usize));
+        }
+
+        if let Some(max_empty_parts) = config.max_empty_parts
+            && size <= config.empty_part_size as u64
+        {
+            empty_parts += 1;
+            if empty_parts > max_empty_parts {
+                return Err(MultipartError::TooManyEmptyParts);
+            }
+        }
+        Ok(())
+    };
+    let mut previous = None;
+    let mut received = BTreeSet::<String>::new();
+
+    while let Some(mut field) = multipart.try_next().await? {
+        // the previous part has been read or skipped by now
+        if let Some((name, body_size)) = previous.take() {
+            finish_part(name, body_size)?;
+        }
+
+        config.prepare_field(&mut field);
+        previous = Some((field.form_field_name.clone(), field.body_size()));
+
+        if config.field_validator.is_some() && !received.contains(&field.form_field_name) {
+            received.insert(field.form_field_name.clone());
+        }
+
+        if truncated.contains(&field.form_field_name) {
+            continue;
+        }
+
+        debug_assert!(
+            !field.form_field_name.is_empty(),
+            "multipart form fields should have names",
+        );
+
+        if config.reject_inconsistent_parts {
+            let has_filename =
+                field.content_disposition().is_some_and(|cd| cd.get_filename().is_some());
+            let reason = match T::field_kind(&field.form_field_name) {
+                FieldKind::Data if has_filename => Some("has a filename but expects data"),
+                FieldKind::File if !has_filename => Some("has no filename but expects a file"),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                return Err(MultipartError::InconsistentPart {
+                    name: field.form_field_name,
+                    reason,
+                });
+            }
+        }
+
+        // Retrieve the limit for this field
+        let entry = field_limits
+            .entry(field.form_field_name.clone())
+            .or_insert_with(|| T::limit(&field.form_field_name));
+
+        limits.field_limit_remaining.clone_from(entry);
+        limits.field_limit_exceeded = false;
+
+        // reject parts that announce they won't fit, into either budget with separate limits,
+        // before reading any of their data, a truncated field may still overflow its own limit
+        let limit_remaining = if limits.separate_memory_limit {
+            limits.total_limit_remaining.max(limits.memory_limit_remaining)
+        } else {
+            limits.total_limit_remaining
+        };
+        let remaining = match limits.field_limit_remaining {
+            Some(field_remaining) if !config.partial_on_limit => {
+                field_remaining.min(limit_remaining)
+            }
+            _ => limit_remaining,
+        };
+        if let Some(declared) = field.content_length()
+            && declared > remaining as u64
+        {
+            return Err(MultipartError::FieldSizeExceeded {
+                name: field.form_field_name,
+                declared,
+                remaining,
+            });
+        }
+
+        let name = field.form_field_name.clone();
+        let res = match raw.as_deref_mut() {
+            Some(raw) => handle_raw::<T>(ctx, field, &mut limits, &mut state, raw).await,
+            None => T::handle_field(ctx, field, &mut limits, &mut state).await,
+        };
+        state.settle_usage(&mut limits);
+        match res {
+            Ok(()) => {}
+            Err(MultipartError::Payload(PayloadError::Overflow))
+                if config.partial_on_limit && limits.field_limit_exceeded =>
+            {
+                log::warn!("Field limit exceeded, skipping the rest of field {name:?}");
+                limits.field_limit_exceeded = false;
+                truncated.push(name);
+                continue;
+            }
             Err(err) => return Err(err),
+        }
+
+        // Update the stored limit
+        *entry = limits.field_limit_remaining;
+
+        state.record_arrival(&name);
+    }
+    if let Some((name, body_size)) = previous {
+        finish_part(name, body_size)?;
+    }
+
+    if !truncated.is_empty()
+        && let Some(req) = ctx.request()
+    {
+        req.extensions_mut().insert(TruncatedFields(truncated));
+    }
+
+    if let Some(order) = state.arrival_order()
+        && let Some(req) = ctx.request()
+    {
+        req.extensions_mut().insert(FieldOrder(order.to_vec()));
+    }
+
+    if let Some(validator) = &config.field_validator {
+        let names: Vec<&str> = received.iter().map(String::as_str).collect();
+        validator(&names)?;
+    }
+
+    T::from_state(state)
+}
+
+/// Read the field into `raw`, then hand a copy of it to the form.
+#[cfg(feature = "form")]
+async fn handle_raw<T: MultipartCollect>(
+    ctx: &FormContext,
+    mut field: Field,
+    limits: &mut Limits,
+    state: &mut State,
+    raw: &mut Vec<(String, NtexBytes)>,
+) -> Result<(), MultipartError> {
+    // the raw copy only counts against the total limit, so the memory limit is charged once,
+    // by the form readers that keep the part in memory
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = field.try_next().await? {
+        limits.try_consume_limits(chunk.len(), false)?;
+        buf.extend_from_slice(&chunk);
+    }
+    let data = buf.freeze();
+    raw.push((field.form_field_name.clone(), data.clone()));
+
+    // the part was charged while reading it, the form copy only adds to the memory in use
+    let total_limit_remaining = limits.total_limit_remaining;
+    let field_limit_remaining = limits.field_limit_remaining.take();
+    let consumed = limits.consumed;
+    limits.total_limit_remaining = usize::MAX;
+
+    let res = T::handle_field(ctx, field.replay(data), limits, state).await;
+
+    limits.total_limit_remaining = total_limit_remaining;
+    limits.field_limit_remaining = field_limit_remaining;
+    limits.consumed = consumed;
+    res
+}
+
+#[cfg(all(test, feature = "form"))]
+mod tests {
+    use super::*;
+    use crate::form::{FieldGroupReader, bytes::Bytes};
+    use futures::future::LocalBoxFuture;
+    use ntex::web::{DefaultError, test::TestRequest};
+
+    /// Hand-written equivalent of a derived form with `#[multipart(limit = "8B")]` files.
+    struct Upload {
+        files: Vec<Bytes>,
+    }
+
+    impl MultipartCollect for Upload {
+        fn limit(field_name: &str) -> Option<usize> {
+            (field_name == "files").then_some(8)
+        }
+
+        fn handle_field<'t>(
+            ctx: &'t FormContext,
+            field: crate::Field,
+            limits: &'t mut Limits,
+            state: &'t mut State,
+        ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+            <Vec<Bytes> as FieldGroupReader>::handle_field(
+                ctx,
+                field,
+                limits,
+                state,
+                crate::form::DuplicateField::Ignore,
+            )
+        }
+
+        fn from_state(mut state: State) -> Result<Self, MultipartError> {
+            Ok(Upload {
+                files: <Vec<Bytes> as FieldGroupReader>::from_state("files", &mut state)?,
+            })
+        }
+    }
+
+    fn upload_request(config: MultipartFormConfig) -> (HttpRequest, Payload) {
+        upload_request_named(config, "files")
+    }
+
+    fn upload_request_named(config: MultipartFormConfig, name: &str) -> (HttpRequest, Payload) {
+        TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(
+                    "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+                ),
+            )
+            .state(config)
+            .set_payload(format!(
+                "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"{name}\"; filename=\"a.txt\"\r\n\r\n\
+                 aaaa\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"{name}\"; filename=\"b.txt\"\r\n\r\n\
+                 bbbbbb\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"{name}\"; filename=\"c.txt\"\r\n\r\n\
+                 c\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+            ))
+            .to_http_parts()
+    }
+
+    #[cfg(feature = "metrics")]
+    #[ntex::test]
+    async fn test_metrics() {
+        let metrics = crate::MultipartMetrics::new();
+        let config = MultipartFormConfig::default().metrics(metrics.clone());
+
+        let (req, mut payload) = upload_request(config);
+        let res = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+        assert_eq!(metrics.parts_parsed(), 2);
+        assert!(metrics.bytes_consumed() > 0);
+        assert_eq!(metrics.limit_hits(), 1);
+        assert_eq!(metrics.rejected(crate::RejectReason::Form), 0);
+    }
+
+    #[ntex::test]
+    async fn test_parse_form() {
+        let body = "--abbc\r\n\
+                    Content-Disposition: form-data; name=\"files\"; filename=\"a.txt\"\r\n\r\n\
+                    aaaa\r\n\
+                    --abbc\r\n\
+                    Content-Disposition: form-data; name=\"files\"; filename=\"b.txt\"\r\n\r\n\
+                    bb\r\n\
+                    --abbc--\r\n";
+        let chunks: Vec<_> =
+            body.as_bytes().chunks(5).map(|c| Ok(NtexBytes::copy_from_slice(c))).collect();
+
+        let form: Upload = parse_form(
+            "multipart/form-data; boundary=abbc",
+            futures::stream::iter(chunks.clone()),
+            MultipartFormConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.files.len(), 2);
+        assert_eq!(form.files[1].data, "bb");
+
+        let res = parse_form::<Upload, _>(
+            "multipart/form-data; boundary=abbc",
+            futures::stream::iter(chunks),
+            MultipartFormConfig::default().total_limit(4),
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
+
+    #[ntex::test]
+    async fn test_validate_fields() {
+        let body = "--abbc\r\n\
+                    Content-Disposition: form-data; name=\"files\"; filename=\"a.txt\"\r\n\r\n\
+                    aaaa\r\n\
+                    --abbc\r\n\
+                    Content-Disposition: form-data; name=\"notes\"\r\n\r\n\
+                    n\r\n\
+                    --abbc\r\n\
+                    Content-Disposition: form-data; name=\"files\"; filename=\"b.txt\"\r\n\r\n\
+                    bb\r\n\
+                    --abbc--\r\n";
+        let parse = |config| {
+            parse_form::<Upload, _>(
+                "multipart/form-data; boundary=abbc",
+                futures::stream::once(async { Ok(NtexBytes::from_static(body.as_bytes())) }),
+                config,
+            )
         };
 
-        if content_type.subtype() != mime::FORM_DATA {
-            // this extractor only supports multipart/form-data
-            return Err(MultipartError::IncompatibleContentType);
+        let config = MultipartFormConfig::default().validate_fields(|names| {
+            assert_eq!(names, ["files", "notes"]);
+            Ok(())
+        });
+        assert_eq!(parse(config).await.unwrap().files.len(), 2);
+
+        let config = MultipartFormConfig::default().validate_fields(|names| {
+            if names.contains(&"email") {
+                Ok(())
+            } else {
+                Err(MultipartError::MissingField("email".to_owned()))
+            }
+        });
+        let res = parse(config).await;
+        assert!(matches!(res, Err(MultipartError::MissingField(name)) if name == "email"));
+    }
+
+    #[ntex::test]
+    async fn test_partial_on_limit() {
+        let (req, mut payload) = upload_request(MultipartFormConfig::default());
+        let res = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+
+        let (req, mut payload) =
+            upload_request(MultipartFormConfig::default().partial_on_limit(true));
+        let form = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.files.len(), 1);
+        assert_eq!(form.files[0].data, "aaaa");
+
+        let extensions = req.extensions();
+        let truncated = extensions.get::<TruncatedFields>().unwrap();
+        assert_eq!(truncated.0, ["files"]);
+    }
+
+    #[ntex::test]
+    async fn test_trim_field_names() {
+        let (req, mut payload) =
+            upload_request_named(MultipartFormConfig::default(), " files ");
+        let form = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert!(form.files.is_empty());
+
+        let config =
+            MultipartFormConfig::default().trim_field_names(true).partial_on_limit(true);
+        let (req, mut payload) = upload_request_named(config, " files ");
+        let form = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.files.len(), 1);
+
+        let extensions = req.extensions();
+        let truncated = extensions.get::<TruncatedFields>().unwrap();
+        assert_eq!(truncated.0, ["files"]);
+    }
+
+    /// Form that only records the order its fields arrived in.
+    struct Ordered {
+        order: Option<Vec<String>>,
+    }
+
+    impl MultipartCollect for Ordered {
+        fn limit(_field_name: &str) -> Option<usize> {
+            None
+        }
+
+        fn handle_field<'t>(
+            ctx: &'t FormContext,
+            field: crate::Field,
+            limits: &'t mut Limits,
+            state: &'t mut State,
+        ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+            <Vec<Bytes> as FieldGroupReader>::handle_field(
+                ctx,
+                field,
+                limits,
+                state,
+                crate::form::DuplicateField::Ignore,
+            )
+        }
+
+        fn from_state(state: State) -> Result<Self, MultipartError> {
+            Ok(Ordered { order: state.arrival_order().map(<[String]>::to_vec) })
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[ntex::test]
+    async fn test_decompressed_limits() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let request = |config: MultipartFormConfig| {
+            let mut body =
+                b"--abbc\r\nContent-Disposition: form-data; name=\"doc\"\r\n\r\n".to_vec();
+            body.extend(std::iter::repeat_n(b'x', 4096));
+            body.extend(b"\r\n--abbc--\r\n");
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&body).unwrap();
+
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .header(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"))
+                .state(config)
+                .set_payload(encoder.finish().unwrap())
+                .to_http_parts()
         };
+        let extract = |config| async move {
+            let (req, mut payload) = request(config);
+            <MultipartForm<Ordered> as FromRequest<DefaultError>>::from_request(
+                &req,
+                &mut payload,
+            )
+            .await
+        };
+
+        extract(MultipartFormConfig::default()).await.unwrap();
 
-        let config = MultipartFormConfig::from_req(req);
-        let mut limits = Limits::new(config.total_limit, config.memory_limit);
-        let mut state = State::default();
+        // the decompressed size is capped regardless of how the other limits are counted
+        for count_decompressed in [true, false] {
+            let config = MultipartFormConfig::default()
+                .decompressed_limit(1024)
+                .count_decompressed(count_decompressed);
+            let res = extract(config).await;
+            assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+        }
 
-        // ensure limits are shared for all fields with this name
-        let mut field_limits = HashMap::<String, Option<usize>>::new();
+        // the compressed body fits the total limit, its content does not
+        let config = MultipartFormConfig::default().total_limit(1024);
+        let res = extract(config.clone()).await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+        extract(config.count_decompressed(false)).await.unwrap();
+    }
 
-        while let Some(field) = multipart.try_next().await? {
-            debug_assert!(
-                !field.form_field_name.is_empty(),
-                "multipart form fields should have names",
-            );
+    #[ntex::test]
+    async fn test_ordered_state() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; name=\"mode\"\r\n\r\n\
+                     resize\r\n\
+                     --abbc\r\n\
+                     Content-Disposition: form-data; name=\"file\"\r\n\r\n\
+                     data\r\n\
+                     --abbc\r\n\
+                     Content-Disposition: form-data; name=\"mode\"\r\n\r\n\
+                     crop\r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
 
-            // Retrieve the limit for this field
-            let entry = field_limits
-                .entry(field.form_field_name.clone())
-                .or_insert_with(|| T::limit(&field.form_field_name));
+        let (req, mut payload) = request(MultipartFormConfig::default());
+        let form = <MultipartForm<Ordered> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.order, None);
+        assert!(req.extensions().get::<FieldOrder>().is_none());
 
-            limits.field_limit_remaining.clone_from(entry);
+        let (req, mut payload) = request(MultipartFormConfig::default().ordered_state(true));
+        let form = <MultipartForm<Ordered> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.order.as_deref().unwrap(), ["mode", "file", "mode"]);
+        assert_eq!(req.extensions().get::<FieldOrder>().unwrap().0, ["mode", "file", "mode"]);
+    }
 
-            T::handle_field(&req, field, &mut limits, &mut state).await?;
+    /// Form that skips all of its fields without reading them.
+    struct Ignored;
 
-            // Update the stored limit
-            *entry = limits.field_limit_remaining;
+    impl MultipartCollect for Ignored {
+        fn limit(_field_name: &str) -> Option<usize> {
+            None
         }
 
-        let inner = T::from_state(state)?;
-        Ok(MultipartForm(inner))
+        fn handle_field<'t>(
+            _: &'t FormContext,
+            _: crate::Field,
+            _: &'t mut Limits,
+            _: &'t mut State,
+        ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn from_state(_: State) -> Result<Self, MultipartError> {
+            Ok(Ignored)
+        }
+    }
+
+    #[ntex::test]
+    async fn test_max_empty_parts() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+                     \r\n\
+                     --abbc\r\n\
+                     Content-Disposition: form-data; name=\"b\"\r\n\r\n\
+                     x\r\n\
+                     --abbc\r\n\
+                     Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+                     \r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
+
+        for (config, ok) in [
+            (MultipartFormConfig::default(), true),
+            (MultipartFormConfig::default().max_empty_parts(2), true),
+            (MultipartFormConfig::default().max_empty_parts(1), false),
+            (MultipartFormConfig::default().max_empty_parts(2).empty_part_size(1), false),
+        ] {
+            let (req, mut payload) = request(config);
+            let res = <MultipartForm<Ordered> as FromRequest<DefaultError>>::from_request(
+                &req,
+                &mut payload,
+            )
+            .await;
+            if ok {
+                assert!(res.is_ok());
+            } else {
+                assert!(matches!(res, Err(MultipartError::TooManyEmptyParts)));
+            }
+        }
+
+        // parts are measured on the wire, whether or not the form reads them
+        for (config, ok) in [
+            (MultipartFormConfig::default().max_empty_parts(2), true),
+            (MultipartFormConfig::default().max_empty_parts(1), false),
+        ] {
+            let (req, mut payload) = request(config);
+            let res = <MultipartForm<Ignored> as FromRequest<DefaultError>>::from_request(
+                &req,
+                &mut payload,
+            )
+            .await;
+            assert_eq!(res.is_ok(), ok);
+        }
+    }
+
+    #[ntex::test]
+    async fn test_multipart_form_with_raw() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; name=\"mode\"\r\n\r\n\
+                     resize\r\n\
+                     --abbc\r\n\
+                     Content-Disposition: form-data; name=\"file\"\r\n\r\n\
+                     data\r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
+
+        let config = MultipartFormConfig::default().ordered_state(true).memory_limit(20);
+        let (req, mut payload) = request(config);
+        let form = <MultipartFormWithRaw<Ordered> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.form.order.as_deref().unwrap(), ["mode", "file"]);
+        assert_eq!(form.raw.len(), 2);
+        assert_eq!((form.raw[0].0.as_str(), &form.raw[0].1[..]), ("mode", &b"resize"[..]));
+        assert_eq!((form.raw[1].0.as_str(), &form.raw[1].1[..]), ("file", &b"data"[..]));
+
+        // only the typed copy counts against the memory limit, the raw copy against the total
+        let (req, mut payload) = request(MultipartFormConfig::default().memory_limit(10));
+        let res = <MultipartFormWithRaw<Ordered> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        assert!(res.is_ok());
+
+        for config in [
+            MultipartFormConfig::default().memory_limit(9),
+            MultipartFormConfig::default().total_limit(9),
+        ] {
+            let (req, mut payload) = request(config);
+            let res =
+                <MultipartFormWithRaw<Ordered> as FromRequest<DefaultError>>::from_request(
+                    &req,
+                    &mut payload,
+                )
+                .await;
+            assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+        }
+    }
+
+    #[ntex::test]
+    async fn test_multipart_form_with_stats() {
+        let (req, mut payload) = upload_request_named(MultipartFormConfig::default(), "doc");
+        let form = <MultipartFormWithStats<Ordered> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            form.stats,
+            FormStats {
+                total_bytes: 11,
+                parts: 3,
+                field_sizes: vec![
+                    ("doc".to_owned(), 4),
+                    ("doc".to_owned(), 6),
+                    ("doc".to_owned(), 1),
+                ],
+            }
+        );
+
+        // parts skipped unread count with their size too
+        let (req, mut payload) = upload_request_named(MultipartFormConfig::default(), "doc");
+        let form =
+            <MultipartFormWithStats<Ignored> as FromRequest<DefaultError>>::from_request(
+                &req,
+                &mut payload,
+            )
+            .await
+            .unwrap();
+        assert_eq!(form.stats.total_bytes, 11);
+        assert_eq!(form.stats.parts, 3);
+        assert_eq!(form.stats.field_sizes[1], ("doc".to_owned(), 6));
+    }
+
+    #[ntex::test]
+    async fn test_filename_decoding() {
+        let config = MultipartFormConfig::default()
+            .filename_decoding(crate::FilenameDecoding::PercentDecode);
+        let (req, mut payload) = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+            )
+            .state(config)
+            .set_payload(
+                "--abbc\r\n\
+                 Content-Disposition: form-data; name=\"files\"; filename=\"caf%C3%A9.txt\"\r\n\r\n\
+                 data\r\n\
+                 --abbc--\r\n",
+            )
+            .to_http_parts();
+        let form = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.files[0].file_name.as_deref(), Some("caf\u{e9}.txt"));
+    }
+
+    #[ntex::test]
+    async fn test_allow_missing_field_name() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; filename=\"x.txt\"\r\n\r\n\
+                     data\r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
+
+        let (req, mut payload) = request(MultipartFormConfig::default());
+        let res = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::ContentDispositionNameMissing)));
+
+        let config = MultipartFormConfig::default()
+            .allow_missing_field_name(true)
+            .missing_field_name("files");
+        let (req, mut payload) = request(config);
+        let form = <MultipartForm<Upload> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert_eq!(form.files.len(), 1);
+        assert_eq!(form.files[0].file_name.as_deref(), Some("x.txt"));
+    }
+
+    /// Hand-written equivalent of a derived form with an optional JSON field.
+    struct Meta {
+        meta: Option<crate::form::json::Json<serde_json::Value>>,
+    }
+
+    impl MultipartCollect for Meta {
+        fn limit(_field_name: &str) -> Option<usize> {
+            None
+        }
+
+        fn field_kind(field_name: &str) -> FieldKind {
+            match field_name {
+                "meta" => <Option<crate::form::json::Json<serde_json::Value>> as FieldGroupReader>::KIND,
+                _ => FieldKind::Any,
+            }
+        }
+
+        fn handle_field<'t>(
+            ctx: &'t FormContext,
+            field: crate::Field,
+            limits: &'t mut Limits,
+            state: &'t mut State,
+        ) -> LocalBoxFuture<'t, Result<(), MultipartError>> {
+            <Option<crate::form::json::Json<serde_json::Value>> as FieldGroupReader>::handle_field(
+                ctx,
+                field,
+                limits,
+                state,
+                crate::form::DuplicateField::Ignore,
+            )
+        }
+
+        fn from_state(mut state: State) -> Result<Self, MultipartError> {
+            Ok(Meta {
+                meta: <Option<crate::form::json::Json<serde_json::Value>> as FieldGroupReader>::from_state(
+                    "meta", &mut state,
+                )?,
+            })
+        }
+    }
+
+    #[ntex::test]
+    async fn test_reject_inconsistent_parts() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; name=\"meta\"; filename=\"meta.json\"\r\n\
+                     Content-Type: application/json\r\n\r\n\
+                     {}\r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
+
+        let (req, mut payload) = request(MultipartFormConfig::default());
+        let form = <MultipartForm<Meta> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert!(form.meta.is_some());
+
+        let (req, mut payload) =
+            request(MultipartFormConfig::default().reject_inconsistent_parts(true));
+        let res = <MultipartForm<Meta> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        assert!(
+            matches!(res, Err(MultipartError::InconsistentPart { name, .. }) if name == "meta")
+        );
+    }
+
+    #[ntex::test]
+    async fn test_declared_size_exceeded() {
+        let request = |config: MultipartFormConfig| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .state(config)
+                .set_payload(
+                    "--abbc\r\n\
+                     Content-Disposition: form-data; name=\"meta\"\r\n\
+                     Content-Type: application/json\r\n\
+                     Content-Length: 2\r\n\r\n\
+                     {}\r\n\
+                     --abbc--\r\n",
+                )
+                .to_http_parts()
+        };
+
+        let (req, mut payload) = request(MultipartFormConfig::default().total_limit(2));
+        let form = <MultipartForm<Meta> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await
+        .unwrap();
+        assert!(form.meta.is_some());
+
+        let (req, mut payload) = request(MultipartFormConfig::default().total_limit(1));
+        let res = <MultipartForm<Meta> as FromRequest<DefaultError>>::from_request(
+            &req,
+            &mut payload,
+        )
+        .await;
+        match res {
+            Err(err @ MultipartError::FieldSizeExceeded { .. }) => {
+                assert_eq!(
+                    err.to_string(),
+                    "Field meta declares 2 bytes but only 1 are allowed"
+                );
+            }
+            _ => panic!("expected a declared size error"),
+        }
     }
 }
@@ -1,17 +1,53 @@
-use crate::MultipartError;
+use crate::multipart::Nesting;
 use crate::payload::{PayloadBuffer, PayloadRef};
 use crate::safety::Safety;
-use futures::Stream;
+use crate::{Multipart, MultipartError};
+use futures::{Stream, TryStreamExt};
 use ntex::http::error::PayloadError;
 use ntex::http::{HeaderMap, header};
-use ntex::util::Bytes;
+use ntex::util::{Bytes, BytesMut};
 use ntex_files::header::ContentDisposition;
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
 use std::{cmp, fmt};
 
+/// How the filename of a part is taken from its Content-Disposition.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilenameDecoding {
+    /// Use the `filename` parameter as it is, as RFC 7578 requires.
+    #[default]
+    Literal,
+
+    /// Percent-decode the `filename` parameter, as some clients encode non-ASCII names that way.
+    PercentDecode,
+
+    /// Use the decoded `filename*` parameter if present, otherwise the literal `filename`.
+    Auto,
+}
+
+impl FilenameDecoding {
+    /// Returns the filename of `cd` decoded with this policy.
+    ///
+    /// Invalid UTF-8 produced by percent-decoding is replaced, a `filename*` in an unsupported
+    /// charset is ignored.
+    pub fn decode<'a>(self, cd: &'a ContentDisposition) -> Option<Cow<'a, str>> {
+        match self {
+            FilenameDecoding::Literal => cd.get_filename().map(Cow::Borrowed),
+            FilenameDecoding::PercentDecode => cd
+                .get_filename()
+                .map(|name| percent_encoding::percent_decode_str(name).decode_utf8_lossy()),
+            FilenameDecoding::Auto => cd
+                .get_filename_ext_decoded()
+                .map(Cow::Owned)
+                .or_else(|| cd.get_filename().map(Cow::Borrowed)),
+        }
+    }
+}
+
 /// A single field in a multipart stream
 pub struct Field {
     /// Field's Content-Type.
@@ -26,6 +62,15 @@ pub struct Field {
     /// Field's header map.
     headers: HeaderMap,
 
+    /// Field's header section as received, if retained.
+    pub(crate) raw_headers: Option<Bytes>,
+
+    /// How [`Field::filename`] decodes the filename.
+    pub(crate) filename_decoding: FilenameDecoding,
+
+    /// Settings of the body this part is nested in, if it is a nested multipart body.
+    pub(crate) nesting: Option<Nesting>,
+
     inner: Rc<RefCell<InnerField>>,
     safety: Safety,
 }
@@ -39,26 +84,171 @@ impl Field {
         form_field_name: Option<String>,
         inner: Rc<RefCell<InnerField>>,
     ) -> Self {
+        let form_field_name = form_field_name.unwrap_or_default();
+
+        let mut safety = safety;
+        if safety.has_unconsumed_hook() {
+            let name = content_disposition.as_ref().and_then(|cd| cd.get_name());
+            let name = name.unwrap_or_default().to_owned();
+            let inner = Rc::downgrade(&inner);
+            safety.probe(Box::new(move || {
+                let inner = inner.upgrade()?;
+                let unconsumed = inner.try_borrow().ok()?.payload.is_some();
+                unconsumed.then(|| name.clone())
+            }));
+        }
+
         Field {
             content_type,
             content_disposition,
-            form_field_name: form_field_name.unwrap_or_default(),
+            form_field_name,
             headers,
+            raw_headers: None,
+            filename_decoding: FilenameDecoding::default(),
+            nesting: None,
             inner,
             safety,
         }
     }
 
+    /// Create a standalone field yielding `body`, for unit testing field readers.
+    ///
+    /// The form field name is taken from the `name` parameter of `content_disposition`.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn for_test(
+        headers: HeaderMap,
+        content_type: Option<mime::Mime>,
+        content_disposition: Option<ContentDisposition>,
+        body: Bytes,
+    ) -> Self {
+        let form_field_name =
+            content_disposition.as_ref().and_then(|cd| cd.get_name()).map(ToOwned::to_owned);
+        Self::in_memory(headers, content_type, content_disposition, form_field_name, body)
+    }
+
+    /// Create a `form-data` field named `name` yielding `body`, for the unit tests of field
+    /// readers.
+    #[cfg(test)]
+    pub(crate) fn test_field(name: &str, body: impl AsRef<[u8]>) -> Self {
+        use ntex_files::header::{DispositionParam, DispositionType};
+
+        let cd = ContentDisposition {
+            disposition: DispositionType::FormData,
+            parameters: vec![DispositionParam::Name(name.to_owned())],
+        };
+        Self::for_test(HeaderMap::new(), None, Some(cd), Bytes::copy_from_slice(body.as_ref()))
+    }
+
+    /// Set the content type of a test field.
+    #[cfg(test)]
+    pub(crate) fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.parse().unwrap());
+        self
+    }
+
+    /// Set the `filename` of a test field.
+    #[cfg(test)]
+    pub(crate) fn with_filename(mut self, filename: &str) -> Self {
+        if let Some(cd) = self.content_disposition.as_mut() {
+            cd.parameters.push(ntex_files::header::DispositionParam::Filename(filename.into()));
+        }
+        self
+    }
+
+    /// Add a header to a test field.
+    #[cfg(test)]
+    pub(crate) fn with_header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.insert(
+            header::HeaderName::from_static(name),
+            header::HeaderValue::from_static(value),
+        );
+        self
+    }
+
+    /// Create a field with the same headers and name as this one, yielding `body`.
+    pub(crate) fn replay(&self, body: Bytes) -> Self {
+        let mut field = Self::in_memory(
+            self.headers.clone(),
+            self.content_type.clone(),
+            self.content_disposition.clone(),
+            Some(self.form_field_name.clone()),
+            body,
+        );
+        field.raw_headers.clone_from(&self.raw_headers);
+        field.filename_decoding = self.filename_decoding;
+        field
+    }
+
+    /// Create a standalone field yielding `body`.
+    fn in_memory(
+        headers: HeaderMap,
+        content_type: Option<mime::Mime>,
+        content_disposition: Option<ContentDisposition>,
+        form_field_name: Option<String>,
+        body: Bytes,
+    ) -> Self {
+        const BOUNDARY: &str = "ntex-multipart-in-memory-boundary";
+
+        let mut payload = BytesMut::with_capacity(body.len() + BOUNDARY.len() + 8);
+        payload.extend_from_slice(&body);
+        payload.extend_from_slice(b"\r\n--");
+        payload.extend_from_slice(BOUNDARY.as_bytes());
+        payload.extend_from_slice(b"--\r\n");
+
+        let stream = futures::stream::once(futures::future::ready(Ok(payload.freeze())));
+        let inner = InnerField {
+            payload: Some(PayloadRef::new(PayloadBuffer::new(stream))),
+            boundary: BOUNDARY.to_owned(),
+            eof: false,
+            length: Some(body.len() as u64),
+            boundary_tail: None,
+            size: 0,
+        };
+
+        Field::new(
+            Safety::new(),
+            headers,
+            content_type,
+            content_disposition,
+            form_field_name,
+            Rc::new(RefCell::new(inner)),
+        )
+    }
+
+    /// Delimit the rest of the body by the boundary instead of its `Content-Length`, so the
+    /// two can be compared.
+    pub(crate) fn ignore_declared_length(&self) {
+        self.inner.borrow_mut().ignore_declared_length();
+    }
+
+    /// Returns a handle to the size of the body, once the field has been read or skipped.
+    pub(crate) fn body_size(&self) -> BodySize {
+        BodySize(Rc::clone(&self.inner))
+    }
+
     /// Get a map of headers
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 
+    /// Returns the raw header section of the field, exactly as received.
+    ///
+    /// Includes the blank line terminating the section. Only available when enabled with
+    /// [`Multipart::raw_headers`](crate::Multipart::raw_headers).
+    pub fn raw_headers(&self) -> Option<&Bytes> {
+        self.raw_headers.as_ref()
+    }
+
     /// Get the content type of the field
     pub fn content_type(&self) -> Option<&mime::Mime> {
         self.content_type.as_ref()
     }
 
+    /// Returns the value of the field's Content-Length header, if set and valid.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get(&header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+    }
+
     /// Returns this field's parsed Content-Disposition header, if set.
     pub fn content_disposition(&self) -> Option<&ContentDisposition> {
         self.content_disposition.as_ref()
@@ -68,15 +258,199 @@ impl Field {
     pub fn name(&self) -> Option<&str> {
         self.content_disposition()?.get_name()
     }
+
+    /// Returns the filename of the field, if set.
+    ///
+    /// The filename is decoded as configured with [`MultipartFormConfig::filename_decoding`],
+    /// by default it is the literal `filename` parameter of the Content-Disposition.
+    ///
+    /// [`MultipartFormConfig::filename_decoding`]: crate::MultipartFormConfig::filename_decoding
+    pub fn filename(&self) -> Option<Cow<'_, str>> {
+        self.filename_decoding.decode(self.content_disposition()?)
+    }
+
+    /// Returns the name forms use to route this field.
+    ///
+    /// This is the Content-Disposition name of `multipart/form-data` parts, possibly trimmed
+    /// with [`MultipartFormConfig::trim_field_names`], and empty for other parts.
+    ///
+    /// [`MultipartFormConfig::trim_field_names`]: crate::MultipartFormConfig::trim_field_names
+    pub fn form_field_name(&self) -> &str {
+        &self.form_field_name
+    }
+
+    /// Parse the field as a nested multipart body, e.g. the `multipart/mixed` block a form
+    /// field may carry several files in.
+    ///
+    /// Returns the field back if its content type is not `multipart/*`, so it can be read as
+    /// usual. The nested body is read from the field, it ends at the boundary of the body
+    /// containing it, and is parsed with the same settings. The containing body must have
+    /// been created with [`Multipart::allow_nested`] for such fields to be yielded at all.
+    pub fn nested_multipart(self) -> Result<Multipart, Box<Field>> {
+        let Some(ct) = self.content_type.clone().filter(|ct| ct.type_() == mime::MULTIPART)
+        else {
+            return Err(Box::new(self));
+        };
+        let nesting = self.nesting.clone().unwrap_or_default();
+        let content_disposition = self.content_disposition.clone();
+
+        // the nested stream only carries payload errors, pass the field error on the side
+        let field_error = Rc::new(Cell::new(None));
+        let error = field_error.clone();
+        let stream = self.map_err(move |err| {
+            error.set(Some(err));
+            PayloadError::Incomplete(None)
+        });
+        Ok(Multipart::nested(ct, stream, nesting, content_disposition, field_error))
+    }
+
+    /// Compute a digest of the field content while it is being streamed.
+    ///
+    /// ```rust,ignore
+    /// let mut field = field.hashed(sha2::Sha256::new());
+    /// while let Some(chunk) = field.try_next().await? {
+    ///     // process chunk
+    /// }
+    /// let digest = field.finish();
+    /// ```
+    #[cfg(feature = "digest")]
+    pub fn hashed<D: digest::Digest>(self, hasher: D) -> crate::HashingField<D> {
+        crate::HashingField::new(self, hasher)
+    }
+
+    /// Pass every chunk to `f` before it is yielded.
+    ///
+    /// Returning an error from `f` aborts the field: the error is yielded in place of the
+    /// chunk and the stream ends. Useful for incremental content checks that should not
+    /// buffer the whole field.
+    pub fn inspect<F>(self, f: F) -> crate::InspectField<F>
+    where
+        F: FnMut(&[u8]) -> Result<(), MultipartError>,
+    {
+        crate::MapChunksField::new(self, crate::adapter::Inspect(f))
+    }
+
+    /// Transform every chunk with `f` before it is yielded, e.g. to decrypt or transcode the
+    /// field on the fly.
+    ///
+    /// The chunks are taken from the field after it was split from the multipart body, so
+    /// `f` may change their length freely. Returning an error from `f` aborts the field like
+    /// [`Field::inspect`]. Transformations compose with [`MapChunksField::map_chunks`].
+    ///
+    /// [`MapChunksField::map_chunks`]: crate::MapChunksField::map_chunks
+    pub fn map_chunks<F>(self, f: F) -> crate::MapChunksField<F>
+    where
+        F: FnMut(Bytes) -> Result<Bytes, MultipartError>,
+    {
+        crate::MapChunksField::new(self, f)
+    }
+
+    /// Fail with [`MultipartError::TooManyChunks`] once the field yields more than `max`
+    /// chunks, whatever their size.
+    ///
+    /// Chunk boundaries follow the transport, so a peer sending tiny packets makes the field
+    /// yield many small chunks. Choose the limit from the expected size divided by a small
+    /// packet size, e.g. a few thousand chunks for a field of a few megabytes.
+    pub fn with_max_chunks(self, max: usize) -> crate::MaxChunksField {
+        crate::MapChunksField::new(self, crate::adapter::MaxChunks { remaining: max })
+    }
+
+    /// Re-chunk the field into pieces of exactly `size` bytes, except for the last one.
+    ///
+    /// Chunk boundaries otherwise follow the transport. Useful for readers that work on
+    /// aligned blocks, e.g. a block cipher, at the cost of copying the data once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn chunked(self, size: usize) -> crate::ChunkedField {
+        crate::ChunkedField::new(self, size)
+    }
+
+    /// Drain the field, discarding its content, and return the number of bytes it carried.
+    pub async fn measure(mut self) -> Result<usize, MultipartError> {
+        let mut size = 0;
+        while let Some(chunk) = self.try_next().await? {
+            size += chunk.len();
+        }
+        Ok(size)
+    }
+
+    /// Read the whole field into memory.
+    ///
+    /// Fails with [`PayloadError::Overflow`] if the field carries more than `limit` bytes. A
+    /// field that arrives in a single chunk is returned without copying.
+    pub async fn collect_bytes(mut self, limit: usize) -> Result<Bytes, MultipartError> {
+        let mut data = Bytes::new();
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.try_next().await? {
+            if data.len() + buf.len() + chunk.len() > limit {
+                return Err(PayloadError::Overflow.into());
+            }
+            if data.is_empty() && buf.is_empty() {
+                data = chunk;
+            } else {
+                if !data.is_empty() {
+                    buf.extend_from_slice(&std::mem::take(&mut data));
+                }
+                buf.extend_from_slice(&chunk);
+            }
+        }
+        Ok(if buf.is_empty() { data } else { buf.freeze() })
+    }
+
+    /// Stream the field into `writer` and return the number of bytes written.
+    ///
+    /// Every chunk counts against the total limit of `limits`. The writer is flushed once the
+    /// field is exhausted, but creating, closing or cleaning up the destination is up to the
+    /// caller. Write failures are returned as [`MultipartError::Write`].
+    #[cfg(all(feature = "form", feature = "tempfile"))]
+    pub async fn write_to<W>(
+        mut self,
+        mut writer: W,
+        limits: &mut crate::form::Limits,
+    ) -> Result<usize, MultipartError>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let mut size = 0;
+        while let Some(chunk) = self.try_next().await? {
+            limits.try_consume_limits(chunk.len(), false)?;
+            writer.write_all(&chunk).await.map_err(MultipartError::Write)?;
+            size += chunk.len();
+        }
+        writer.flush().await.map_err(MultipartError::Write)?;
+        Ok(size)
+    }
 }
 
 impl Stream for Field {
     type Item = Result<Bytes, MultipartError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let res = self.poll_chunk(cx);
+
+        #[cfg(feature = "metrics")]
+        if let Poll::Ready(Some(Err(ref err))) = res
+            && let Some(ref payload) = self.inner.borrow().payload
+            && let Some(ref metrics) = payload.buffer().metrics
+        {
+            metrics.error(err);
+        }
+
+        res
+    }
+}
+
+impl Field {
+    fn poll_chunk(&self, cx: &mut Context) -> Poll<Option<Result<Bytes, MultipartError>>> {
         if self.safety.current() {
             let mut inner = self.inner.borrow_mut();
-            if let Some(mut payload) = inner.payload.as_ref().unwrap().get_mut(&self.safety) {
+            if let Some(ref payload) = inner.payload
+                && let Some(mut payload) = payload.get_mut(&self.safety)
+            {
                 payload.poll_stream(cx)?;
             }
             inner.poll(&self.safety)
@@ -104,11 +478,25 @@ impl fmt::Debug for Field {
     }
 }
 
+/// Size of the body of a field, as read so far by the field or by the multipart stream
+/// skipping it.
+pub(crate) struct BodySize(Rc<RefCell<InnerField>>);
+
+impl BodySize {
+    pub(crate) fn get(&self) -> u64 {
+        self.0.borrow().size
+    }
+}
+
 pub(crate) struct InnerField {
     payload: Option<PayloadRef>,
     boundary: String,
     eof: bool,
     length: Option<u64>,
+    // tail of the body read so far, kept when scanning declared-length bodies for the boundary
+    boundary_tail: Option<BytesMut>,
+    // number of body bytes read so far, by the field or by the multipart stream skipping it
+    size: u64,
 }
 
 impl InnerField {
@@ -131,7 +519,51 @@ impl InnerField {
             None
         };
 
-        Ok(InnerField { boundary, payload: Some(payload), eof: false, length: len })
+        Ok(InnerField {
+            boundary,
+            payload: Some(payload),
+            eof: false,
+            length: len,
+            boundary_tail: None,
+            size: 0,
+        })
+    }
+
+    /// Reject a declared-length body that contains the boundary delimiter.
+    pub(crate) fn reject_boundary_in_body(&mut self) {
+        if self.length.is_some() {
+            // the CRLF ending the header block also starts a delimiter
+            self.boundary_tail = Some(BytesMut::copy_from_slice(b"\r\n"));
+        }
+    }
+
+    /// Read the rest of the body up to the boundary, even if it declared a length.
+    pub(crate) fn ignore_declared_length(&mut self) {
+        self.length = None;
+        self.boundary_tail = None;
+    }
+
+    /// Scans `chunk` for `\r\n--boundary`, including delimiters split across chunks.
+    fn scan_boundary(tail: &mut BytesMut, boundary: &str, chunk: &[u8]) -> bool {
+        let delimiter_len = boundary.len() + 4;
+        tail.extend_from_slice(chunk);
+
+        let mut pos = 0;
+        while let Some(idx) = twoway::find_bytes(&tail[pos..], b"\r\n--") {
+            let start = pos + idx + 4;
+            if tail.len() < start + boundary.len() {
+                break;
+            }
+            if &tail[start..start + boundary.len()] == boundary.as_bytes() {
+                return true;
+            }
+            pos += idx + 1;
+        }
+
+        if tail.len() >= delimiter_len {
+            let _ = tail.split_to(tail.len() - (delimiter_len - 1));
+        }
+        false
     }
 
     /// Reads body part content chunk of the specified size.
@@ -181,8 +613,8 @@ impl InnerField {
             };
         }
 
-        // check boundary
-        if len > 4 && payload.buf[0] == b'\r' {
+        // check boundary, a buffer holding exactly `\r\n--` still needs more data to decide
+        if len >= 4 && payload.buf[0] == b'\r' {
             let b_len = if &payload.buf[..2] == b"\r\n" && &payload.buf[2..4] == b"--" {
                 Some(4)
             } else if &payload.buf[1..3] == b"--" {
@@ -194,7 +626,11 @@ impl InnerField {
             if let Some(b_len) = b_len {
                 let b_size = boundary.len() + b_len;
                 if len < b_size {
-                    return Poll::Pending;
+                    return if payload.eof {
+                        Poll::Ready(Some(Err(MultipartError::Incomplete)))
+                    } else {
+                        Poll::Pending
+                    };
                 } else if &payload.buf[b_len..b_size] == boundary.as_bytes() {
                     // found boundary
                     return Poll::Ready(None);
@@ -210,6 +646,8 @@ impl InnerField {
                 if cur + 4 > len {
                     if cur > 0 {
                         Poll::Ready(Some(Ok(payload.buf.split_to(cur))))
+                    } else if payload.eof {
+                        Poll::Ready(Some(Err(MultipartError::Incomplete)))
                     } else {
                         Poll::Pending
                     }
@@ -254,7 +692,15 @@ impl InnerField {
 
                 match res {
                     Poll::Pending => return Poll::Pending,
-                    Poll::Ready(Some(Ok(bytes))) => return Poll::Ready(Some(Ok(bytes))),
+                    Poll::Ready(Some(Ok(bytes))) => {
+                        if let Some(tail) = self.boundary_tail.as_mut()
+                            && InnerField::scan_boundary(tail, &self.boundary, &bytes)
+                        {
+                            return Poll::Ready(Some(Err(MultipartError::BoundaryInBody)));
+                        }
+                        self.size += bytes.len() as u64;
+                        return Poll::Ready(Some(Ok(bytes)));
+                    }
                     Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
                     Poll::Ready(None) => self.eof = true,
                 }
@@ -0,0 +1,221 @@
+//! Decodes a base64 text field into bytes.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldReader, FormContext, Limits, bytes::Bytes},
+};
+use base64::Engine as _;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use derive_more::{Deref, DerefMut, Display};
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+
+const PADDING_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, PADDING_INDIFFERENT);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, PADDING_INDIFFERENT);
+
+/// Decode a base64 text field.
+///
+/// ASCII whitespace, including line breaks, is ignored and padding is optional. The alphabet
+/// is the standard one unless [`Base64Config::url_safe`] is set. `T` is usually `Vec<u8>` or
+/// [`ntex::util::Bytes`].
+#[derive(Debug, Deref, DerefMut)]
+pub struct Base64<T = Vec<u8>>(pub T);
+
+impl<T> Base64<T> {
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'t, T> FieldReader<'t> for Base64<T>
+where
+    T: From<Vec<u8>> + 'static,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<Base64Config>().unwrap_or(&DEFAULT_CONFIG);
+            let form_field_name = field.form_field_name.clone();
+
+            let bytes = Bytes::read_field(ctx, field, limits).await?;
+
+            let mut text = bytes.data.to_vec();
+            text.retain(|b| !b.is_ascii_whitespace());
+
+            let engine = if config.url_safe { &URL_SAFE } else { &STANDARD };
+            let data = engine.decode(&text).map_err(|err| MultipartError::Field {
+                name: form_field_name,
+                source: Base64Error::Decode(err).into(),
+            })?;
+
+            Ok(Base64(T::from(data)))
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum Base64Error {
+    /// Decode error.
+    #[display("Base64 decode error: {}", _0)]
+    Decode(base64::DecodeError),
+}
+
+/// Return `BadRequest` for `Base64Error`
+impl WebResponseError<DefaultError> for Base64Error {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Configuration for the [`Base64`] field reader.
+#[derive(Clone)]
+pub struct Base64Config {
+    url_safe: bool,
+}
+
+const DEFAULT_CONFIG: Base64Config = Base64Config { url_safe: false };
+
+impl Base64Config {
+    /// Sets whether the field uses the URL-safe alphabet (`-` and `_`) instead of the standard
+    /// one (`+` and `/`).
+    pub fn url_safe(mut self, url_safe: bool) -> Self {
+        self.url_safe = url_safe;
+        self
+    }
+}
+
+impl Default for Base64Config {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Multipart;
+    use futures::StreamExt;
+    use ntex::http::HeaderMap;
+    use ntex::http::header::{self, HeaderValue};
+
+    #[ntex::test]
+    async fn test_base64() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let data = Base64::<Vec<u8>>::read_field(
+            &ctx,
+            Field::test_field("blob", "+/9u\r\ndGV4\n"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(data.into_inner(), b"\xfb\xff\x6etex");
+
+        let data = Base64::<ntex::util::Bytes>::read_field(
+            &ctx,
+            Field::test_field("blob", "bnRleA"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(data.into_inner(), "ntex");
+
+        let res =
+            Base64::<Vec<u8>>::read_field(&ctx, Field::test_field("blob", "-_9u"), &mut limits)
+                .await;
+        assert!(matches!(res, Err(MultipartError::Field { name, .. }) if name == "blob"));
+    }
+
+    #[ntex::test]
+    async fn test_base64_url_safe() {
+        let ctx = FormContext::new().with_config(Base64Config::default().url_safe(true));
+        let mut limits = Limits::new(1024, 1024);
+
+        let data =
+            Base64::<Vec<u8>>::read_field(&ctx, Field::test_field("blob", "-_9u"), &mut limits)
+                .await
+                .unwrap();
+        assert_eq!(data.into_inner(), b"\xfb\xff\x6e");
+
+        let res =
+            Base64::<Vec<u8>>::read_field(&ctx, Field::test_field("blob", "+/9u"), &mut limits)
+                .await;
+        assert!(res.is_err());
+    }
+
+    /// Encode `data` and wrap it into lines of 76 characters separated by `newline`.
+    fn encode_wrapped(engine: &GeneralPurpose, data: &[u8], newline: &str) -> String {
+        let encoded = engine.encode(data);
+        let lines: Vec<&str> =
+            encoded.as_bytes().chunks(76).map(|l| std::str::from_utf8(l).unwrap()).collect();
+        lines.join(newline)
+    }
+
+    /// Read the single part of a multipart body, split into small chunks.
+    async fn read_part(ctx: &FormContext, boundary: &str, body: &str) -> Vec<u8> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(&format!("multipart/form-data; boundary={boundary}"))
+                .unwrap(),
+        );
+        let payload = format!(
+            "--{boundary}\r\n\
+             Content-Disposition: form-data; name=\"blob\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n\
+             {body}\r\n\
+             --{boundary}--\r\n"
+        );
+        let stream = futures::stream::iter(
+            payload
+                .into_bytes()
+                .chunks(5)
+                .map(|c| Ok(ntex::util::Bytes::copy_from_slice(c)))
+                .collect::<Vec<_>>(),
+        );
+
+        let mut multipart = Multipart::new(&headers, stream);
+        let field = multipart.next().await.unwrap().unwrap();
+        let mut limits = Limits::new(4096, 4096);
+        let data = Base64::<Vec<u8>>::read_field(ctx, field, &mut limits).await.unwrap();
+        assert!(multipart.next().await.is_none());
+        data.into_inner()
+    }
+
+    #[ntex::test]
+    async fn test_base64_decoded_dashes() {
+        let ctx = FormContext::new();
+        let boundary = "abbc761f78ff4d7cb7573b5a23f96ef0";
+
+        // decoded lines that look like delimiters, including the boundary itself
+        let data = format!("--\n--{boundary}\r\n--{boundary}--\r\n-- end\n").repeat(4);
+        for newline in ["\n", "\r\n"] {
+            let body = encode_wrapped(&STANDARD, data.as_bytes(), newline);
+            assert!(body.len() > 76);
+            assert_eq!(read_part(&ctx, boundary, &body).await, data.as_bytes());
+        }
+    }
+
+    #[ntex::test]
+    async fn test_base64_encoded_dashes() {
+        let ctx = FormContext::new().with_config(Base64Config::default().url_safe(true));
+
+        // 0xfb 0xef 0xbe encodes to "----" with the URL-safe alphabet, so every wrapped line
+        // starts with dashes, sharing a prefix with the boundary
+        let data = [0xfb, 0xef, 0xbe].repeat(40);
+        let boundary = "----------------abc";
+        for newline in ["\n", "\r\n"] {
+            let body = encode_wrapped(&URL_SAFE, &data, newline);
+            assert!(body.contains(&format!("{newline}----")));
+            assert_eq!(read_part(&ctx, boundary, &body).await, data);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Reads a field with a reader chosen by its content type.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldReader, FormContext, Limits},
+};
+use derive_more::Display;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+
+/// Read a field as JSON or as plain text, depending on its `Content-Type`.
+///
+/// Fields with an `application/json` (or `+json`) content type are read with `J`, fields with
+/// a `text/plain` content type, or none at all, are read with `T`. Any other content type is
+/// rejected.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     metadata: ByContentType<Json<Metadata>, Text<String>>,
+/// }
+/// ```
+#[derive(Debug)]
+pub enum ByContentType<J, T> {
+    /// The field was read as JSON.
+    Json(J),
+
+    /// The field was read as plain text.
+    Text(T),
+}
+
+impl<'t, J, T> FieldReader<'t> for ByContentType<J, T>
+where
+    J: FieldReader<'t>,
+    T: FieldReader<'t>,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let (is_json, is_text) = match field.content_type() {
+                Some(mime) => (
+                    mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON),
+                    mime.type_() == mime::TEXT && mime.subtype() == mime::PLAIN,
+                ),
+                // https://datatracker.ietf.org/doc/html/rfc7578#section-4.4
+                // content type defaults to text/plain
+                None => (false, true),
+            };
+
+            if is_json {
+                Ok(ByContentType::Json(J::read_field(ctx, field, limits).await?))
+            } else if is_text {
+                Ok(ByContentType::Text(T::read_field(ctx, field, limits).await?))
+            } else {
+                Err(MultipartError::Field {
+                    name: field.form_field_name,
+                    source: ByContentTypeError::Unsupported.into(),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum ByContentTypeError {
+    /// The field content type is neither JSON nor plain text.
+    #[display("Unsupported field content type")]
+    Unsupported,
+}
+
+/// Return `BadRequest` for `ByContentTypeError`
+impl WebResponseError<DefaultError> for ByContentTypeError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::{json::Json, text::Text};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        name: String,
+    }
+
+    type Flexible = ByContentType<Json<Item>, Text<String>>;
+
+    #[ntex::test]
+    async fn test_by_content_type() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let json = Field::test_field("item", r#"{"name": "ntex"}"#)
+            .with_content_type("application/json");
+        match Flexible::read_field(&ctx, json, &mut limits).await.unwrap() {
+            ByContentType::Json(item) => assert_eq!(item.0, Item { name: "ntex".to_owned() }),
+            ByContentType::Text(_) => panic!("expected json"),
+        }
+
+        for text in [
+            Field::test_field("item", "ntex").with_content_type("text/plain"),
+            Field::test_field("item", "ntex"),
+        ] {
+            match Flexible::read_field(&ctx, text, &mut limits).await.unwrap() {
+                ByContentType::Text(text) => assert_eq!(text.0, "ntex"),
+                ByContentType::Json(_) => panic!("expected text"),
+            }
+        }
+
+        let other = Field::test_field("item", "ntex").with_content_type("image/png");
+        let res = Flexible::read_field(&ctx, other, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Field { name, .. }) if name == "item"));
+    }
+}
@@ -1,12 +1,12 @@
 use crate::{
     Field, MultipartError,
-    form::{FieldReader, Limits},
+    form::{FieldReader, FormContext, Limits},
 };
 use futures::TryStreamExt;
 use futures::future::LocalBoxFuture;
 use mime::Mime;
 use ntex::util::BytesMut;
-use ntex::web::HttpRequest;
+use std::borrow::Cow;
 
 /// Read the field into memory.
 #[derive(Debug)]
@@ -25,7 +25,7 @@ impl<'t> FieldReader<'t> for Bytes {
     type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
 
     fn read_field(
-        _: &'t HttpRequest,
+        _: &'t FormContext,
         mut field: Field,
         limits: &'t mut Limits,
     ) -> Self::Future {
@@ -40,11 +40,7 @@ impl<'t> FieldReader<'t> for Bytes {
             Ok(Bytes {
                 data: buf.freeze(),
                 content_type: field.content_type().map(ToOwned::to_owned),
-                file_name: field
-                    .content_disposition()
-                    .expect("multipart form fields should have a content-disposition header")
-                    .get_filename()
-                    .map(ToOwned::to_owned),
+                file_name: field.filename().map(Cow::into_owned),
             })
         })
     }
@@ -0,0 +1,212 @@
+//! Deserializes a field as CSV.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits},
+};
+use derive_more::{Deref, DerefMut, Display};
+use futures::TryStreamExt;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::util::BytesMut;
+use ntex::web::{DefaultError, WebResponseError};
+use serde::de::DeserializeOwned;
+
+/// Deserialize each record of a CSV field into a `T`.
+///
+/// The field is read into memory, counting against the memory limit, and parsed as it is
+/// without being written to disk first. Use [`CsvConfig`] to change the delimiter or to parse
+/// fields without a header row.
+#[derive(Debug, Deref, DerefMut)]
+pub struct Csv<T: DeserializeOwned>(pub Vec<T>);
+
+impl<T: DeserializeOwned> Csv<T> {
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<'t, T> FieldReader<'t> for Csv<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(
+        ctx: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<CsvConfig>().unwrap_or(&DEFAULT_CONFIG);
+
+            let mut buf = BytesMut::new();
+            while let Some(chunk) = field.try_next().await? {
+                limits.try_consume_limits(chunk.len(), true)?;
+                buf.extend_from_slice(&chunk);
+            }
+
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(config.delimiter)
+                .has_headers(config.has_headers)
+                .from_reader(buf.as_ref());
+
+            reader.deserialize().collect::<Result<Vec<T>, _>>().map(Csv).map_err(|err| {
+                MultipartError::Field {
+                    name: field.form_field_name,
+                    source: CsvFieldError::from_csv(err).into(),
+                }
+            })
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum CsvFieldError {
+    /// A record could not be parsed or deserialized.
+    #[display("CSV error at line {line}, column {column}: {message}")]
+    Deserialize {
+        /// Line of the failing record, starting at 1.
+        line: u64,
+
+        /// Column of the failing value starting at 1, or 0 if the whole record failed.
+        column: u64,
+
+        /// Description of the failure.
+        message: String,
+    },
+}
+
+impl CsvFieldError {
+    fn from_csv(err: csv::Error) -> Self {
+        let line = err.position().map_or(0, |pos| pos.line());
+        let column = match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.field().map_or(0, |field| field + 1),
+            _ => 0,
+        };
+        CsvFieldError::Deserialize { line, column, message: err.to_string() }
+    }
+}
+
+/// Return `BadRequest` for `CsvFieldError`
+impl WebResponseError<DefaultError> for CsvFieldError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Configuration for the [`Csv`] field reader.
+#[derive(Clone)]
+pub struct CsvConfig {
+    delimiter: u8,
+    has_headers: bool,
+}
+
+const DEFAULT_CONFIG: CsvConfig = CsvConfig { delimiter: b',', has_headers: true };
+
+impl CsvConfig {
+    /// Sets the field delimiter. By default this is a comma.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets whether the first record is a header row. Enabled by default.
+    ///
+    /// With a header row, records are matched to struct fields by column name, otherwise by
+    /// position.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        name: String,
+        count: u32,
+    }
+
+    #[ntex::test]
+    async fn test_csv() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let rows = Csv::<Row>::read_field(
+            &ctx,
+            Field::test_field("rows", "name,count\nfoo,1\nbar,2\n")
+                .with_content_type("text/csv"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            rows.into_inner(),
+            [
+                Row { name: "foo".to_owned(), count: 1 },
+                Row { name: "bar".to_owned(), count: 2 }
+            ]
+        );
+    }
+
+    #[ntex::test]
+    async fn test_csv_config() {
+        let ctx = FormContext::new()
+            .with_config(CsvConfig::default().delimiter(b';').has_headers(false));
+        let mut limits = Limits::new(1024, 1024);
+
+        let rows = Csv::<Row>::read_field(
+            &ctx,
+            Field::test_field("rows", "foo;1\n").with_content_type("text/csv"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(rows.0, [Row { name: "foo".to_owned(), count: 1 }]);
+    }
+
+    #[ntex::test]
+    async fn test_csv_error() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let res = Csv::<Row>::read_field(
+            &ctx,
+            Field::test_field("rows", "name,count\nfoo,1\nbar,two\n")
+                .with_content_type("text/csv"),
+            &mut limits,
+        )
+        .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "rows");
+                let msg = source.to_string();
+                assert!(msg.starts_with("CSV error at line 3, column 2"), "{msg}");
+            }
+            _ => panic!("expected a field error"),
+        }
+
+        let mut limits = Limits::new(1024, 8);
+        let res = Csv::<Row>::read_field(
+            &ctx,
+            Field::test_field("rows", "name,count\nfoo,1\n").with_content_type("text/csv"),
+            &mut limits,
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Reads a field as text in the charset it declares.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits, bytes::Bytes},
+};
+use derive_more::Display;
+use encoding_rs::Encoding;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::util::ByteString;
+use ntex::web::{DefaultError, WebResponseError};
+use std::borrow::Cow;
+use std::ops::Deref;
+
+/// Read a field as text, decoded from the `charset` parameter of its Content-Type.
+///
+/// Fields without a charset are decoded with the default of [`DecodedTextConfig`], UTF-8
+/// unless changed. Text that needs no conversion, valid UTF-8 or plain ASCII in an ASCII
+/// compatible charset, shares the buffer the field was read into instead of being copied.
+///
+/// Unlike [`Text<String>`](super::text::Text) this does not deserialize the value, use it for
+/// handlers that only look at the string. See [`decode_text`] to decode bytes already in hand.
+#[derive(Debug, Clone)]
+pub struct DecodedText {
+    text: ByteString,
+    charset: &'static str,
+}
+
+impl DecodedText {
+    /// Returns the decoded text.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the name of the charset the field was decoded from, e.g. `UTF-8`.
+    pub fn charset(&self) -> &'static str {
+        self.charset
+    }
+
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> ByteString {
+        self.text
+    }
+}
+
+impl Deref for DecodedText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<'t> FieldReader<'t> for DecodedText {
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<DecodedTextConfig>().unwrap_or(&DEFAULT_CONFIG);
+            let form_field_name = field.form_field_name.clone();
+            let label = field
+                .content_type()
+                .and_then(|mime| mime.get_param(mime::CHARSET))
+                .map(|charset| charset.as_str().to_owned());
+
+            let data = Bytes::read_field(ctx, field, limits).await?.data;
+
+            let to_field_error = |err: DecodedTextError| MultipartError::Field {
+                name: form_field_name.clone(),
+                source: err.into(),
+            };
+            let encoding = match label {
+                Some(label) => encoding_for_label(&label).map_err(to_field_error)?,
+                None => config.default_charset,
+            };
+
+            let text = match decode(&data, encoding).map_err(to_field_error)? {
+                // nothing was converted, keep the received buffer
+                Cow::Borrowed(_) => {
+                    ByteString::try_from(data).expect("validated by the decoder")
+                }
+                Cow::Owned(text) => ByteString::from(text),
+            };
+            Ok(DecodedText { text, charset: encoding.name() })
+        })
+    }
+}
+
+/// Decode `data` from the charset named by `charset`, or from UTF-8 if there is none.
+///
+/// The result borrows `data` when no conversion is needed, e.g. when it already is valid
+/// UTF-8.
+pub fn decode_text<'a>(
+    data: &'a [u8],
+    charset: Option<&str>,
+) -> Result<Cow<'a, str>, DecodedTextError> {
+    let encoding = match charset {
+        Some(label) => encoding_for_label(label)?,
+        None => encoding_rs::UTF_8,
+    };
+    decode(data, encoding)
+}
+
+fn encoding_for_label(label: &str) -> Result<&'static Encoding, DecodedTextError> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| DecodedTextError::UnknownCharset(label.to_owned()))
+}
+
+fn decode<'a>(
+    data: &'a [u8],
+    encoding: &'static Encoding,
+) -> Result<Cow<'a, str>, DecodedTextError> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(data)
+        .ok_or(DecodedTextError::Malformed { charset: encoding.name() })
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum DecodedTextError {
+    /// The declared charset is not supported.
+    #[display("Unknown charset: {}", _0)]
+    UnknownCharset(String),
+
+    /// The field contains bytes that are not valid in its charset.
+    #[display("Field is not valid {}", charset)]
+    Malformed {
+        /// Name of the charset the field was decoded from.
+        charset: &'static str,
+    },
+}
+
+/// Return `BadRequest` for `DecodedTextError`
+impl WebResponseError<DefaultError> for DecodedTextError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Configuration for the [`DecodedText`] field reader.
+#[derive(Clone)]
+pub struct DecodedTextConfig {
+    default_charset: &'static Encoding,
+}
+
+impl DecodedTextConfig {
+    /// Sets the charset of fields whose Content-Type has no `charset` parameter, by any of its
+    /// WHATWG labels, e.g. `latin1` or `shift_jis`. By default this is UTF-8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `label` is not a known charset.
+    pub fn default_charset(mut self, label: &str) -> Self {
+        self.default_charset =
+            Encoding::for_label(label.as_bytes()).expect("unknown default charset");
+        self
+    }
+}
+
+const DEFAULT_CONFIG: DecodedTextConfig =
+    DecodedTextConfig { default_charset: &encoding_rs::UTF_8_INIT };
+
+impl Default for DecodedTextConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ntex::test]
+    async fn test_decoded_text() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let text =
+            DecodedText::read_field(&ctx, Field::test_field("note", "grüße"), &mut limits)
+                .await
+                .unwrap();
+        assert_eq!(text.as_str(), "grüße");
+        assert_eq!(text.charset(), "UTF-8");
+
+        let latin1 = Field::test_field("note", b"gr\xfc\xdfe")
+            .with_content_type("text/plain; charset=iso-8859-1");
+        let text = DecodedText::read_field(&ctx, latin1, &mut limits).await.unwrap();
+        assert_eq!(&*text, "grüße");
+        assert_eq!(text.charset(), "windows-1252");
+
+        let ctx = FormContext::new()
+            .with_config(DecodedTextConfig::default().default_charset("latin1"));
+        let text =
+            DecodedText::read_field(&ctx, Field::test_field("note", b"\xe9t\xe9"), &mut limits)
+                .await
+                .unwrap();
+        assert_eq!(text.as_str(), "été");
+    }
+
+    #[ntex::test]
+    async fn test_decoded_text_errors() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let res =
+            DecodedText::read_field(&ctx, Field::test_field("note", b"\xff"), &mut limits)
+                .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "note");
+                assert_eq!(source.to_string(), "Field is not valid UTF-8");
+            }
+            _ => panic!("expected a field error"),
+        }
+
+        let unknown =
+            Field::test_field("note", b"a").with_content_type("text/plain; charset=x-unknown");
+        let res = DecodedText::read_field(&ctx, unknown, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Field { .. })));
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let data = b"plain";
+        assert!(matches!(decode_text(data, None), Ok(Cow::Borrowed("plain"))));
+        assert!(matches!(decode_text(data, Some("latin1")), Ok(Cow::Borrowed("plain"))));
+        assert_eq!(decode_text(b"\xe9", Some("latin1")).unwrap(), "é");
+        assert!(matches!(
+            decode_text(b"a", Some("nope")),
+            Err(DecodedTextError::UnknownCharset(label)) if label == "nope"
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+//! Deserializes a field as a delimited list of plain text values.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits, bytes::Bytes},
+};
+use derive_more::{Deref, DerefMut, Display};
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+use serde::de::DeserializeOwned;
+use std::{fmt, str};
+
+/// Deserialize a field holding several values separated by a delimiter, newlines by default.
+///
+/// Meant for clients sending a multi-value selection, like a `tags` textarea, as one field
+/// instead of repeating it. Each value is trimmed and deserialized with [`serde_plain`] like
+/// [`Text`](super::text::Text), blank values are skipped. Use [`DelimitedConfig`] to split on
+/// another character.
+#[derive(Debug, Deref, DerefMut)]
+pub struct Delimited<T: DeserializeOwned>(pub Vec<T>);
+
+impl<T: DeserializeOwned> Delimited<T> {
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<'t, T> FieldReader<'t> for Delimited<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<DelimitedConfig>().unwrap_or(&DEFAULT_CONFIG);
+            let form_field_name = field.form_field_name.clone();
+
+            let bytes = Bytes::read_field(ctx, field, limits).await?;
+
+            let text = str::from_utf8(&bytes.data).map_err(|err| MultipartError::Field {
+                name: form_field_name.clone(),
+                source: DelimitedError::Utf8Error(err).into(),
+            })?;
+
+            let mut values = Vec::new();
+            let mut invalid = Vec::new();
+            for token in text.split(config.delimiter).map(str::trim).filter(|t| !t.is_empty()) {
+                match serde_plain::from_str(token) {
+                    Ok(value) => values.push(value),
+                    Err(err) => invalid.push(InvalidValue { token: token.to_owned(), err }),
+                }
+            }
+
+            if invalid.is_empty() {
+                Ok(Delimited(values))
+            } else {
+                Err(MultipartError::Field {
+                    name: form_field_name,
+                    source: DelimitedError::Deserialize(invalid).into(),
+                })
+            }
+        })
+    }
+}
+
+/// A value of a [`Delimited`] field that failed to deserialize.
+#[derive(Debug)]
+pub struct InvalidValue {
+    /// The trimmed value as received.
+    pub token: String,
+
+    /// The deserialize error.
+    pub err: serde_plain::Error,
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum DelimitedError {
+    /// UTF-8 decoding error.
+    #[display("UTF-8 decoding error: {}", _0)]
+    Utf8Error(str::Utf8Error),
+
+    /// One or more values failed to deserialize.
+    #[display("Plain text deserialize error: {}", InvalidValues(_0))]
+    Deserialize(Vec<InvalidValue>),
+}
+
+struct InvalidValues<'a>(&'a [InvalidValue]);
+
+impl fmt::Display for InvalidValues<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, value) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:?}: {}", value.token, value.err)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return `BadRequest` for `DelimitedError`
+impl WebResponseError<DefaultError> for DelimitedError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// Configuration for the [`Delimited`] field reader.
+#[derive(Clone)]
+pub struct DelimitedConfig {
+    delimiter: char,
+}
+
+impl DelimitedConfig {
+    /// Sets the character separating values. By default this is a newline, a `\r` before it
+    /// is trimmed along with other surrounding whitespace.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+const DEFAULT_CONFIG: DelimitedConfig = DelimitedConfig { delimiter: '\n' };
+
+impl Default for DelimitedConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ntex::test]
+    async fn test_delimited() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let tags = Delimited::<String>::read_field(
+            &ctx,
+            Field::test_field("tags", "ntex\r\n web \r\n\r\n"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tags.into_inner(), ["ntex", "web"]);
+
+        let ctx = FormContext::new().with_config(DelimitedConfig::default().delimiter(','));
+        let ids = Delimited::<u32>::read_field(
+            &ctx,
+            Field::test_field("tags", "1, 2,3"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids.0, [1, 2, 3]);
+    }
+
+    #[ntex::test]
+    async fn test_delimited_error() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let res = Delimited::<u32>::read_field(
+            &ctx,
+            Field::test_field("tags", "1\nx\n3\ny"),
+            &mut limits,
+        )
+        .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "tags");
+                let msg = source.to_string();
+                assert!(msg.contains("\"x\"") && msg.contains("\"y\""), "{msg}");
+            }
+            _ => panic!("expected a field error"),
+        }
+    }
+}
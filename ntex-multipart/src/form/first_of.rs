@@ -0,0 +1,178 @@
+//! Reads a field with the first of several readers that accepts it.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldReader, FormContext, Limits},
+};
+use derive_more::Display;
+use futures::TryStreamExt;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::util::BytesMut;
+use ntex::web::{DefaultError, WebResponseError};
+
+/// Read a field with `A`, falling back to `B` if `A` rejects it.
+///
+/// Readers consume the field, so it is read into memory first, counting against the memory
+/// limit, and each reader runs over a copy of that buffer. The limits are charged only once,
+/// as consumed by the reader that succeeded. If no reader succeeds, the field fails with
+/// [`FirstOfError::NoneMatched`] listing every reader's error.
+///
+/// More than two readers are tried by nesting, `FirstOf<A, FirstOf<B, C>>`.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     id: FirstOf<Json<Reference>, FirstOf<Text<u64>, Text<String>>>,
+/// }
+/// ```
+#[derive(Debug)]
+pub enum FirstOf<A, B> {
+    /// The field was read with the first reader.
+    First(A),
+
+    /// The first reader failed and the field was read with the second one.
+    Second(B),
+}
+
+impl<'t, A, B> FieldReader<'t> for FirstOf<A, B>
+where
+    A: for<'a> FieldReader<'a>,
+    B: for<'a> FieldReader<'a>,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    fn read_field(
+        ctx: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            // the buffer is charged on a copy, the readers charge the limits for the same bytes
+            let mut buf_limits = limits.clone();
+            let mut buf = BytesMut::new();
+            while let Some(chunk) = field.try_next().await? {
+                buf_limits.try_consume_limits(chunk.len(), true)?;
+                buf.extend_from_slice(&chunk);
+            }
+            let data = buf.freeze();
+
+            let mut errors = Vec::new();
+
+            let mut attempt = limits.clone();
+            match A::read_field(ctx, field.replay(data.clone()), &mut attempt).await {
+                Ok(value) => {
+                    *limits = attempt;
+                    return Ok(FirstOf::First(value));
+                }
+                Err(err) => errors.push(reason(err)),
+            }
+
+            let mut attempt = limits.clone();
+            match B::read_field(ctx, field.replay(data), &mut attempt).await {
+                Ok(value) => {
+                    *limits = attempt;
+                    return Ok(FirstOf::Second(value));
+                }
+                Err(err) => errors.push(reason(err)),
+            }
+
+            *limits = buf_limits;
+            Err(MultipartError::Field {
+                name: field.form_field_name,
+                source: FirstOfError::NoneMatched(errors).into(),
+            })
+        })
+    }
+}
+
+/// Describe why a reader rejected the field, without the field name wrapping it.
+fn reason(err: MultipartError) -> String {
+    match err {
+        MultipartError::Field { source, .. } => source.to_string(),
+        err => err.to_string(),
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum FirstOfError {
+    /// Every reader rejected the field, with their errors in the order they were tried.
+    #[display("No reader accepted the field: {}", _0.join("; "))]
+    NoneMatched(Vec<String>),
+}
+
+/// Return `BadRequest` for `FirstOfError`
+impl WebResponseError<DefaultError> for FirstOfError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::{
+        json::{Json, JsonConfig},
+        text::Text,
+    };
+
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        name: String,
+    }
+
+    #[ntex::test]
+    async fn test_first_of() {
+        type Flexible = FirstOf<Json<Item>, FirstOf<Text<u32>, Text<String>>>;
+
+        let ctx =
+            FormContext::new().with_config(JsonConfig::default().validate_content_type(false));
+        let mut limits = Limits::new(1024, 1024);
+
+        match Flexible::read_field(
+            &ctx,
+            Field::test_field("item", r#"{"name": "ntex"}"#),
+            &mut limits,
+        )
+        .await
+        {
+            Ok(FirstOf::First(item)) => assert_eq!(item.0, Item { name: "ntex".to_owned() }),
+            _ => panic!("expected json"),
+        }
+        assert_eq!(limits.total_limit_remaining, 1008);
+        assert_eq!(limits.memory_limit_remaining, 1008);
+
+        match Flexible::read_field(&ctx, Field::test_field("item", "42"), &mut limits).await {
+            Ok(FirstOf::Second(FirstOf::First(num))) => assert_eq!(num.0, 42),
+            _ => panic!("expected a number"),
+        }
+        match Flexible::read_field(&ctx, Field::test_field("item", "ntex"), &mut limits).await {
+            Ok(FirstOf::Second(FirstOf::Second(text))) => assert_eq!(text.0, "ntex"),
+            _ => panic!("expected text"),
+        }
+        assert_eq!(limits.total_limit_remaining, 1002);
+
+        let res = FirstOf::<Json<Item>, Text<u32>>::read_field(
+            &ctx,
+            Field::test_field("item", "ntex"),
+            &mut limits,
+        )
+        .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "item");
+                assert!(source.to_string().starts_with("No reader accepted the field: "));
+            }
+            _ => panic!("expected a field error"),
+        }
+        assert_eq!(limits.total_limit_remaining, 998);
+
+        let mut limits = Limits::new(1024, 2);
+        let res =
+            Flexible::read_field(&ctx, Field::test_field("item", "ntex"), &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Payload(_))));
+    }
+}
@@ -0,0 +1,164 @@
+//! Writes an image field to a temporary file on disk, after checking its magic bytes.
+
+use crate::{
+    Field, MultipartError,
+    form::{
+        FieldKind, FieldReader, FormContext, Limits,
+        temp_file::{TempFile, TempFileConfig, write_tempfile},
+    },
+};
+use derive_more::Display;
+use futures::TryStreamExt;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::util::BytesMut;
+use ntex::web::{DefaultError, WebResponseError};
+
+/// Number of leading bytes needed to recognize every supported format.
+const MAGIC_LEN: usize = 12;
+
+/// Write an image field to a temporary file on disk.
+///
+/// The leading bytes of the field are checked against the PNG, JPEG, GIF and WebP signatures
+/// before anything is written, the declared content type is not trusted. Temporary files are
+/// created according to [`TempFileConfig`].
+#[derive(Debug)]
+pub struct ImageFile {
+    /// The image on disk.
+    pub file: TempFile,
+
+    /// The format detected from the magic bytes.
+    pub format: ImageFormat,
+}
+
+/// Image formats recognized by [`ImageFile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+}
+
+impl ImageFormat {
+    /// Detect the image format from the leading bytes of a file.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.len() >= MAGIC_LEN && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+        {
+            Some(ImageFormat::WebP)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the mime type of the format.
+    pub fn mime(&self) -> mime::Mime {
+        match self {
+            ImageFormat::Png => mime::IMAGE_PNG,
+            ImageFormat::Jpeg => mime::IMAGE_JPEG,
+            ImageFormat::Gif => mime::IMAGE_GIF,
+            ImageFormat::WebP => "image/webp".parse().unwrap(),
+        }
+    }
+}
+
+impl<'t> FieldReader<'t> for ImageFile {
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::File;
+
+    fn read_field(
+        ctx: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            let mut head = BytesMut::new();
+            while head.len() < MAGIC_LEN {
+                match field.try_next().await? {
+                    Some(chunk) => {
+                        limits.try_consume_limits(chunk.len(), false)?;
+                        head.extend_from_slice(&chunk);
+                    }
+                    None => break,
+                }
+            }
+
+            let Some(format) = ImageFormat::from_magic(&head) else {
+                return Err(MultipartError::Field {
+                    name: field.form_field_name,
+                    source: ImageFileError::UnknownFormat.into(),
+                });
+            };
+
+            let file = write_tempfile(TempFileConfig::from_ctx(ctx), &mut field, limits, &head)
+                .await?;
+            Ok(ImageFile { file, format })
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum ImageFileError {
+    /// The field does not start with the signature of a supported image format.
+    #[display("Field is not a PNG, JPEG, GIF or WebP image")]
+    UnknownFormat,
+}
+
+/// Return `BadRequest` for `ImageFileError`
+impl WebResponseError<DefaultError> for ImageFileError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_magic() {
+        assert_eq!(ImageFormat::from_magic(b"\x89PNG\r\n\x1a\n\0\0"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_magic(b"\xff\xd8\xff\xe0"), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::from_magic(b"GIF89a\x01\0"), Some(ImageFormat::Gif));
+        assert_eq!(ImageFormat::from_magic(b"RIFF\x24\0\0\0WEBPVP8 "), Some(ImageFormat::WebP));
+        assert_eq!(ImageFormat::from_magic(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(ImageFormat::from_magic(b"<svg"), None);
+        assert_eq!(ImageFormat::from_magic(b""), None);
+    }
+
+    #[test]
+    fn test_image_file() {
+        crate::form::block_on_tokio(async {
+            let ctx = FormContext::new();
+            let mut limits = Limits::new(1024, 1024);
+            let image = ImageFile::read_field(
+                &ctx,
+                Field::test_field("avatar", b"GIF89a\x01\0\x01\0")
+                    .with_content_type("image/png"),
+                &mut limits,
+            )
+            .await
+            .unwrap();
+            assert_eq!(image.format, ImageFormat::Gif);
+            assert_eq!(image.file.size, 10);
+        });
+    }
+
+    #[ntex::test]
+    async fn test_image_file_rejects_non_image() {
+        let field = Field::test_field("avatar", b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>")
+            .with_content_type("image/png");
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+        let res = ImageFile::read_field(&ctx, field, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Field { name, .. }) if name == "avatar"));
+    }
+}
@@ -2,13 +2,27 @@
 
 use crate::{
     Field, MultipartError,
-    form::{FieldReader, Limits, bytes::Bytes},
+    form::{FieldKind, FieldReader, FormContext, Limits, bytes::Bytes, override_status},
 };
 use derive_more::{Deref, DerefMut, Display};
+use futures::channel::mpsc;
 use futures::future::LocalBoxFuture;
+use futures::{Stream, StreamExt, TryStreamExt, ready};
 use ntex::http::StatusCode;
-use ntex::web::{DefaultError, HttpRequest, WebResponseError};
+use ntex::util::{Bytes as NtexBytes, BytesMut};
+use ntex::web::{DefaultError, WebResponseError};
 use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::mpsc::{Receiver, TrySendError, sync_channel};
+use std::task::{Context, Poll};
+use std::{cmp, io};
+
+/// Number of chunks that may be queued for the streaming deserializer.
+const STREAMING_BACKLOG: usize = 4;
+
+/// Record separator of JSON text sequences, RFC 7464.
+const JSON_SEQ_SEPARATOR: u8 = 0x1e;
 
 /// Deserialize from JSON.
 #[derive(Debug, Deref, DerefMut)]
@@ -26,39 +40,259 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
 
-    fn read_field(req: &'t HttpRequest, field: Field, limits: &'t mut Limits) -> Self::Future {
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
         Box::pin(async move {
-            let config = req.app_state::<JsonConfig>().unwrap_or(&DEFAULT_CONFIG);
-
-            if config.validate_content_type {
-                let valid = if let Some(mime) = field.content_type() {
-                    mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
-                } else {
-                    false
-                };
-
-                if !valid {
-                    return Err(MultipartError::Field {
-                        name: field.form_field_name,
-                        source: JsonFieldError::ContentType.into(),
-                    });
-                }
-            }
+            let config = ctx.config::<JsonConfig>().unwrap_or(&DEFAULT_CONFIG);
+            config.check_content_type(&field)?;
 
             let form_field_name = field.form_field_name.clone();
 
-            let bytes = Bytes::read_field(req, field, limits).await?;
+            let bytes = Bytes::read_field(ctx, field, limits).await?;
 
             Ok(Json(serde_json::from_slice(bytes.data.as_ref()).map_err(|err| {
-                MultipartError::Field {
+                config.on_error(MultipartError::Field {
                     name: form_field_name,
                     source: JsonFieldError::Deserialize(err).into(),
+                })
+            })?))
+        })
+    }
+}
+
+/// Deserialize from JSON incrementally, without collecting the whole field first.
+///
+/// Chunks are handed to a [`serde_json`] deserializer running on the blocking thread pool as
+/// they arrive, so only a handful of chunks are held in memory at any time. Every chunk still
+/// counts against the memory limit when it is pulled from the field.
+///
+/// Prefer [`Json`] for small payloads, the hand-off to another thread is not free.
+#[derive(Debug, Deref, DerefMut)]
+pub struct StreamingJson<T: DeserializeOwned>(pub T);
+
+impl<T: DeserializeOwned> StreamingJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'t, T> FieldReader<'t> for StreamingJson<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(
+        ctx: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<JsonConfig>().unwrap_or(&DEFAULT_CONFIG);
+            config.check_content_type(&field)?;
+
+            let (tx, rx) = sync_channel(STREAMING_BACKLOG);
+            let (ack_tx, mut ack_rx) = mpsc::unbounded();
+            let task = ntex::rt::spawn_blocking(move || {
+                let reader = ChunkReader { rx, ack: ack_tx, chunk: NtexBytes::new() };
+                serde_json::from_reader::<_, T>(reader)
+            });
+
+            'field: while let Some(mut chunk) = field.try_next().await? {
+                limits.try_consume_limits(chunk.len(), true)?;
+
+                loop {
+                    match tx.try_send(chunk) {
+                        Ok(()) => break,
+                        // wait for the deserializer to take a chunk off the queue
+                        Err(TrySendError::Full(rejected)) if ack_rx.next().await.is_some() => {
+                            chunk = rejected
+                        }
+                        // deserializer gave up early, the rest of the field is drained by the
+                        // multipart stream
+                        Err(_) => break 'field,
+                    }
                 }
+            }
+            drop(tx);
+
+            let result = task.await.map_err(|_| MultipartError::Field {
+                name: field.form_field_name.clone(),
+                source: JsonFieldError::Canceled.into(),
+            })?;
+
+            Ok(StreamingJson(result.map_err(|err| {
+                config.on_error(MultipartError::Field {
+                    name: field.form_field_name,
+                    source: JsonFieldError::Deserialize(err).into(),
+                })
             })?))
         })
     }
 }
 
+/// Blocking reader over the chunks sent by [`StreamingJson`].
+///
+/// Every chunk taken off the queue is acknowledged, so the sending side can wait for room in
+/// the queue without blocking the runtime.
+struct ChunkReader {
+    rx: Receiver<NtexBytes>,
+    ack: mpsc::UnboundedSender<()>,
+    chunk: NtexBytes,
+}
+
+impl io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    let _ = self.ack.unbounded_send(());
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = cmp::min(buf.len(), self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+/// A stream of JSON records read from a single `application/x-ndjson` or
+/// `application/json-seq` field.
+///
+/// The field is split on record separators, a newline for NDJSON or the `RS` (`0x1e`) byte
+/// for JSON text sequences (RFC 7464) as picked from the field's content type, and each record
+/// is deserialized as soon as it is complete. Only the record being received is buffered, so a
+/// field may carry any number of records. Blank records are skipped.
+///
+/// A record that fails to deserialize yields a [`JsonFieldError::Deserialize`] error and the
+/// stream moves on to the next record. A record longer than
+/// [`max_record_len`](Self::max_record_len) ends the stream with
+/// [`JsonFieldError::RecordTooLong`].
+///
+/// ```
+/// # use futures::TryStreamExt;
+/// # use ntex_multipart::{Field, MultipartError, form::json::JsonRecords};
+/// async fn import(field: Field) -> Result<usize, MultipartError> {
+///     let mut records = JsonRecords::<serde_json::Value>::new(field).max_record_len(64 * 1024);
+///     let mut count = 0;
+///     while let Some(_record) = records.try_next().await? {
+///         count += 1;
+///     }
+///     Ok(count)
+/// }
+/// ```
+pub struct JsonRecords<T = serde_json::Value> {
+    field: Field,
+    separator: u8,
+    buf: BytesMut,
+    scanned: usize,
+    max_record_len: Option<usize>,
+    eof: bool,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> JsonRecords<T> {
+    /// Read records from `field`, split on `RS` if its content type is `application/json-seq`
+    /// and on newlines otherwise.
+    pub fn new(field: Field) -> Self {
+        let separator = match field.content_type() {
+            Some(mime) if mime.subtype() == "json-seq" => JSON_SEQ_SEPARATOR,
+            _ => b'\n',
+        };
+        JsonRecords {
+            field,
+            separator,
+            buf: BytesMut::new(),
+            scanned: 0,
+            max_record_len: None,
+            eof: false,
+            _t: PhantomData,
+        }
+    }
+
+    /// Sets the byte separating records, overriding the one picked from the content type.
+    pub fn separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets the maximum size of a single record in bytes. Unlimited by default.
+    pub fn max_record_len(mut self, max: usize) -> Self {
+        self.max_record_len = Some(max);
+        self
+    }
+
+    fn error(&self, err: JsonFieldError) -> MultipartError {
+        MultipartError::Field { name: self.field.form_field_name.clone(), source: err.into() }
+    }
+
+    /// Deserialize a record, `None` if it is blank.
+    fn parse(&self, record: &[u8]) -> Option<Result<T, MultipartError>> {
+        if record.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        Some(
+            serde_json::from_slice(record)
+                .map_err(|err| self.error(JsonFieldError::Deserialize(err))),
+        )
+    }
+}
+
+impl<T: DeserializeOwned> Stream for JsonRecords<T> {
+    type Item = Result<T, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let pos = this.buf[this.scanned..].iter().position(|b| *b == this.separator);
+            let len = pos.map_or(this.buf.len(), |pos| this.scanned + pos);
+
+            if let Some(max) = this.max_record_len
+                && len > max
+            {
+                // the rest of the field is drained by the multipart stream
+                this.eof = true;
+                this.buf.clear();
+                this.scanned = 0;
+                return Poll::Ready(Some(Err(this.error(JsonFieldError::RecordTooLong))));
+            }
+
+            if pos.is_some() {
+                let record = this.buf.split_to(len + 1);
+                this.scanned = 0;
+                match this.parse(&record[..len]) {
+                    Some(item) => return Poll::Ready(Some(item)),
+                    None => continue,
+                }
+            }
+            this.scanned = len;
+
+            if this.eof {
+                let record = std::mem::take(&mut this.buf);
+                this.scanned = 0;
+                return Poll::Ready(this.parse(&record));
+            }
+
+            match ready!(Pin::new(&mut this.field).poll_next(cx)) {
+                Some(Ok(chunk)) => this.buf.extend_from_slice(&chunk),
+                Some(Err(err)) => {
+                    this.eof = true;
+                    this.buf.clear();
+                    this.scanned = 0;
+                    return Poll::Ready(Some(Err(err)));
+                }
+                None => this.eof = true,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Display)]
 #[non_exhaustive]
 pub enum JsonFieldError {
@@ -69,6 +303,14 @@ pub enum JsonFieldError {
     /// Content type error.
     #[display("Content type error")]
     ContentType,
+
+    /// The streaming deserializer could not run on the blocking thread pool.
+    #[display("Json deserializer task failed")]
+    Canceled,
+
+    /// A record of a [`JsonRecords`] stream exceeds its maximum length.
+    #[display("Json record is too long")]
+    RecordTooLong,
 }
 
 /// Return `BadRequest` for `JsonFieldError`
@@ -82,9 +324,11 @@ impl WebResponseError<DefaultError> for JsonFieldError {
 #[derive(Clone)]
 pub struct JsonConfig {
     validate_content_type: bool,
+    status_on_error: Option<StatusCode>,
 }
 
-const DEFAULT_CONFIG: JsonConfig = JsonConfig { validate_content_type: true };
+const DEFAULT_CONFIG: JsonConfig =
+    JsonConfig { validate_content_type: true, status_on_error: None };
 
 impl JsonConfig {
     /// Sets whether or not the field must have a valid `Content-Type` header to be parsed.
@@ -92,6 +336,39 @@ impl JsonConfig {
         self.validate_content_type = validate_content_type;
         self
     }
+
+    /// Sets the response status of fields that fail validation, e.g. `422 Unprocessable
+    /// Entity` for JSON that does not deserialize. By default the status of
+    /// [`JsonFieldError`], `400 Bad Request`, is used.
+    ///
+    /// Limit and stream errors keep their own status.
+    pub fn status_on_error(mut self, status: StatusCode) -> Self {
+        self.status_on_error = Some(status);
+        self
+    }
+
+    /// Applies [`JsonConfig::status_on_error`] to a validation error.
+    fn on_error(&self, err: MultipartError) -> MultipartError {
+        override_status(err, self.status_on_error)
+    }
+
+    fn check_content_type(&self, field: &Field) -> Result<(), MultipartError> {
+        if self.validate_content_type {
+            let valid = if let Some(mime) = field.content_type() {
+                mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+            } else {
+                false
+            };
+
+            if !valid {
+                return Err(self.on_error(MultipartError::Field {
+                    name: field.form_field_name.clone(),
+                    source: JsonFieldError::ContentType.into(),
+                }));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for JsonConfig {
@@ -99,3 +376,153 @@ impl Default for JsonConfig {
         DEFAULT_CONFIG
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Multipart;
+    use ntex::http::header::{self, HeaderMap, HeaderValue};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    fn json_multipart(body: &'static str) -> Multipart {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+        let payload = format!(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"item\"\r\n\
+             Content-Type: application/json\r\n\r\n\
+             {}\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+            body
+        );
+        let stream = futures::stream::iter(
+            payload
+                .into_bytes()
+                .chunks(7)
+                .map(|c| Ok(NtexBytes::copy_from_slice(c)))
+                .collect::<Vec<_>>(),
+        );
+        Multipart::new(&headers, stream)
+    }
+
+    #[ntex::test]
+    async fn test_streaming_json() {
+        let ctx = FormContext::new();
+        let mut multipart = json_multipart(r#"{"name": "ntex", "tags": ["web", "async"]}"#);
+        let field = multipart.next().await.unwrap().unwrap();
+
+        let mut limits = Limits::new(1024, 1024);
+        let item = StreamingJson::<Item>::read_field(&ctx, field, &mut limits).await.unwrap();
+        assert_eq!(
+            item.into_inner(),
+            Item { name: "ntex".to_owned(), tags: vec!["web".to_owned(), "async".to_owned()] }
+        );
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_json_status_on_error() {
+        let ctx = FormContext::new().with_config(
+            JsonConfig::default().status_on_error(StatusCode::UNPROCESSABLE_ENTITY),
+        );
+        let mut limits = Limits::new(1024, 1024);
+
+        let mut multipart = json_multipart(r#"{"name": "ntex"}"#);
+        let field = multipart.next().await.unwrap().unwrap();
+        match Json::<Item>::read_field(&ctx, field, &mut limits).await {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "item");
+                assert!(source.to_string().starts_with("Json deserialize error"));
+                assert_eq!(
+                    source.as_response_error().status_code(),
+                    StatusCode::UNPROCESSABLE_ENTITY
+                );
+            }
+            _ => panic!("expected a field error"),
+        }
+    }
+
+    #[ntex::test]
+    async fn test_streaming_json_limits() {
+        let ctx = FormContext::new();
+        let mut multipart = json_multipart(r#"{"name": "ntex", "tags": ["web", "async"]}"#);
+        let field = multipart.next().await.unwrap().unwrap();
+
+        let mut limits = Limits::new(1024, 16);
+        let res = StreamingJson::<Item>::read_field(&ctx, field, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Payload(_))));
+    }
+
+    #[ntex::test]
+    async fn test_streaming_json_invalid() {
+        let ctx = FormContext::new();
+        let mut multipart = json_multipart(r#"{"name": 1}"#);
+        let field = multipart.next().await.unwrap().unwrap();
+
+        let mut limits = Limits::new(1024, 1024);
+        let res = StreamingJson::<Item>::read_field(&ctx, field, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Field { .. })));
+    }
+
+    #[ntex::test]
+    async fn test_json_records() {
+        let field = Field::test_field(
+            "records",
+            "{\"name\": \"a\", \"tags\": []}\r\n\n{\"name\": \"b\", \"tags\": [\"x\"]}",
+        )
+        .with_content_type("application/x-ndjson");
+        let items: Vec<Item> = JsonRecords::new(field).try_collect().await.unwrap();
+        assert_eq!(
+            items,
+            [
+                Item { name: "a".to_owned(), tags: vec![] },
+                Item { name: "b".to_owned(), tags: vec!["x".to_owned()] }
+            ]
+        );
+
+        let field = Field::test_field("records", "\x1e1\n\x1e{\"a\": 2}\n\x1e[3]\n")
+            .with_content_type("application/json-seq");
+        let values: Vec<serde_json::Value> =
+            JsonRecords::new(field).try_collect().await.unwrap();
+        assert_eq!(
+            values,
+            [serde_json::json!(1), serde_json::json!({"a": 2}), serde_json::json!([3])]
+        );
+    }
+
+    #[ntex::test]
+    async fn test_json_records_errors() {
+        let field = Field::test_field("records", "1\nnope\n3\n")
+            .with_content_type("application/x-ndjson");
+        let res: Vec<_> = JsonRecords::<u32>::new(field).collect().await;
+        assert_eq!(res.len(), 3);
+        assert!(matches!(res[0], Ok(1)));
+        assert!(
+            matches!(&res[1], Err(MultipartError::Field { name, .. }) if name == "records")
+        );
+        assert!(matches!(res[2], Ok(3)));
+
+        let field = Field::test_field("records", "1\n12345678\n3\n")
+            .with_content_type("application/x-ndjson");
+        let res: Vec<_> = JsonRecords::<u32>::new(field).max_record_len(4).collect().await;
+        assert_eq!(res.len(), 2);
+        assert!(matches!(res[0], Ok(1)));
+        match &res[1] {
+            Err(MultipartError::Field { source, .. }) => {
+                assert_eq!(source.to_string(), "Json record is too long")
+            }
+            _ => panic!("expected a field error"),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Enforces a minimum size on a field.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits},
+};
+use derive_more::{Deref, DerefMut, Display};
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+
+/// Read a field with `R`, rejecting it if it carried fewer than `MIN` bytes.
+///
+/// The size is taken from the bytes the inner reader charged against the form limits.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Comment {
+///     body: MinSize<Text<String>, 1>,
+/// }
+/// ```
+#[derive(Debug, Deref, DerefMut)]
+pub struct MinSize<R, const MIN: usize>(pub R);
+
+impl<R, const MIN: usize> MinSize<R, MIN> {
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> R {
+        self.0
+    }
+}
+
+impl<'t, R, const MIN: usize> FieldReader<'t> for MinSize<R, MIN>
+where
+    R: for<'a> FieldReader<'a>,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = R::KIND;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let name = field.form_field_name.clone();
+
+            // the inner reader gets its own copy, so the consumed bytes can be compared after
+            let mut inner_limits = limits.clone();
+            let inner = R::read_field(ctx, field, &mut inner_limits).await?;
+
+            let size = inner_limits.consumed() - limits.consumed();
+            *limits = inner_limits;
+
+            if size < MIN {
+                return Err(MultipartError::Field {
+                    name,
+                    source: MinSizeError::TooSmall { min: MIN, size }.into(),
+                });
+            }
+
+            Ok(MinSize(inner))
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum MinSizeError {
+    /// The field is smaller than required.
+    #[display("Field is {} bytes, at least {} bytes are required", size, min)]
+    TooSmall { min: usize, size: usize },
+}
+
+/// Return `BadRequest` for `MinSizeError`
+impl WebResponseError<DefaultError> for MinSizeError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::text::Text;
+
+    #[ntex::test]
+    async fn test_min_size() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let text = MinSize::<Text<String>, 3>::read_field(
+            &ctx,
+            Field::test_field("comment", "ntex"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(text.into_inner().into_inner(), "ntex");
+
+        let res = MinSize::<Text<String>, 3>::read_field(
+            &ctx,
+            Field::test_field("comment", "nt"),
+            &mut limits,
+        )
+        .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "comment");
+                assert_eq!(
+                    source.to_string(),
+                    "Field is 2 bytes, at least 3 bytes are required"
+                );
+            }
+            _ => panic!("expected a field error"),
+        }
+    }
+}
@@ -1,21 +1,81 @@
 //! Extract and process typed data from fields of a `multipart/form-data` request.
 
 use crate::{Field, MultipartError};
-use derive_more::{Deref, DerefMut};
+use derive_more::{Deref, DerefMut, Display};
 use futures::future::LocalBoxFuture;
-use ntex::http::error::PayloadError;
-use ntex::web::HttpRequest;
+use ntex::http::{StatusCode, error::PayloadError};
+use ntex::util::Extensions;
+use ntex::web::{DefaultError, HttpRequest, WebResponseError};
 use std::{
     any::Any,
     collections::HashMap,
     future::{Future, ready},
 };
 
+pub mod base64;
+pub mod by_content_type;
 pub mod bytes;
+#[cfg(feature = "csv")]
+pub mod csv;
+pub mod decoded_text;
+pub mod delimited;
+pub mod first_of;
+#[cfg(feature = "tempfile")]
+pub mod image_file;
 pub mod json;
+pub mod min_size;
+pub mod small_text;
+#[cfg(feature = "tempfile")]
+pub mod split;
 #[cfg(feature = "tempfile")]
 pub mod temp_file;
 pub mod text;
+pub mod timed;
+#[cfg(feature = "url")]
+pub mod url;
+pub mod with_headers;
+
+/// The source of the configs field readers are run with.
+///
+/// Readers look their config up by type, e.g. [`TextConfig`](text::TextConfig), and fall back
+/// to its default if there is none. Configs added with [`FormContext::with_config`] are found
+/// first, then those registered as app state of the request the context was created from. The
+/// [`struct@MultipartForm`](crate::MultipartForm) extractor uses the request of the handler,
+/// a context without a request allows running readers on their own, see
+/// [`parse_form_with`](crate::parse_form_with).
+#[derive(Default)]
+pub struct FormContext {
+    req: Option<HttpRequest>,
+    configs: Extensions,
+}
+
+impl FormContext {
+    /// Create a context without a request and without configs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a context looking configs up in the app state of `req`.
+    pub fn from_req(req: &HttpRequest) -> Self {
+        FormContext { req: Some(req.clone()), configs: Extensions::new() }
+    }
+
+    /// Add `config`, taking precedence over a config of the same type in app state.
+    pub fn with_config<C: 'static>(mut self, config: C) -> Self {
+        self.configs.insert(config);
+        self
+    }
+
+    /// Returns the config of type `C`, if there is one.
+    pub fn config<C: 'static>(&self) -> Option<&C> {
+        self.configs.get::<C>().or_else(|| self.req.as_ref()?.app_state::<C>())
+    }
+
+    /// Returns the request the context was created from.
+    pub fn request(&self) -> Option<&HttpRequest> {
+        self.req.as_ref()
+    }
+}
 
 /// Trait that data types to be used in a multipart form struct should implement.
 ///
@@ -33,13 +93,94 @@ pub trait FieldReader<'t>: Sized + Any {
     /// your implementation of this method, you should [`fuse()`] the `Field` first.
     ///
     /// [`fuse()`]: futures_util::stream::StreamExt::fuse()
-    fn read_field(req: &'t HttpRequest, field: Field, limits: &'t mut Limits) -> Self::Future;
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future;
+
+    /// The kind of part this reader expects, see [`FieldKind`].
+    const KIND: FieldKind = FieldKind::Any;
+}
+
+/// The kind of part a [`FieldReader`] expects.
+///
+/// Only used to reject parts that do not match their form field, see
+/// [`MultipartFormConfig::reject_inconsistent_parts`].
+///
+/// [`MultipartFormConfig::reject_inconsistent_parts`]: crate::MultipartFormConfig::reject_inconsistent_parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Either a file or a data part.
+    Any,
+
+    /// A data part, such as JSON or text, that has no `filename`.
+    Data,
+
+    /// A file upload, that has a `filename`.
+    File,
 }
 
 /// Used to accumulate the state of the loaded fields.
+///
+/// Fields are keyed by name, so the order they were received in is lost unless the state is
+/// created with [`State::ordered`], see [`MultipartFormConfig::ordered_state`].
+///
+/// [`MultipartFormConfig::ordered_state`]: crate::MultipartFormConfig::ordered_state
 #[doc(hidden)]
 #[derive(Default, Deref, DerefMut)]
-pub struct State(pub HashMap<String, Box<dyn Any>>);
+pub struct State {
+    #[deref]
+    #[deref_mut]
+    fields: HashMap<String, Box<dyn Any>>,
+    order: Option<Vec<String>>,
+    /// Limits consumed by the stored value of each field, see [`Limits::refund_replaced`].
+    usage: HashMap<String, FieldUsage>,
+    /// Limits remaining before the last replaceable field was read.
+    pending_usage: Option<(String, FieldUsage)>,
+}
+
+/// Limits consumed by reading a field.
+#[derive(Debug, Clone, Copy)]
+struct FieldUsage {
+    memory: usize,
+    field: usize,
+}
+
+impl State {
+    /// Create a state that also records the order fields arrive in.
+    pub fn ordered() -> Self {
+        State { order: Some(Vec::new()), ..State::default() }
+    }
+
+    /// Record the arrival of a field, if this state is ordered.
+    pub fn record_arrival(&mut self, name: &str) {
+        if let Some(order) = &mut self.order {
+            order.push(name.to_owned());
+        }
+    }
+
+    /// Record the limits consumed by the field last read with
+    /// [`Limits::refund_replaced`] enabled, given the limits after reading it, and give back
+    /// the limits consumed by the value it replaced.
+    pub fn settle_usage(&mut self, limits: &mut Limits) {
+        if let Some((name, before)) = self.pending_usage.take() {
+            let usage = FieldUsage {
+                memory: before.memory.saturating_sub(limits.memory_limit_remaining),
+                field: before.field.saturating_sub(limits.field_limit_remaining.unwrap_or(0)),
+            };
+            if let Some(replaced) = self.usage.insert(name, usage) {
+                limits.memory_limit_remaining += replaced.memory;
+                if let Some(ref mut field_limit) = limits.field_limit_remaining {
+                    *field_limit += replaced.field;
+                }
+            }
+        }
+    }
+
+    /// Names of the processed fields in the order they were received, one entry per field.
+    ///
+    /// Returns `None` if the state is not ordered.
+    pub fn arrival_order(&self) -> Option<&[String]> {
+        self.order.as_deref()
+    }
+}
 
 /// Trait that the field collection types implement, i.e. `Vec<T>`, `Option<T>`, or `T` itself.
 #[doc(hidden)]
@@ -48,7 +189,7 @@ pub trait FieldGroupReader<'t>: Sized + Any {
 
     /// The form will call this function for each matching field.
     fn handle_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
@@ -57,6 +198,9 @@ pub trait FieldGroupReader<'t>: Sized + Any {
 
     /// Construct `Self` from the group of processed fields.
     fn from_state(name: &str, state: &'t mut State) -> Result<Self, MultipartError>;
+
+    /// The kind of part the fields of the group expect.
+    const KIND: FieldKind = FieldKind::Any;
 }
 
 impl<'t, T> FieldGroupReader<'t> for Option<T>
@@ -65,8 +209,10 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<(), MultipartError>>;
 
+    const KIND: FieldKind = T::KIND;
+
     fn handle_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
@@ -86,16 +232,11 @@ where
             }
         }
 
-        Box::pin(async move {
-            let field_name = field.form_field_name.clone();
-            let t = T::read_field(req, field, limits).await?;
-            state.insert(field_name, Box::new(t));
-            Ok(())
-        })
+        Box::pin(read_replacing::<T>(ctx, field, limits, state))
     }
 
     fn from_state(name: &str, state: &'t mut State) -> Result<Self, MultipartError> {
-        Ok(state.remove(name).map(|m| *m.downcast::<T>().unwrap()))
+        state.remove(name).map(|m| downcast::<T>(name, m)).transpose()
     }
 }
 
@@ -105,8 +246,10 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<(), MultipartError>>;
 
+    const KIND: FieldKind = T::KIND;
+
     fn handle_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
@@ -119,9 +262,11 @@ where
                 .entry(field.form_field_name.clone())
                 .or_insert_with(|| Box::<Vec<T>>::default())
                 .downcast_mut::<Vec<T>>()
-                .unwrap();
+                .ok_or_else(|| {
+                    MultipartError::FieldTypeMismatch(field.form_field_name.clone())
+                })?;
 
-            let item = T::read_field(req, field, limits).await?;
+            let item = T::read_field(ctx, field, limits).await?;
             vec.push(item);
 
             Ok(())
@@ -129,7 +274,11 @@ where
     }
 
     fn from_state(name: &str, state: &'t mut State) -> Result<Self, MultipartError> {
-        Ok(state.remove(name).map(|m| *m.downcast::<Vec<T>>().unwrap()).unwrap_or_default())
+        Ok(state
+            .remove(name)
+            .map(|m| downcast::<Vec<T>>(name, m))
+            .transpose()?
+            .unwrap_or_default())
     }
 }
 
@@ -139,8 +288,10 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<(), MultipartError>>;
 
+    const KIND: FieldKind = T::KIND;
+
     fn handle_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
@@ -160,19 +311,14 @@ where
             }
         }
 
-        Box::pin(async move {
-            let field_name = field.form_field_name.clone();
-            let t = T::read_field(req, field, limits).await?;
-            state.insert(field_name, Box::new(t));
-            Ok(())
-        })
+        Box::pin(read_replacing::<T>(ctx, field, limits, state))
     }
 
     fn from_state(name: &str, state: &'t mut State) -> Result<Self, MultipartError> {
         state
             .remove(name)
-            .map(|m| *m.downcast::<T>().unwrap())
-            .ok_or_else(|| MultipartError::MissingField(name.to_owned()))
+            .map(|m| downcast::<T>(name, m))
+            .ok_or_else(|| MultipartError::MissingField(name.to_owned()))?
     }
 }
 
@@ -182,23 +328,25 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<(), MultipartError>>;
 
+    const KIND: FieldKind = T::KIND;
+
     fn handle_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
         _duplicate_field: DuplicateField,
     ) -> Self::Future {
-        let field_name = field.name().unwrap().to_string();
+        let field_name = field.form_field_name.clone();
 
         Box::pin(async move {
             let vec = state
-                .entry(field_name)
+                .entry(field_name.clone())
                 .or_insert_with(|| Box::<Vec<T>>::default())
                 .downcast_mut::<Vec<T>>()
-                .unwrap();
+                .ok_or(MultipartError::FieldTypeMismatch(field_name))?;
 
-            let item = T::read_field(req, field, limits).await?;
+            let item = T::read_field(ctx, field, limits).await?;
             vec.push(item);
 
             Ok(())
@@ -207,14 +355,87 @@ where
 
     fn from_state(name: &str, state: &'t mut State) -> Result<Self, MultipartError> {
         if let Some(boxed_vec) = state.remove(name) {
-            let vec = *boxed_vec.downcast::<Vec<T>>().unwrap();
-            Ok(Some(vec))
+            Ok(Some(downcast::<Vec<T>>(name, boxed_vec)?))
         } else {
             Ok(None)
         }
     }
 }
 
+/// Read a field into the state, replacing any earlier value of the same name.
+///
+/// With [`Limits::refund_replaced`], the memory and field limits consumed by the earlier value
+/// are given back by [`State::settle_usage`] once the field has been read.
+async fn read_replacing<'t, T: FieldReader<'t>>(
+    ctx: &'t FormContext,
+    field: Field,
+    limits: &'t mut Limits,
+    state: &'t mut State,
+) -> Result<(), MultipartError> {
+    let field_name = field.form_field_name.clone();
+    if !limits.refund_replaced {
+        let t = T::read_field(ctx, field, limits).await?;
+        state.insert(field_name, Box::new(t));
+        return Ok(());
+    }
+
+    // the reader holds on to the limits, the form settles the usage once it is done
+    state.pending_usage = Some((
+        field_name.clone(),
+        FieldUsage {
+            memory: limits.memory_limit_remaining,
+            field: limits.field_limit_remaining.unwrap_or(0),
+        },
+    ));
+    match T::read_field(ctx, field, limits).await {
+        Ok(t) => {
+            state.insert(field_name, Box::new(t));
+            Ok(())
+        }
+        Err(err) => {
+            // the earlier value stays, and so does its usage
+            state.pending_usage = None;
+            Err(err)
+        }
+    }
+}
+
+/// Take a processed field out of the state as the type its group reader expects.
+fn downcast<T: Any>(name: &str, value: Box<dyn Any>) -> Result<T, MultipartError> {
+    value
+        .downcast::<T>()
+        .map(|value| *value)
+        .map_err(|_| MultipartError::FieldTypeMismatch(name.to_owned()))
+}
+
+/// Report a reader error, a [`MultipartError::Field`], with `status` instead of the status of
+/// its source, if one is set.
+pub(crate) fn override_status(
+    err: MultipartError,
+    status: Option<StatusCode>,
+) -> MultipartError {
+    match (err, status) {
+        (MultipartError::Field { name, source }, Some(status)) => {
+            MultipartError::Field { name, source: StatusOverride { status, source }.into() }
+        }
+        (err, _) => err,
+    }
+}
+
+/// A reader error reported with the status set in the reader config.
+#[derive(Debug, Display)]
+#[display("{}", source)]
+struct StatusOverride {
+    status: StatusCode,
+    source: ntex::web::Error,
+}
+
+impl WebResponseError<DefaultError> for StatusOverride {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+}
+
 #[doc(hidden)]
 pub enum DuplicateField {
     /// Additional fields are not processed.
@@ -228,10 +449,37 @@ pub enum DuplicateField {
 }
 
 /// Used to keep track of the remaining limits for the form and current field.
+#[derive(Debug, Clone)]
 pub struct Limits {
     pub total_limit_remaining: usize,
     pub memory_limit_remaining: usize,
     pub field_limit_remaining: Option<usize>,
+
+    /// Remaining decompressed bytes, regardless of how the other limits are counted.
+    pub decompressed_limit_remaining: Option<usize>,
+
+    /// Whether decompressed chunks count against the other limits with their decompressed
+    /// size rather than their size on the wire.
+    pub count_decompressed: bool,
+
+    /// Whether a field replaced under `DuplicateField::Replace` gives back the limits it
+    /// consumed, see [`Limits::refund_replaced`].
+    pub refund_replaced: bool,
+
+    /// Whether in-memory bytes count against the memory limit only, see
+    /// [`Limits::separate_memory_limit`].
+    pub separate_memory_limit: bool,
+
+    /// Bytes charged so far, whichever limits they counted against.
+    pub(crate) consumed: usize,
+
+    /// Source of a compressed body and the wire bytes charged so far, see
+    /// [`Limits::count_decompressed`].
+    #[cfg(feature = "compress")]
+    pub(crate) compressed: Option<(std::rc::Rc<crate::decompress::Source>, usize)>,
+
+    /// Set when the last overflow was caused by the field limit.
+    pub(crate) field_limit_exceeded: bool,
 }
 
 impl Limits {
@@ -240,9 +488,92 @@ impl Limits {
             total_limit_remaining: total_limit,
             memory_limit_remaining: memory_limit,
             field_limit_remaining: None,
+            decompressed_limit_remaining: None,
+            count_decompressed: true,
+            refund_replaced: false,
+            separate_memory_limit: false,
+            consumed: 0,
+            #[cfg(feature = "compress")]
+            compressed: None,
+            field_limit_exceeded: false,
         }
     }
 
+    /// Caps the decompressed size of the form, see [`Limits::try_consume_decompressed`].
+    pub fn decompressed_limit(mut self, decompressed_limit: usize) -> Self {
+        self.decompressed_limit_remaining = Some(decompressed_limit);
+        self
+    }
+
+    /// Sets whether decompressed chunks are counted with their decompressed size (the
+    /// default) or with their size on the wire.
+    pub fn count_decompressed(mut self, count_decompressed: bool) -> Self {
+        self.count_decompressed = count_decompressed;
+        self
+    }
+
+    /// Sets whether a field replaced by a later one with the same name, in a form using
+    /// `#[multipart(duplicate_field = "replace")]`, gives back the limits it consumed.
+    /// Disabled by default.
+    ///
+    /// When enabled, the earlier value is kept until its replacement has been read, then
+    /// dropped, and the memory limit and the field limit are credited with what reading it
+    /// consumed, so a re-sent field is only accounted for once. Both values count while the
+    /// replacement is read, and a replacement that fails to read leaves the earlier value and
+    /// its accounting untouched. The total limit is not refunded, it bounds the bytes received,
+    /// which includes the replaced field. Fields collected into a `Vec` are never replaced and
+    /// keep counting against all limits.
+    pub fn refund_replaced(mut self, refund_replaced: bool) -> Self {
+        self.refund_replaced = refund_replaced;
+        self
+    }
+
+    /// Sets whether bytes read into memory count against the memory limit only. Disabled by
+    /// default.
+    ///
+    /// By default every byte counts against the total limit, and in-memory bytes also count
+    /// against the memory limit, so a large file written to disk can exhaust the total limit
+    /// and leave no room for a small JSON field read after it. When enabled, the memory limit
+    /// alone caps in-memory readers and the total limit only bounds the bytes written to disk
+    /// or streamed elsewhere, so the two budgets do not compete.
+    pub fn separate_memory_limit(mut self, separate_memory_limit: bool) -> Self {
+        self.separate_memory_limit = separate_memory_limit;
+        self
+    }
+
+    /// Returns the number of bytes charged so far, whichever limits they counted against.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Like [`Limits::try_consume_limits`], for a chunk that was `wire_bytes` long before
+    /// decompression and `decompressed_bytes` long after.
+    ///
+    /// The decompressed size always counts against the decompressed limit, so a small
+    /// compressed part can not expand without bound. The other limits are charged with the
+    /// size selected by [`Limits::count_decompressed`].
+    ///
+    /// [`Limits::try_consume_limits`] calls this for the fields of a body decompressed by
+    /// [`Multipart`](crate::Multipart), with the compressed bytes received since its last
+    /// call.
+    pub fn try_consume_decompressed(
+        &mut self,
+        wire_bytes: usize,
+        decompressed_bytes: usize,
+        in_memory: bool,
+    ) -> Result<(), MultipartError> {
+        if let Some(decompressed_limit) = self.decompressed_limit_remaining {
+            self.decompressed_limit_remaining = Some(
+                decompressed_limit
+                    .checked_sub(decompressed_bytes)
+                    .ok_or(MultipartError::Payload(PayloadError::Overflow))?,
+            );
+        }
+
+        let bytes = if self.count_decompressed { decompressed_bytes } else { wire_bytes };
+        self.consume(bytes, in_memory)
+    }
+
     /// This function should be called within a [`FieldReader`] when reading each chunk of a field
     /// to ensure that the form limits are not exceeded.
     ///
@@ -255,10 +586,24 @@ impl Limits {
         bytes: usize,
         in_memory: bool,
     ) -> Result<(), MultipartError> {
-        self.total_limit_remaining = self
-            .total_limit_remaining
-            .checked_sub(bytes)
-            .ok_or(MultipartError::Payload(PayloadError::Overflow))?;
+        // fields of a compressed body are read decompressed
+        #[cfg(feature = "compress")]
+        if let Some((ref source, ref mut charged)) = self.compressed {
+            let wire_bytes = source.wire_bytes.get() - *charged;
+            *charged += wire_bytes;
+            return self.try_consume_decompressed(wire_bytes, bytes, in_memory);
+        }
+
+        self.consume(bytes, in_memory)
+    }
+
+    fn consume(&mut self, bytes: usize, in_memory: bool) -> Result<(), MultipartError> {
+        if !(in_memory && self.separate_memory_limit) {
+            self.total_limit_remaining = self
+                .total_limit_remaining
+                .checked_sub(bytes)
+                .ok_or(MultipartError::Payload(PayloadError::Overflow))?;
+        }
 
         if in_memory {
             self.memory_limit_remaining = self
@@ -266,15 +611,166 @@ impl Limits {
                 .checked_sub(bytes)
                 .ok_or(MultipartError::Payload(PayloadError::Overflow))?;
         }
+        self.consumed += bytes;
 
         if let Some(field_limit) = self.field_limit_remaining {
-            self.field_limit_remaining = Some(
-                field_limit
-                    .checked_sub(bytes)
-                    .ok_or(MultipartError::Payload(PayloadError::Overflow))?,
-            );
+            let Some(field_limit) = field_limit.checked_sub(bytes) else {
+                self.field_limit_exceeded = true;
+                return Err(MultipartError::Payload(PayloadError::Overflow));
+            };
+            self.field_limit_remaining = Some(field_limit);
         }
 
         Ok(())
     }
 }
+
+/// Run `fut` on a tokio runtime, for tests of readers that write files: files are written with
+/// tokio, so they can't run under `ntex::test`.
+#[cfg(all(test, feature = "tempfile"))]
+pub(crate) fn block_on_tokio<F: Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(fut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_form_context() {
+        let ctx = FormContext::new();
+        assert!(ctx.config::<u32>().is_none());
+        assert!(ctx.request().is_none());
+
+        let req =
+            ntex::web::test::TestRequest::default().state(1_u32).state("app").to_http_request();
+        let ctx = FormContext::from_req(&req).with_config(2_u32);
+        assert_eq!(ctx.config::<u32>(), Some(&2));
+        assert_eq!(ctx.config::<&str>(), Some(&"app"));
+        assert!(ctx.request().is_some());
+    }
+
+    #[test]
+    fn test_from_state_type_mismatch() {
+        let mut state = State::default();
+        state.insert("count".to_owned(), Box::new(1_u32));
+        state.insert("tags".to_owned(), Box::new(vec![1_u32]));
+
+        let res = <text::Text<String> as FieldGroupReader>::from_state("count", &mut state);
+        assert!(matches!(res, Err(MultipartError::FieldTypeMismatch(name)) if name == "count"));
+
+        let res = <Vec<text::Text<String>> as FieldGroupReader>::from_state("tags", &mut state);
+        assert!(matches!(res, Err(MultipartError::FieldTypeMismatch(name)) if name == "tags"));
+    }
+
+    #[ntex::test]
+    async fn test_refund_replaced() {
+        let ctx = FormContext::new();
+        for refund in [false, true] {
+            let mut limits = Limits::new(100, 12).refund_replaced(refund);
+            limits.field_limit_remaining = Some(11);
+            let mut state = State::default();
+
+            for body in ["first", "second", "third"] {
+                let res = <text::Text<String> as FieldGroupReader>::handle_field(
+                    &ctx,
+                    Field::test_field("note", body),
+                    &mut limits,
+                    &mut state,
+                    DuplicateField::Replace,
+                )
+                .await;
+
+                if !refund && body == "third" {
+                    // all values are charged without a refund
+                    assert!(matches!(
+                        res,
+                        Err(MultipartError::Payload(PayloadError::Overflow))
+                    ));
+                    continue;
+                }
+                res.unwrap();
+                state.settle_usage(&mut limits);
+            }
+
+            if refund {
+                assert_eq!(limits.total_limit_remaining, 84);
+                assert_eq!(limits.memory_limit_remaining, 7);
+                assert_eq!(limits.field_limit_remaining, Some(6));
+
+                // a replacement that fails keeps the earlier value and its usage
+                let res = <text::Text<String> as FieldGroupReader>::handle_field(
+                    &ctx,
+                    Field::test_field("note", "far too long"),
+                    &mut limits,
+                    &mut state,
+                    DuplicateField::Replace,
+                )
+                .await;
+                assert!(res.is_err());
+                state.settle_usage(&mut limits);
+                assert!(state.pending_usage.is_none());
+                assert_eq!(state.usage["note"].memory, 5);
+
+                let note =
+                    <text::Text<String> as FieldGroupReader>::from_state("note", &mut state);
+                assert_eq!(note.unwrap().into_inner(), "third");
+            }
+        }
+    }
+
+    #[ntex::test]
+    async fn test_separate_memory_limit() {
+        let meta = || Field::test_field("meta", "{}").with_content_type("application/json");
+
+        let ctx = FormContext::new();
+        for separate in [false, true] {
+            let mut limits = Limits::new(100, 10).separate_memory_limit(separate);
+
+            // a file written to disk takes the whole total limit
+            limits.try_consume_limits(100, false).unwrap();
+            assert_eq!(limits.memory_limit_remaining, 10);
+
+            let res =
+                json::Json::<serde_json::Value>::read_field(&ctx, meta(), &mut limits).await;
+            if separate {
+                res.unwrap();
+                assert_eq!(limits.total_limit_remaining, 0);
+                assert_eq!(limits.memory_limit_remaining, 8);
+                assert_eq!(limits.consumed(), 102);
+            } else {
+                assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+            }
+        }
+
+        let mut limits = Limits::new(100, 10).separate_memory_limit(true);
+        assert!(matches!(
+            limits.try_consume_limits(11, true),
+            Err(MultipartError::Payload(PayloadError::Overflow))
+        ));
+        assert!(matches!(
+            limits.try_consume_limits(101, false),
+            Err(MultipartError::Payload(PayloadError::Overflow))
+        ));
+    }
+
+    #[test]
+    fn test_try_consume_decompressed() {
+        let mut limits = Limits::new(100, 100).decompressed_limit(50);
+        limits.try_consume_decompressed(5, 20, true).unwrap();
+        assert_eq!(limits.total_limit_remaining, 80);
+        assert_eq!(limits.memory_limit_remaining, 80);
+        assert_eq!(limits.decompressed_limit_remaining, Some(30));
+
+        let mut limits = Limits::new(100, 100).decompressed_limit(50).count_decompressed(false);
+        limits.try_consume_decompressed(5, 20, false).unwrap();
+        assert_eq!(limits.total_limit_remaining, 95);
+        assert_eq!(limits.memory_limit_remaining, 100);
+
+        // wire size is well within the limits, but the chunk expands past the cap
+        assert!(matches!(
+            limits.try_consume_decompressed(5, 40, false),
+            Err(MultipartError::Payload(PayloadError::Overflow))
+        ));
+    }
+}
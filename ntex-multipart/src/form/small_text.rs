@@ -0,0 +1,182 @@
+//! Reads a short text field without allocating for it.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits},
+};
+use derive_more::Display;
+use futures::TryStreamExt;
+use futures::future::LocalBoxFuture;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+use std::{fmt, ops::Deref, str};
+
+/// Read a text field of at most `N` bytes into an inline buffer.
+///
+/// Suited to small values such as CSRF tokens and flags, which would otherwise get a heap
+/// allocation each. The bytes are counted against the form limits like any other in-memory
+/// field, and a field longer than `N` bytes fails with [`SmallTextError::TooLong`] as soon as
+/// the excess is received.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Login {
+///     csrf_token: SmallText<64>,
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct SmallText<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> SmallText<N> {
+    /// Returns the text.
+    pub fn as_str(&self) -> &str {
+        // only valid UTF-8 is stored, see `read_field`
+        str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> Deref for SmallText<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Debug for SmallText<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for SmallText<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> PartialEq<str> for SmallText<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for SmallText<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<'t, const N: usize> FieldReader<'t> for SmallText<N> {
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(
+        _: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            let mut text = SmallText { buf: [0; N], len: 0 };
+
+            while let Some(chunk) = field.try_next().await? {
+                limits.try_consume_limits(chunk.len(), true)?;
+
+                let end = text.len + chunk.len();
+                if end > N {
+                    return Err(MultipartError::Field {
+                        name: field.form_field_name,
+                        source: SmallTextError::TooLong { max: N }.into(),
+                    });
+                }
+                text.buf[text.len..end].copy_from_slice(&chunk);
+                text.len = end;
+            }
+
+            if let Err(err) = str::from_utf8(&text.buf[..text.len]) {
+                return Err(MultipartError::Field {
+                    name: field.form_field_name,
+                    source: SmallTextError::Utf8Error(err).into(),
+                });
+            }
+
+            Ok(text)
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum SmallTextError {
+    /// The field does not fit the buffer.
+    #[display("Field is longer than {} bytes", max)]
+    TooLong { max: usize },
+
+    /// UTF-8 decoding error.
+    #[display("UTF-8 decoding error: {}", _0)]
+    Utf8Error(str::Utf8Error),
+}
+
+/// Return `BadRequest` for `SmallTextError`
+impl WebResponseError<DefaultError> for SmallTextError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ntex::test]
+    async fn test_small_text() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let text =
+            SmallText::<8>::read_field(&ctx, Field::test_field("token", b"ntex"), &mut limits)
+                .await
+                .unwrap();
+        assert_eq!(text, "ntex");
+        assert_eq!(text.len(), 4);
+        assert_eq!(limits.memory_limit_remaining, 1020);
+
+        let text =
+            SmallText::<4>::read_field(&ctx, Field::test_field("token", b""), &mut limits)
+                .await
+                .unwrap();
+        assert!(text.is_empty());
+
+        let res = SmallText::<4>::read_field(
+            &ctx,
+            Field::test_field("token", b"ntex-rs"),
+            &mut limits,
+        )
+        .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "token");
+                assert_eq!(source.to_string(), "Field is longer than 4 bytes");
+            }
+            _ => panic!("expected a field error"),
+        }
+
+        let res = SmallText::<4>::read_field(
+            &ctx,
+            Field::test_field("token", b"\xff\xfe"),
+            &mut limits,
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::Field { .. })));
+
+        let mut limits = Limits::new(1024, 2);
+        let res =
+            SmallText::<8>::read_field(&ctx, Field::test_field("token", b"ntex"), &mut limits)
+                .await;
+        assert!(matches!(res, Err(MultipartError::Payload(_))));
+    }
+}
@@ -0,0 +1,148 @@
+//! Deserializes the text fields of a form, while writing its files to disk.
+
+use crate::{
+    Multipart, MultipartError,
+    form::{FieldReader, FormContext, Limits, temp_file::TempFile, text::TextError},
+};
+use derive_more::Display;
+use futures::TryStreamExt;
+use ntex::http::StatusCode;
+use ntex::web::{DefaultError, WebResponseError};
+use serde::de::DeserializeOwned;
+
+/// Read a form into a struct built from its text fields, and the list of its file fields.
+///
+/// Parts with a `filename` are written to disk as [`TempFile`]s. All other parts are read into
+/// memory as UTF-8 text and deserialized into `T` as if they were urlencoded form pairs, so `T`
+/// may only contain flat, scalar fields and repeated names are rejected. Every field counts
+/// against `limits`.
+///
+/// Useful for forms whose text fields map onto an existing serde type, the files can then be
+/// matched by [`TempFile::file_name`] or by the order they were sent in.
+///
+/// A deserialization failure is reported as a [`MultipartError::Field`] with an empty name.
+pub async fn read_text_and_files<T>(
+    ctx: &FormContext,
+    mut multipart: Multipart,
+    limits: &mut Limits,
+) -> Result<(T, Vec<TempFile>), MultipartError>
+where
+    T: DeserializeOwned,
+{
+    let mut pairs = Vec::new();
+    let mut files = Vec::new();
+
+    while let Some(mut field) = multipart.try_next().await? {
+        let is_file = field.content_disposition().and_then(|cd| cd.get_filename()).is_some();
+        if is_file {
+            files.push(TempFile::read_field(ctx, field, limits).await?);
+            continue;
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            limits.try_consume_limits(chunk.len(), true)?;
+            data.extend_from_slice(&chunk);
+        }
+
+        let text = String::from_utf8(data).map_err(|err| MultipartError::Field {
+            name: field.form_field_name.clone(),
+            source: TextError::Utf8Error(err.utf8_error()).into(),
+        })?;
+        pairs.push((field.form_field_name.clone(), text));
+    }
+
+    // the pairs are known to be valid, so this only re-encodes them for the deserializer
+    let encoded = serde_urlencoded::to_string(&pairs).expect("string pairs always encode");
+    let text = serde_urlencoded::from_str(&encoded).map_err(|err| MultipartError::Field {
+        name: String::new(),
+        source: SplitError::Deserialize(err).into(),
+    })?;
+
+    Ok((text, files))
+}
+
+#[derive(Debug, Display)]
+#[non_exhaustive]
+pub enum SplitError {
+    /// Deserialize error.
+    #[display("Form text fields deserialize error: {}", _0)]
+    Deserialize(serde_urlencoded::de::Error),
+}
+
+/// Return `BadRequest` for `SplitError`
+impl WebResponseError<DefaultError> for SplitError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ntex::http::header::{self, HeaderMap, HeaderValue};
+    use ntex::util::Bytes;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Meta {
+        title: String,
+        year: u16,
+    }
+
+    fn multipart(body: &'static str) -> Multipart {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+        Multipart::new(
+            &headers,
+            futures::stream::iter([Ok(Bytes::from_static(body.as_bytes()))]),
+        )
+    }
+
+    #[test]
+    fn test_read_text_and_files() {
+        crate::form::block_on_tokio(async {
+            let multipart = multipart(
+                "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+                 ntex & co\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"cover\"; filename=\"cover.png\"\r\n\r\n\
+                 image\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"year\"\r\n\r\n\
+                 2024\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+            );
+
+            let ctx = FormContext::new();
+            let mut limits = Limits::new(1024, 1024);
+            let (meta, files) =
+                read_text_and_files::<Meta>(&ctx, multipart, &mut limits).await.unwrap();
+            assert_eq!(meta, Meta { title: "ntex & co".to_owned(), year: 2024 });
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].file_name.as_deref(), Some("cover.png"));
+            assert_eq!(files[0].size, 5);
+        });
+    }
+
+    #[ntex::test]
+    async fn test_read_text_and_files_deserialize_error() {
+        let multipart = multipart(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             ntex\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+        let res = read_text_and_files::<Meta>(&ctx, multipart, &mut limits).await;
+        assert!(matches!(res, Err(MultipartError::Field { name, .. }) if name.is_empty()));
+    }
+}
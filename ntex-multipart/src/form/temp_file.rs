@@ -1,16 +1,18 @@
 //! Writes a field to a temporary file on disk.
 
 use crate::{
-    Field, MultipartError,
-    form::{FieldReader, Limits},
+    Field, MultipartError, MultipartFormConfig,
+    form::{FieldKind, FieldReader, FormContext, Limits, override_status},
 };
 use derive_more::Display;
 use futures::TryStreamExt;
 use futures::future::LocalBoxFuture;
 use mime::Mime;
-use ntex::http::StatusCode;
+use ntex::http::{Payload, StatusCode};
+use ntex::util::{Bytes, BytesMut};
 use ntex::web::{DefaultError, HttpRequest, WebResponseError};
 use std::{
+    borrow::Cow,
     io,
     path::{Path, PathBuf},
 };
@@ -36,52 +38,202 @@ pub struct TempFile {
 impl<'t> FieldReader<'t> for TempFile {
     type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
 
+    const KIND: FieldKind = FieldKind::File;
+
     fn read_field(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         mut field: Field,
         limits: &'t mut Limits,
     ) -> Self::Future {
         Box::pin(async move {
-            let config = req.app_state::<TempFileConfig>().unwrap_or(&DEFAULT_CONFIG);
-            let mut size = 0;
+            write_tempfile(TempFileConfig::from_ctx(ctx), &mut field, limits, &[]).await
+        })
+    }
+}
 
-            let file = config.create_tempfile().map_err(|err| MultipartError::Field {
-                name: field.form_field_name.to_owned(),
-                source: TempFileError::FileIo(err).into(),
-            })?;
+/// Read the only file named `field_name` of a multipart request to a temporary file.
+///
+/// Saves defining a one-field form for endpoints accepting a single upload. The first part
+/// named `field_name` with a `filename` is written to a [`TempFile`], other parts are skipped.
+/// The whole body is read, within the limits of `config`, and fails with
+/// [`MultipartError::MissingField`] if there is no such file or
+/// [`MultipartError::DuplicateField`] if there is more than one.
+pub async fn read_single_file(
+    req: &HttpRequest,
+    payload: &mut Payload,
+    field_name: &str,
+    config: &MultipartFormConfig,
+) -> Result<TempFile, MultipartError> {
+    let ctx = FormContext::from_req(req);
+    let mut multipart = config.multipart(req.headers(), payload);
+    let mut limits = config.limits(&multipart);
+    let mut file = None;
 
-            let mut file_async = tokio::fs::File::from_std(file.reopen().map_err(|err| {
-                MultipartError::Field {
-                    name: field.form_field_name.to_owned(),
-                    source: TempFileError::FileIo(err).into(),
-                }
-            })?);
+    while let Some(mut field) = multipart.try_next().await? {
+        config.prepare_field(&mut field);
+        if field.form_field_name != field_name || field.filename().is_none() {
+            continue;
+        }
+        if file.is_some() {
+            return Err(MultipartError::DuplicateField(field_name.to_owned()));
+        }
+        file = Some(TempFile::read_field(&ctx, field, &mut limits).await?);
+    }
+
+    file.ok_or_else(|| MultipartError::MissingField(field_name.to_owned()))
+}
+
+/// Write `head` followed by the rest of the field to a new temporary file.
+pub(crate) async fn write_tempfile(
+    config: &TempFileConfig,
+    field: &mut Field,
+    limits: &mut Limits,
+    head: &[u8],
+) -> Result<TempFile, MultipartError> {
+    let io_err = |field: &Field, err| MultipartError::Field {
+        name: field.form_field_name.to_owned(),
+        source: TempFileError::FileIo(err).into(),
+    };
+
+    if config.verify_declared_length {
+        field.ignore_declared_length();
+    }
+
+    config.check_free_space(field)?;
+    let file = config.create_tempfile().map_err(|err| io_err(field, err))?;
+    let mut file_async =
+        tokio::fs::File::from_std(file.reopen().map_err(|err| io_err(field, err))?);
+
+    file_async.write_all(head).await.map_err(|err| io_err(field, err))?;
+    let mut size = head.len();
+    let mut unchecked = size;
+
+    while let Some(chunk) = field.try_next().await? {
+        limits.try_consume_limits(chunk.len(), false)?;
+        size += chunk.len();
+        file_async.write_all(chunk.as_ref()).await.map_err(|err| io_err(field, err))?;
+
+        // the partial file is removed when `file` is dropped on error
+        unchecked += chunk.len();
+        if unchecked >= FREE_SPACE_CHECK_INTERVAL {
+            unchecked = 0;
+            config.check_free_space(field)?;
+        }
+    }
+
+    file_async.flush().await.map_err(|err| io_err(field, err))?;
+
+    if config.verify_declared_length
+        && let Some(declared) = field.content_length()
+        && declared != size as u64
+    {
+        let err = MultipartError::Field {
+            name: field.form_field_name.to_owned(),
+            source: TempFileError::LengthMismatch { declared, actual: size as u64 }.into(),
+        };
+        return Err(override_status(err, config.status_on_error));
+    }
+
+    Ok(TempFile {
+        file,
+        content_type: field.content_type().map(ToOwned::to_owned),
+        file_name: file_name(field),
+        size,
+    })
+}
+
+/// Number of bytes written between two checks of [`TempFileConfig::min_free_space`].
+const FREE_SPACE_CHECK_INTERVAL: usize = 1_048_576; // 1 MiB
+
+fn file_name(field: &Field) -> Option<String> {
+    field.filename().map(Cow::into_owned)
+}
+
+/// Keep the field in memory, or write it to a temporary file once it grows too large.
+///
+/// Fields up to the threshold set with [`MaybeTempFileConfig`] (256 KiB by default) are kept in
+/// memory and count against the form memory limit. Larger fields are written to a temporary
+/// file, as [`TempFile`] does, and only count against the total limit.
+#[derive(Debug)]
+pub enum MaybeTempFile {
+    /// The field was small enough to be kept in memory.
+    Memory {
+        /// The field content.
+        data: Bytes,
+
+        /// The value of the `content-type` header.
+        content_type: Option<Mime>,
+
+        /// The `filename` value in the `content-disposition` header.
+        file_name: Option<String>,
+    },
+
+    /// The field exceeded the threshold and was written to disk.
+    File(TempFile),
+}
+
+impl MaybeTempFile {
+    /// Returns the value of the `content-type` header.
+    pub fn content_type(&self) -> Option<&Mime> {
+        match self {
+            MaybeTempFile::Memory { content_type, .. } => content_type.as_ref(),
+            MaybeTempFile::File(file) => file.content_type.as_ref(),
+        }
+    }
+
+    /// Returns the `filename` value in the `content-disposition` header.
+    pub fn file_name(&self) -> Option<&str> {
+        match self {
+            MaybeTempFile::Memory { file_name, .. } => file_name.as_deref(),
+            MaybeTempFile::File(file) => file.file_name.as_deref(),
+        }
+    }
+
+    /// Returns the size in bytes of the field.
+    pub fn size(&self) -> usize {
+        match self {
+            MaybeTempFile::Memory { data, .. } => data.len(),
+            MaybeTempFile::File(file) => file.size,
+        }
+    }
+}
+
+impl<'t> FieldReader<'t> for MaybeTempFile {
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = FieldKind::File;
+
+    fn read_field(
+        ctx: &'t FormContext,
+        mut field: Field,
+        limits: &'t mut Limits,
+    ) -> Self::Future {
+        Box::pin(async move {
+            let config = ctx.config::<MaybeTempFileConfig>().unwrap_or(&DEFAULT_MAYBE_CONFIG);
+            let mut buf = BytesMut::new();
 
             while let Some(chunk) = field.try_next().await? {
+                if buf.len() + chunk.len() <= config.threshold {
+                    limits.try_consume_limits(chunk.len(), true)?;
+                    buf.extend_from_slice(&chunk);
+                    continue;
+                }
+
+                // the buffered part moves to disk, so it no longer takes up memory
+                limits.memory_limit_remaining += buf.len();
                 limits.try_consume_limits(chunk.len(), false)?;
-                size += chunk.len();
-                file_async.write_all(chunk.as_ref()).await.map_err(|err| {
-                    MultipartError::Field {
-                        name: field.form_field_name.to_owned(),
-                        source: TempFileError::FileIo(err).into(),
-                    }
-                })?;
-            }
+                buf.extend_from_slice(&chunk);
 
-            file_async.flush().await.map_err(|err| MultipartError::Field {
-                name: field.form_field_name.to_owned(),
-                source: TempFileError::FileIo(err).into(),
-            })?;
+                let file =
+                    write_tempfile(TempFileConfig::from_ctx(ctx), &mut field, limits, &buf)
+                        .await?;
+                return Ok(MaybeTempFile::File(file));
+            }
 
-            Ok(TempFile {
-                file,
+            Ok(MaybeTempFile::Memory {
+                data: buf.freeze(),
                 content_type: field.content_type().map(ToOwned::to_owned),
-                file_name: field
-                    .content_disposition()
-                    .expect("multipart form fields should have a content-disposition header")
-                    .get_filename()
-                    .map(ToOwned::to_owned),
-                size,
+                file_name: file_name(&field),
             })
         })
     }
@@ -93,12 +245,37 @@ pub enum TempFileError {
     /// File I/O Error
     #[display("File I/O error: {}", _0)]
     FileIo(io::Error),
+
+    /// The field size differs from its declared `Content-Length`.
+    #[display("Field length {actual} does not match its declared length {declared}")]
+    LengthMismatch {
+        /// The `Content-Length` of the field.
+        declared: u64,
+
+        /// The number of bytes actually received.
+        actual: u64,
+    },
+
+    /// The temp file directory has less free space than [`TempFileConfig::min_free_space`].
+    #[display("Insufficient free space for temporary file: {available} bytes available")]
+    InsufficientSpace {
+        /// Free space left in the directory, in bytes.
+        available: u64,
+
+        /// The configured minimum, in bytes.
+        required: u64,
+    },
 }
 
-/// Return `BadRequest` for `TempFileError`
+/// Return `InternalServerError` for I/O errors, `BadRequest` for length mismatches and
+/// `InsufficientStorage` when running low on disk space
 impl WebResponseError<DefaultError> for TempFileError {
     fn status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            TempFileError::FileIo(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            TempFileError::LengthMismatch { .. } => StatusCode::BAD_REQUEST,
+            TempFileError::InsufficientSpace { .. } => StatusCode::INSUFFICIENT_STORAGE,
+        }
     }
 }
 
@@ -106,9 +283,17 @@ impl WebResponseError<DefaultError> for TempFileError {
 #[derive(Clone)]
 pub struct TempFileConfig {
     directory: Option<PathBuf>,
+    verify_declared_length: bool,
+    min_free_space: Option<u64>,
+    status_on_error: Option<StatusCode>,
 }
 
 impl TempFileConfig {
+    /// Extracts the config from the form context, falling back to the default config.
+    pub(crate) fn from_ctx(ctx: &FormContext) -> &Self {
+        ctx.config::<Self>().unwrap_or(&DEFAULT_CONFIG)
+    }
+
     fn create_tempfile(&self) -> io::Result<NamedTempFile> {
         if let Some(ref dir) = self.directory {
             NamedTempFile::new_in(dir)
@@ -116,6 +301,25 @@ impl TempFileConfig {
             NamedTempFile::new()
         }
     }
+
+    fn check_free_space(&self, field: &Field) -> Result<(), MultipartError> {
+        let Some(required) = self.min_free_space else {
+            return Ok(());
+        };
+        let dir = self.directory.clone().unwrap_or_else(std::env::temp_dir);
+        let available = fs4::available_space(dir).map_err(|err| MultipartError::Field {
+            name: field.form_field_name.to_owned(),
+            source: TempFileError::FileIo(err).into(),
+        })?;
+
+        if available < required {
+            return Err(MultipartError::Field {
+                name: field.form_field_name.to_owned(),
+                source: TempFileError::InsufficientSpace { available, required }.into(),
+            });
+        }
+        Ok(())
+    }
 }
 
 impl TempFileConfig {
@@ -126,12 +330,288 @@ impl TempFileConfig {
         self.directory = Some(dir.as_ref().to_owned());
         self
     }
+
+    /// Sets whether the file size must match the `Content-Length` of the field, if it has one.
+    ///
+    /// The field is then read up to the boundary rather than for its declared length, and a
+    /// mismatch fails with [`TempFileError::LengthMismatch`] and discards the file. Disabled by
+    /// default.
+    pub fn verify_declared_length(mut self, verify_declared_length: bool) -> Self {
+        self.verify_declared_length = verify_declared_length;
+        self
+    }
+
+    /// Sets the free space, in bytes, to leave in the temp file directory.
+    ///
+    /// The free space is checked before creating a file and again after every MiB written. If
+    /// it drops below `min_free_space` the write fails with [`TempFileError::InsufficientSpace`]
+    /// and the partial file is removed. Not checked by default.
+    pub fn min_free_space(mut self, min_free_space: u64) -> Self {
+        self.min_free_space = Some(min_free_space);
+        self
+    }
+
+    /// Sets the response status of files that fail validation, i.e. a
+    /// [`TempFileError::LengthMismatch`], which is `400 Bad Request` by default.
+    ///
+    /// I/O and disk space errors are not caused by the upload and keep their own status.
+    pub fn status_on_error(mut self, status: StatusCode) -> Self {
+        self.status_on_error = Some(status);
+        self
+    }
 }
 
-const DEFAULT_CONFIG: TempFileConfig = TempFileConfig { directory: None };
+const DEFAULT_CONFIG: TempFileConfig = TempFileConfig {
+    directory: None,
+    verify_declared_length: false,
+    min_free_space: None,
+    status_on_error: None,
+};
 
 impl Default for TempFileConfig {
     fn default() -> Self {
         DEFAULT_CONFIG
     }
 }
+
+/// Configuration for the [`MaybeTempFile`] field reader.
+///
+/// Temporary files are created according to [`TempFileConfig`].
+#[derive(Clone)]
+pub struct MaybeTempFileConfig {
+    threshold: usize,
+}
+
+impl MaybeTempFileConfig {
+    /// Sets the largest field size that is kept in memory. By default this is 256 KiB.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+const DEFAULT_MAYBE_CONFIG: MaybeTempFileConfig = MaybeTempFileConfig {
+    threshold: 262_144, // 256 KiB
+};
+
+impl Default for MaybeTempFileConfig {
+    fn default() -> Self {
+        DEFAULT_MAYBE_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Multipart;
+    use ntex::http::header::{self, HeaderMap, HeaderValue};
+    use ntex::web::test::TestRequest;
+    use std::io::Read;
+
+    fn upload(body: &str) -> Field {
+        Field::test_field("upload", body).with_filename("upload.txt")
+    }
+
+    #[ntex::test]
+    async fn test_maybe_temp_file_memory() {
+        let ctx = FormContext::new().with_config(MaybeTempFileConfig::default().threshold(8));
+        let mut limits = Limits::new(100, 100);
+
+        let file = MaybeTempFile::read_field(&ctx, upload("small"), &mut limits).await.unwrap();
+        match file {
+            MaybeTempFile::Memory { ref data, .. } => assert_eq!(data, "small"),
+            MaybeTempFile::File(_) => panic!("expected in-memory field"),
+        }
+        assert_eq!(file.size(), 5);
+        assert_eq!(file.file_name(), Some("upload.txt"));
+        assert_eq!(limits.total_limit_remaining, 95);
+        assert_eq!(limits.memory_limit_remaining, 95);
+    }
+
+    #[test]
+    fn test_maybe_temp_file_spill() {
+        crate::form::block_on_tokio(maybe_temp_file_spill());
+    }
+
+    async fn maybe_temp_file_spill() {
+        let ctx = FormContext::new().with_config(MaybeTempFileConfig::default().threshold(8));
+        let mut limits = Limits::new(100, 100);
+
+        let file = MaybeTempFile::read_field(&ctx, upload("larger than eight"), &mut limits)
+            .await
+            .unwrap();
+        let MaybeTempFile::File(mut file) = file else {
+            panic!("expected spilled field");
+        };
+        let mut content = String::new();
+        file.file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "larger than eight");
+        assert_eq!(file.size, 17);
+        assert_eq!(limits.total_limit_remaining, 83);
+        assert_eq!(limits.memory_limit_remaining, 100);
+
+        let mut limits = Limits::new(10, 100);
+        let res =
+            MaybeTempFile::read_field(&ctx, upload("larger than eight"), &mut limits).await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_verify_declared_length() {
+        crate::form::block_on_tokio(verify_declared_length());
+    }
+
+    async fn verify_declared_length() {
+        let multipart = |declared: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+            );
+            let body = format!(
+                "--abbc\r\n\
+                 Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+                 Content-Length: {declared}\r\n\r\n\
+                 data\r\n\
+                 --abbc--\r\n"
+            );
+            let stream = futures::stream::iter([Ok(Bytes::from(body))]);
+            Multipart::new(&headers, stream)
+        };
+        let read = async |ctx: &FormContext, declared: &str| {
+            let mut multipart = multipart(declared);
+            let field = multipart.try_next().await.unwrap().unwrap();
+            let mut limits = Limits::new(100, 100);
+            TempFile::read_field(ctx, field, &mut limits).await
+        };
+        let ctx = FormContext::new()
+            .with_config(TempFileConfig::default().verify_declared_length(true));
+
+        let file = read(&ctx, "4").await.unwrap();
+        assert_eq!(file.size, 4);
+
+        // shorter and longer than the body up to the boundary
+        for declared in ["10", "2"] {
+            match read(&ctx, declared).await {
+                Err(MultipartError::Field { name, source }) => {
+                    assert_eq!(name, "upload");
+                    assert_eq!(
+                        source.as_response_error().status_code(),
+                        StatusCode::BAD_REQUEST
+                    );
+                }
+                _ => panic!("expected a length mismatch"),
+            }
+        }
+
+        let ctx = FormContext::new().with_config(
+            TempFileConfig::default()
+                .verify_declared_length(true)
+                .status_on_error(StatusCode::UNPROCESSABLE_ENTITY),
+        );
+        match read(&ctx, "2").await {
+            Err(MultipartError::Field { source, .. }) => assert_eq!(
+                source.as_response_error().status_code(),
+                StatusCode::UNPROCESSABLE_ENTITY
+            ),
+            _ => panic!("expected a length mismatch"),
+        }
+
+        // not verified by default, the body is read for its declared length
+        let ctx = FormContext::new();
+        assert_eq!(read(&ctx, "2").await.unwrap().size, 2);
+    }
+
+    #[test]
+    fn test_min_free_space() {
+        crate::form::block_on_tokio(min_free_space());
+    }
+
+    async fn min_free_space() {
+        let ctx =
+            FormContext::new().with_config(TempFileConfig::default().min_free_space(u64::MAX));
+        let mut limits = Limits::new(100, 100);
+
+        let res = TempFile::read_field(&ctx, upload("data"), &mut limits).await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "upload");
+                assert_eq!(
+                    source.as_response_error().status_code(),
+                    StatusCode::INSUFFICIENT_STORAGE
+                );
+            }
+            _ => panic!("expected insufficient space"),
+        }
+
+        let ctx = FormContext::new().with_config(TempFileConfig::default().min_free_space(0));
+        let file = TempFile::read_field(&ctx, upload("data"), &mut limits).await.unwrap();
+        assert_eq!(file.size, 4);
+    }
+
+    #[test]
+    fn test_read_single_file() {
+        crate::form::block_on_tokio(read_single_file_cases());
+    }
+
+    async fn read_single_file_cases() {
+        let request = |body: &'static str| {
+            TestRequest::default()
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+                )
+                .set_payload(body)
+                .to_http_parts()
+        };
+        let config = MultipartFormConfig::default();
+
+        let (req, mut payload) = request(
+            "--abbc\r\n\
+             Content-Disposition: form-data; name=\"avatar\"\r\n\r\n\
+             not a file\r\n\
+             --abbc\r\n\
+             Content-Disposition: form-data; name=\"avatar\"; filename=\"a.png\"\r\n\r\n\
+             image\r\n\
+             --abbc\r\n\
+             Content-Disposition: form-data; name=\"other\"; filename=\"b.png\"\r\n\r\n\
+             other\r\n\
+             --abbc--\r\n",
+        );
+        let file = read_single_file(&req, &mut payload, "avatar", &config).await.unwrap();
+        assert_eq!(file.file_name.as_deref(), Some("a.png"));
+        assert_eq!(file.size, 5);
+
+        let (req, mut payload) = request(
+            "--abbc\r\n\
+             Content-Disposition: form-data; name=\"avatar\"; filename=\"a.png\"\r\n\r\n\
+             image\r\n\
+             --abbc\r\n\
+             Content-Disposition: form-data; name=\"avatar\"; filename=\"b.png\"\r\n\r\n\
+             image\r\n\
+             --abbc--\r\n",
+        );
+        let res = read_single_file(&req, &mut payload, "avatar", &config).await;
+        assert!(matches!(res, Err(MultipartError::DuplicateField(name)) if name == "avatar"));
+
+        let (req, mut payload) = request(
+            "--abbc\r\n\
+             Content-Disposition: form-data; name=\"other\"; filename=\"a.png\"\r\n\r\n\
+             image\r\n\
+             --abbc--\r\n",
+        );
+        let res = read_single_file(&req, &mut payload, "avatar", &config).await;
+        assert!(matches!(res, Err(MultipartError::MissingField(name)) if name == "avatar"));
+
+        // parts are named like in a form
+        let (req, mut payload) = request(
+            "--abbc\r\n\
+             Content-Disposition: form-data; name=\" avatar \"; filename=\"a.png\"\r\n\r\n\
+             image\r\n\
+             --abbc--\r\n",
+        );
+        let config = MultipartFormConfig::default().trim_field_names(true);
+        let file = read_single_file(&req, &mut payload, "avatar", &config).await.unwrap();
+        assert_eq!(file.size, 5);
+    }
+}
@@ -2,14 +2,15 @@
 
 use crate::{
     Field, MultipartError,
-    form::{FieldReader, Limits, bytes::Bytes},
+    form::{FieldKind, FieldReader, FormContext, Limits, bytes::Bytes, override_status},
 };
 use derive_more::{Deref, DerefMut, Display};
 use futures::future::LocalBoxFuture;
 use ntex::http::StatusCode;
-use ntex::web::{DefaultError, HttpRequest, WebResponseError};
-use serde::de::DeserializeOwned;
-use std::str;
+use ntex::web::{DefaultError, WebResponseError};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::{fmt, str};
 
 /// Deserialize from plain text.
 ///
@@ -31,44 +32,144 @@ where
 {
     type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
 
-    fn read_field(req: &'t HttpRequest, field: Field, limits: &'t mut Limits) -> Self::Future {
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
         Box::pin(async move {
-            let config = req.app_state::<TextConfig>().unwrap_or(&DEFAULT_CONFIG);
-
-            if config.validate_content_type {
-                let valid = if let Some(mime) = field.content_type() {
-                    mime.subtype() == mime::PLAIN || mime.suffix() == Some(mime::PLAIN)
-                } else {
-                    // https://datatracker.ietf.org/doc/html/rfc7578#section-4.4
-                    // content type defaults to text/plain, so None should be considered valid
-                    true
-                };
+            let config = ctx.config::<TextConfig>().unwrap_or(&DEFAULT_CONFIG);
+            read_text(config, ctx, field, limits)
+                .await
+                .map(Text)
+                .map_err(|err| override_status(err, config.status_on_error))
+        })
+    }
+}
 
-                if !valid {
-                    return Err(MultipartError::Field {
-                        name: field.form_field_name,
-                        source: TextError::ContentType.into(),
-                    });
-                }
-            }
+/// Read and deserialize a plain text field according to `config`.
+async fn read_text<T: DeserializeOwned>(
+    config: &TextConfig,
+    ctx: &FormContext,
+    field: Field,
+    limits: &mut Limits,
+) -> Result<T, MultipartError> {
+    if config.validate_content_type {
+        let valid = if let Some(mime) = field.content_type() {
+            mime.subtype() == mime::PLAIN || mime.suffix() == Some(mime::PLAIN)
+        } else {
+            // https://datatracker.ietf.org/doc/html/rfc7578#section-4.4
+            // content type defaults to text/plain, so None should be considered valid
+            true
+        };
 
-            let form_field_name = field.form_field_name.clone();
+        if !valid {
+            return Err(MultipartError::Field {
+                name: field.form_field_name,
+                source: TextError::ContentType.into(),
+            });
+        }
+    }
+
+    let form_field_name = field.form_field_name.clone();
+
+    let bytes = Bytes::read_field(ctx, field, limits).await?;
+
+    let text = str::from_utf8(&bytes.data).map_err(|err| MultipartError::Field {
+        name: form_field_name.clone(),
+        source: TextError::Utf8Error(err).into(),
+    })?;
+
+    serde_plain::from_str(text).map_err(|err| MultipartError::Field {
+        name: form_field_name,
+        source: TextError::Deserialize(err).into(),
+    })
+}
+
+/// Deserialize a plain text field into a unit-only enum, reporting the allowed values when it
+/// holds any other value.
+///
+/// Works like [`Text`] and uses the same [`TextConfig`], but an unknown value fails with
+/// [`TextError::NotOneOf`], e.g. `Value "purple" is not allowed, expected one of [red, green,
+/// blue]`, instead of a generic deserialize error. The allowed values are the variant names
+/// serde uses for `T`, so `#[serde(rename)]` and `#[serde(rename_all)]` are honored.
+#[derive(Debug, Deref, DerefMut)]
+pub struct OneOf<T: DeserializeOwned>(pub T);
 
-            let bytes = Bytes::read_field(req, field, limits).await?;
+impl<T: DeserializeOwned> OneOf<T> {
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'t, T> FieldReader<'t> for OneOf<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
 
-            let text = str::from_utf8(&bytes.data).map_err(|err| MultipartError::Field {
-                name: form_field_name.clone(),
-                source: TextError::Utf8Error(err).into(),
-            })?;
+    const KIND: FieldKind = FieldKind::Data;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let form_field_name = field.form_field_name.clone();
+            let text = Text::<String>::read_field(ctx, field, limits).await?.into_inner();
 
-            Ok(Text(serde_plain::from_str(text).map_err(|err| MultipartError::Field {
-                name: form_field_name,
-                source: TextError::Deserialize(err).into(),
-            })?))
+            serde_plain::from_str(&text).map(OneOf).map_err(|err| {
+                let source = match enum_variants::<T>() {
+                    Some(allowed) => TextError::NotOneOf { value: text, allowed },
+                    None => TextError::Deserialize(err),
+                };
+                let err =
+                    MultipartError::Field { name: form_field_name, source: source.into() };
+                let config = ctx.config::<TextConfig>().unwrap_or(&DEFAULT_CONFIG);
+                override_status(err, config.status_on_error)
+            })
         })
     }
 }
 
+/// Returns the variant names of `T` if it deserializes from an enum.
+fn enum_variants<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut variants = None;
+    let _ = T::deserialize(VariantsProbe(&mut variants));
+    variants
+}
+
+/// Deserializer that fails on any input, recording the variants of the enum it was asked for.
+struct VariantsProbe<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for VariantsProbe<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not an enum"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(variants);
+        Err(de::Error::custom("probed"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct AllowedValues(&'static [&'static str]);
+
+impl fmt::Display for AllowedValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.0.join(", "))
+    }
+}
+
 #[derive(Debug, Display)]
 #[non_exhaustive]
 pub enum TextError {
@@ -83,6 +184,16 @@ pub enum TextError {
     /// Content type error.
     #[display("Content type error")]
     ContentType,
+
+    /// Value is not one of the variants allowed by a [`OneOf`] field.
+    #[display("Value {value:?} is not allowed, expected one of {}", AllowedValues(allowed))]
+    NotOneOf {
+        /// The value as received.
+        value: String,
+
+        /// The allowed values.
+        allowed: &'static [&'static str],
+    },
 }
 
 /// Return `BadRequest` for `TextError`
@@ -96,6 +207,7 @@ impl WebResponseError<DefaultError> for TextError {
 #[derive(Clone)]
 pub struct TextConfig {
     validate_content_type: bool,
+    status_on_error: Option<StatusCode>,
 }
 
 impl TextConfig {
@@ -107,12 +219,93 @@ impl TextConfig {
         self.validate_content_type = validate_content_type;
         self
     }
+
+    /// Sets the response status of fields that fail to be read, e.g. `422 Unprocessable
+    /// Entity` for values that do not deserialize. By default the status of [`TextError`],
+    /// `400 Bad Request`, is used.
+    ///
+    /// Limit and stream errors keep their own status.
+    pub fn status_on_error(mut self, status: StatusCode) -> Self {
+        self.status_on_error = Some(status);
+        self
+    }
 }
 
-const DEFAULT_CONFIG: TextConfig = TextConfig { validate_content_type: true };
+const DEFAULT_CONFIG: TextConfig =
+    TextConfig { validate_content_type: true, status_on_error: None };
 
 impl Default for TextConfig {
     fn default() -> Self {
         DEFAULT_CONFIG
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+
+    #[ntex::test]
+    async fn test_one_of() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let color =
+            OneOf::<Color>::read_field(&ctx, Field::test_field("color", "green"), &mut limits)
+                .await
+                .unwrap();
+        assert_eq!(color.into_inner(), Color::Green);
+
+        let res =
+            OneOf::<Color>::read_field(&ctx, Field::test_field("color", "purple"), &mut limits)
+                .await;
+        match res {
+            Err(MultipartError::Field { name, source }) => {
+                assert_eq!(name, "color");
+                assert_eq!(
+                    source.to_string(),
+                    "Value \"purple\" is not allowed, expected one of [red, green, blue]"
+                );
+            }
+            _ => panic!("expected a field error"),
+        }
+
+        let ctx = FormContext::new().with_config(
+            TextConfig::default().status_on_error(StatusCode::UNPROCESSABLE_ENTITY),
+        );
+        for res in [
+            OneOf::<Color>::read_field(&ctx, Field::test_field("color", "purple"), &mut limits)
+                .await
+                .map(|_| ()),
+            Text::<u32>::read_field(&ctx, Field::test_field("color", "x"), &mut limits)
+                .await
+                .map(|_| ()),
+        ] {
+            match res {
+                Err(MultipartError::Field { source, .. }) => assert_eq!(
+                    source.as_response_error().status_code(),
+                    StatusCode::UNPROCESSABLE_ENTITY
+                ),
+                _ => panic!("expected a field error"),
+            }
+        }
+
+        // not an enum, the deserialize error is kept
+        let res =
+            OneOf::<u32>::read_field(&ctx, Field::test_field("color", "x"), &mut limits).await;
+        match res {
+            Err(MultipartError::Field { source, .. }) => {
+                assert!(source.to_string().starts_with("Plain text deserialize error"))
+            }
+            _ => panic!("expected a field error"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Bounds the time spent reading a field.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits},
+};
+use derive_more::{Deref, DerefMut};
+use futures::future::LocalBoxFuture;
+use std::time::Duration;
+
+/// Read a field with `R`, failing with [`MultipartError::Timeout`] if it takes longer than
+/// `MILLIS` milliseconds.
+///
+/// The deadline only covers this field, independently of any form-wide timeout.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     metadata: Timed<Json<Metadata>, 500>,
+///     comment: Text<String>,
+/// }
+/// ```
+#[derive(Debug, Deref, DerefMut)]
+pub struct Timed<R, const MILLIS: u64>(pub R);
+
+impl<R, const MILLIS: u64> Timed<R, MILLIS> {
+    /// Unwraps into inner value.
+    pub fn into_inner(self) -> R {
+        self.0
+    }
+}
+
+impl<'t, R, const MILLIS: u64> FieldReader<'t> for Timed<R, MILLIS>
+where
+    R: FieldReader<'t>,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = R::KIND;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let read = R::read_field(ctx, field, limits);
+            match ntex::time::timeout(Duration::from_millis(MILLIS), read).await {
+                Ok(inner) => Ok(Timed(inner?)),
+                Err(()) => Err(MultipartError::Timeout),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::text::Text;
+
+    /// Reader that takes its time before giving up on the field.
+    struct Slow;
+
+    impl<'t> FieldReader<'t> for Slow {
+        type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+        fn read_field(_: &'t FormContext, _: Field, _: &'t mut Limits) -> Self::Future {
+            Box::pin(async move {
+                ntex::time::sleep(Duration::from_millis(200)).await;
+                Ok(Slow)
+            })
+        }
+    }
+
+    #[ntex::test]
+    async fn test_timed() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let text = Timed::<Text<String>, 1000>::read_field(
+            &ctx,
+            Field::test_field("comment", "ntex"),
+            &mut limits,
+        )
+        .await
+        .unwrap();
+        assert_eq!(text.into_inner().into_inner(), "ntex");
+
+        let res = Timed::<Slow, 10>::read_field(
+            &ctx,
+            Field::test_field("comment", "ntex"),
+            &mut limits,
+        )
+        .await;
+        assert!(matches!(res, Err(MultipartError::Timeout)));
+    }
+}
@@ -0,0 +1,79 @@
+//! Keeps the headers of a field next to its value.
+
+use crate::{
+    Field, MultipartError,
+    form::{FieldKind, FieldReader, FormContext, Limits},
+};
+use derive_more::{Deref, DerefMut};
+use futures::future::LocalBoxFuture;
+use ntex::http::HeaderMap;
+
+/// Read a field with `R`, keeping a copy of the field headers.
+///
+/// Useful for readers such as [`Json`](super::json::Json) or [`Text`](super::text::Text) that
+/// do not expose the headers of the part, e.g. to check a custom `X-Checksum` header.
+///
+/// ```rust,ignore
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     metadata: WithHeaders<Json<Metadata>>,
+/// }
+/// ```
+#[derive(Debug, Deref, DerefMut)]
+pub struct WithHeaders<R> {
+    /// The value read by the inner reader.
+    #[deref]
+    #[deref_mut]
+    pub value: R,
+
+    /// The headers of the field.
+    pub headers: HeaderMap,
+}
+
+impl<R> WithHeaders<R> {
+    /// Splits into the inner value and the field headers.
+    pub fn into_parts(self) -> (R, HeaderMap) {
+        (self.value, self.headers)
+    }
+}
+
+impl<'t, R> FieldReader<'t> for WithHeaders<R>
+where
+    R: FieldReader<'t>,
+{
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    const KIND: FieldKind = R::KIND;
+
+    fn read_field(ctx: &'t FormContext, field: Field, limits: &'t mut Limits) -> Self::Future {
+        // the inner reader consumes the field, so copy the headers first
+        let headers = field.headers().clone();
+
+        Box::pin(async move {
+            let value = R::read_field(ctx, field, limits).await?;
+            Ok(WithHeaders { value, headers })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::text::Text;
+
+    #[ntex::test]
+    async fn test_with_headers() {
+        let ctx = FormContext::new();
+        let mut limits = Limits::new(1024, 1024);
+
+        let field = Field::test_field("comment", b"ntex").with_header("x-checksum", "abc");
+
+        let text =
+            WithHeaders::<Text<String>>::read_field(&ctx, field, &mut limits).await.unwrap();
+        assert_eq!(text.as_str(), "ntex");
+
+        let (value, headers) = text.into_parts();
+        assert_eq!(value.into_inner(), "ntex");
+        assert_eq!(headers.get("x-checksum").unwrap(), "abc");
+    }
+}
@@ -0,0 +1,178 @@
+//! Multipart pass-through re-encoding
+
+use crate::writer::{content_type, encode_end, encode_head, generate_boundary};
+use crate::{Field, Multipart, MultipartError, validate_boundary};
+use futures::Stream;
+use ntex::util::Bytes;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Re-encodes a parsed [`Multipart`] stream, part by part, without buffering it.
+///
+/// Every field is emitted with its original headers and content, delimited by either the
+/// original boundary or a fresh one. The result can be used as a request body for
+/// forwarding uploads to another service.
+pub struct MultipartForward {
+    multipart: Multipart,
+    boundary: String,
+    subtype: String,
+    field: Option<Field>,
+    done: bool,
+}
+
+impl MultipartForward {
+    /// Forward multipart stream, preserving its boundary.
+    pub fn new(multipart: Multipart) -> Self {
+        // a parsed boundary has been validated already
+        let boundary = multipart.boundary_param().unwrap_or_else(generate_boundary);
+        Self::build(multipart, boundary)
+    }
+
+    /// Forward multipart stream, delimiting parts with the provided boundary.
+    ///
+    /// Fails with [`MultipartError::Boundary`] if the boundary is not valid, see
+    /// [`validate_boundary`].
+    pub fn with_boundary(
+        multipart: Multipart,
+        boundary: impl Into<String>,
+    ) -> Result<Self, MultipartError> {
+        let boundary = boundary.into();
+        validate_boundary(&boundary)?;
+        Ok(Self::build(multipart, boundary))
+    }
+
+    fn build(multipart: Multipart, boundary: String) -> Self {
+        let subtype = multipart.subtype().unwrap_or_else(|| "mixed".to_owned());
+        MultipartForward { multipart, subtype, boundary, field: None, done: false }
+    }
+
+    /// Returns the boundary of the re-encoded stream.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns Content-Type value for the re-encoded stream.
+    pub fn content_type(&self) -> String {
+        content_type(&self.subtype, &self.boundary)
+    }
+}
+
+impl Stream for MultipartForward {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(ref mut field) = this.field {
+            return match Pin::new(field).poll_next(cx) {
+                Poll::Ready(None) => {
+                    this.field = None;
+                    Poll::Ready(Some(Ok(Bytes::from_static(b"\r\n"))))
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    this.done = true;
+                    this.field = None;
+                    Poll::Ready(Some(Err(err)))
+                }
+                res => res,
+            };
+        }
+
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut this.multipart).poll_next(cx) {
+            Poll::Ready(Some(Ok(field))) => {
+                let head = encode_head(&this.boundary, field.headers());
+                this.field = Some(field);
+                Poll::Ready(Some(Ok(head)))
+            }
+            Poll::Ready(Some(Err(err))) => {
+                this.done = true;
+                Poll::Ready(Some(Err(err)))
+            }
+            Poll::Ready(None) => {
+                this.done = true;
+                Poll::Ready(Some(Ok(encode_end(&this.boundary))))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{StreamExt, TryStreamExt, stream};
+    use ntex::http::HeaderMap;
+    use ntex::http::header::{self, HeaderValue};
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        headers
+    }
+
+    #[ntex::test]
+    async fn test_forward() {
+        let body = Bytes::from_static(
+            b"preamble\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"file\"; filename=\"fn.txt\"\r\n\
+              Content-Type: text/plain\r\n\r\n\
+              test\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+              data\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let multipart = Multipart::new(
+            &headers("multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0"),
+            stream::iter([Ok(body)]),
+        );
+
+        let forward = MultipartForward::with_boundary(multipart, "fresh-boundary").unwrap();
+        assert_eq!(forward.content_type(), "multipart/form-data; boundary=fresh-boundary");
+        let out: Vec<Bytes> = forward.try_collect().await.unwrap();
+        let out = out.concat();
+
+        // header order within a part is not preserved by `HeaderMap`
+        let disposition =
+            "content-disposition: form-data; name=\"file\"; filename=\"fn.txt\"\r\n";
+        let content_type = "content-type: text/plain\r\n";
+        let tail = "\r\ntest\r\n\
+                    --fresh-boundary\r\n\
+                    content-disposition: form-data; name=\"text\"\r\n\r\n\
+                    data\r\n\
+                    --fresh-boundary--\r\n";
+        let expected = [
+            format!("--fresh-boundary\r\n{disposition}{content_type}{tail}"),
+            format!("--fresh-boundary\r\n{content_type}{disposition}{tail}"),
+        ];
+        assert!(expected.iter().any(|e| e.as_bytes() == out.as_slice()));
+
+        let mut multipart = Multipart::new(
+            &headers("multipart/form-data; boundary=fresh-boundary"),
+            stream::iter([Ok(Bytes::from(out))]),
+        );
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert_eq!(field.measure().await.unwrap(), 4);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("text"));
+        assert_eq!(field.measure().await.unwrap(), 4);
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_forward_error() {
+        let multipart = Multipart::new(&headers("text/plain"), stream::empty());
+        let mut forward = MultipartForward::new(multipart);
+        assert!(matches!(
+            forward.next().await,
+            Some(Err(MultipartError::IncompatibleContentType))
+        ));
+        assert!(forward.next().await.is_none());
+    }
+}
@@ -1,18 +1,50 @@
 #![allow(dead_code, clippy::borrow_interior_mutable_const)]
 
+mod adapter;
+#[cfg(feature = "compress")]
+mod decompress;
 mod error;
 mod extractor;
 pub(crate) mod field;
 #[cfg(feature = "form")]
 pub mod form;
+mod forward;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod multipart;
 #[cfg(feature = "form")]
 mod multipart_form;
+mod part;
 pub(crate) mod payload;
 pub(crate) mod safety;
+mod scan;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
+pub(crate) mod writer;
 
+#[cfg(feature = "digest")]
+pub use self::adapter::HashingField;
+pub use self::adapter::{
+    ChunkMap, ChunkedField, Inspect, InspectField, MapChunksField, MaxChunks, MaxChunksField,
+};
 pub use self::error::MultipartError;
-pub use self::field::Field;
-pub use self::multipart::Multipart;
 #[cfg(feature = "form")]
-pub use self::multipart_form::{MultipartCollect, MultipartForm};
+pub use self::extractor::{parse_form, parse_form_with};
+pub use self::field::{Field, FilenameDecoding};
+#[cfg(feature = "form")]
+pub use self::form::temp_file::read_single_file;
+pub use self::forward::MultipartForward;
+#[cfg(feature = "metrics")]
+pub use self::metrics::{MultipartMetrics, RejectReason};
+pub use self::multipart::{Multipart, validate_boundary};
+#[cfg(feature = "form")]
+pub use self::multipart_form::{
+    FieldOrder, FormStats, MultipartCollect, MultipartForm, MultipartFormConfig,
+    MultipartFormWithRaw, MultipartFormWithStats, ParseSizeError, TruncatedFields,
+};
+pub use self::part::Part;
+pub use self::payload::{BufPolicy, DefaultBufPolicy};
+pub use self::scan::{PartMetadata, ScannedMultipart};
+#[cfg(any(test, feature = "test-util"))]
+pub use self::test_util::{ParsedMultipart, ParsedPart};
+pub use self::writer::MultipartWriter;
@@ -0,0 +1,227 @@
+//! Counters for monitoring the multipart parser
+
+use crate::{Field, MultipartError};
+use ntex::http::error::PayloadError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of parsed parts, consumed bytes and rejections.
+///
+/// Share a handle between requests, e.g. with [`Multipart::metrics`] or
+/// [`MultipartFormConfig::metrics`], and read the totals from a scrape endpoint. All counters
+/// are relaxed atomics, only monotonic totals are kept.
+///
+/// [`Multipart::metrics`]: crate::Multipart::metrics
+/// [`MultipartFormConfig::metrics`]: crate::MultipartFormConfig::metrics
+#[derive(Debug, Default)]
+pub struct MultipartMetrics {
+    parts_parsed: AtomicU64,
+    bytes_consumed: AtomicU64,
+    rejected: [AtomicU64; RejectReason::ALL.len()],
+}
+
+impl MultipartMetrics {
+    /// Create a handle with all counters at zero.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Returns the number of part headers parsed successfully.
+    pub fn parts_parsed(&self) -> u64 {
+        self.parts_parsed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes read from payload streams.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.bytes_consumed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of streams or forms rejected for `reason`.
+    pub fn rejected(&self, reason: RejectReason) -> u64 {
+        self.rejected[reason as usize].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of rejections caused by a limit, same as
+    /// `rejected(RejectReason::Limit)`.
+    pub fn limit_hits(&self) -> u64 {
+        self.rejected(RejectReason::Limit)
+    }
+
+    pub(crate) fn record_bytes(&self, len: usize) {
+        self.bytes_consumed.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_error(&self, err: &MultipartError) {
+        self.rejected[RejectReason::of(err) as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Category of an error counted by [`MultipartMetrics::rejected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RejectReason {
+    /// A size, count or nesting limit was exceeded.
+    Limit,
+
+    /// The Content-Type or the part headers are missing or malformed.
+    Headers,
+
+    /// The multipart body is malformed, e.g. incomplete or with a boundary in a part.
+    Framing,
+
+    /// The payload stream failed or timed out.
+    Payload,
+
+    /// The form extractor rejected a field, e.g. unknown, duplicate or invalid.
+    Form,
+}
+
+impl RejectReason {
+    /// Every reason, in declaration order.
+    pub const ALL: [RejectReason; 5] = [
+        RejectReason::Limit,
+        RejectReason::Headers,
+        RejectReason::Framing,
+        RejectReason::Payload,
+        RejectReason::Form,
+    ];
+
+    /// Categorize `err`.
+    pub fn of(err: &MultipartError) -> Self {
+        match err {
+            MultipartError::Payload(PayloadError::Overflow)
+            | MultipartError::NestingTooDeep
+            | MultipartError::HeaderCountExceeded
+            | MultipartError::TooManyPartHeaders
+            | MultipartError::HeaderValueTooLong
+            | MultipartError::TooManyChunks
+            | MultipartError::TooManyEmptyParts
+            | MultipartError::FieldSizeExceeded { .. } => RejectReason::Limit,
+            MultipartError::NoContentType
+            | MultipartError::ParseContentType
+            | MultipartError::IncompatibleContentType
+            | MultipartError::Boundary
+            | MultipartError::ContentDispositionMissing
+            | MultipartError::ContentDispositionNameMissing
+            | MultipartError::InvalidFieldName
+            | MultipartError::UnterminatedQuote
+            | MultipartError::Decode(_) => RejectReason::Headers,
+            MultipartError::Nested
+            | MultipartError::NestedBoundaryMissing
+            | MultipartError::BoundaryInBody
+            | MultipartError::Empty
+            | MultipartError::Incomplete
+            | MultipartError::NotConsumed => RejectReason::Framing,
+            MultipartError::Payload(_)
+            | MultipartError::Timeout
+            | MultipartError::Decompress(_)
+            | MultipartError::Write(_) => RejectReason::Payload,
+            MultipartError::Field { .. }
+            | MultipartError::DuplicateField(_)
+            | MultipartError::MissingField(_)
+            | MultipartError::FieldTypeMismatch(_)
+            | MultipartError::InconsistentPart { .. }
+            | MultipartError::UnknownField(_) => RejectReason::Form,
+        }
+    }
+
+    /// Returns a lowercase name of the reason, e.g. for a metric label.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectReason::Limit => "limit",
+            RejectReason::Headers => "headers",
+            RejectReason::Framing => "framing",
+            RejectReason::Payload => "payload",
+            RejectReason::Form => "form",
+        }
+    }
+}
+
+/// A metrics handle attached to a payload buffer.
+#[derive(Debug, Clone)]
+pub(crate) struct Recorder {
+    metrics: Arc<MultipartMetrics>,
+    /// Whether stream errors are counted here, the form extractor counts them itself.
+    errors: bool,
+}
+
+impl Recorder {
+    pub(crate) fn new(metrics: Arc<MultipartMetrics>, errors: bool) -> Self {
+        Recorder { metrics, errors }
+    }
+
+    pub(crate) fn bytes(&self, len: usize) {
+        self.metrics.record_bytes(len);
+    }
+
+    pub(crate) fn part(&self, res: &Result<Field, MultipartError>) {
+        match res {
+            Ok(_) => {
+                self.metrics.parts_parsed.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => self.error(err),
+        }
+    }
+
+    pub(crate) fn error(&self, err: &MultipartError) {
+        if self.errors {
+            self.metrics.record_error(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Multipart;
+    use futures::{StreamExt, stream};
+    use ntex::http::HeaderMap;
+    use ntex::http::header::{self, HeaderValue};
+    use ntex::util::Bytes;
+
+    #[ntex::test]
+    async fn test_multipart_metrics() {
+        let body = Bytes::from_static(
+            b"--abbc\r\n\
+              Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+              one\r\n\
+              --abbc\r\n\
+              Content-Disposition: form-data; name=\"b\"\r\n\r\n\
+              two",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+        );
+        let metrics = MultipartMetrics::new();
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(body.clone())])).metrics(metrics.clone());
+
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.next().await.unwrap().unwrap(), "one");
+        drop(field);
+        let mut field = multipart.next().await.unwrap().unwrap();
+        while let Some(Ok(_)) = field.next().await {}
+        drop(field);
+
+        assert_eq!(metrics.parts_parsed(), 2);
+        assert_eq!(metrics.bytes_consumed(), body.len() as u64);
+        assert_eq!(metrics.rejected(RejectReason::Framing), 1);
+        assert_eq!(metrics.limit_hits(), 0);
+    }
+
+    #[test]
+    fn test_reject_reason() {
+        assert_eq!(
+            RejectReason::of(&MultipartError::Payload(PayloadError::Overflow)),
+            RejectReason::Limit
+        );
+        assert_eq!(RejectReason::of(&MultipartError::Timeout), RejectReason::Payload);
+        assert_eq!(
+            RejectReason::of(&MultipartError::MissingField("a".to_owned())),
+            RejectReason::Form
+        );
+        assert_eq!(RejectReason::ALL.map(RejectReason::as_str)[1], "headers");
+    }
+}
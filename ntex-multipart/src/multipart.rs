@@ -3,21 +3,33 @@
 use crate::Field;
 use crate::error::MultipartError;
 use crate::field::InnerField;
-use crate::payload::{PayloadBuffer, PayloadRef};
+use crate::part::Part;
+use crate::payload::{BufPolicy, PayloadBuffer, PayloadRef};
 use crate::safety::Safety;
-use futures::stream::Stream;
+use futures::io::{AsyncRead, AsyncReadExt};
+use futures::stream::{self, Stream, StreamExt};
 use mime::Mime;
 use ntex::http::error::{DecodeError, PayloadError};
 use ntex::http::header::{self, HeaderMap, HeaderName, HeaderValue};
-use ntex::util::Bytes;
+use ntex::time::Sleep;
+use ntex::util::{Bytes, BytesMut};
 use ntex_files::header::DispositionType;
 use ntex_files::header::{ContentDisposition, Header};
-use std::cell::RefCell;
+#[cfg(feature = "form")]
+use {crate::form::Limits, futures::TryStreamExt, std::collections::HashMap};
+
+use std::cell::{Cell, RefCell};
 use std::task::{Context, Poll};
-use std::{convert::TryFrom, pin::Pin, rc::Rc};
+use std::{convert::TryFrom, pin::Pin, rc::Rc, time::Duration};
 
 const MAX_HEADERS: usize = 32;
 
+/// Default for [`Multipart::max_nesting_depth`].
+const DEFAULT_MAX_NESTING_DEPTH: usize = 4;
+
+/// Default chunk size for [`Multipart::from_async_read`].
+const DEFAULT_READ_CHUNK_SIZE: usize = 8192;
+
 /// The server-side implementation of `multipart/form-data` requests.
 ///
 /// This will parse the incoming stream into `MultipartItem` instances via its
@@ -28,6 +40,7 @@ pub struct Multipart {
     safety: Safety,
     error: Option<MultipartError>,
     inner: Option<Rc<RefCell<InnerMultipart>>>,
+    content_disposition: Option<ContentDisposition>,
 }
 
 enum InnerMultipartItem {
@@ -53,15 +66,213 @@ struct InnerMultipart {
     boundary: String,
     state: InnerState,
     item: InnerMultipartItem,
+    settings: Settings,
+    /// Number of multipart bodies this one is nested in.
+    depth: usize,
+}
+
+/// Parsing options applied to every part.
+#[derive(Default, Clone)]
+struct Settings {
+    strict_headers: bool,
+    max_headers_per_part: Option<usize>,
+    raw_headers: bool,
+    require_part: bool,
+    unfold_headers: bool,
+    default_content_type: Option<Mime>,
+    max_nesting_depth: Option<usize>,
+    reject_boundary_in_body: bool,
+    max_header_value_len: Option<usize>,
+    fallback_field_name: Option<String>,
+    validate_field_names: bool,
+    allow_nested: bool,
+}
+
+/// Parsing options and depth a nested multipart body inherits from the body containing it,
+/// see [`Field::nested_multipart`].
+#[derive(Default, Clone)]
+pub(crate) struct Nesting {
+    settings: Settings,
+    depth: usize,
+}
+
+/// Part headers that must not be repeated in strict mode.
+const UNIQUE_HEADERS: [&str; 4] =
+    ["content-disposition", "content-type", "content-length", "content-transfer-encoding"];
+
+/// Scan a raw Content-Type value for a `boundary` parameter, ignoring the parameter's case
+/// and position.
+fn find_boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+        if value.is_empty() { None } else { Some(value.to_owned()) }
+    })
+}
+
+/// Recover the media type and a quoted boundary from a Content-Type the mime parser rejects,
+/// e.g. because of an unrelated malformed parameter.
+fn fallback_content_type(content_type: &str) -> Option<(Mime, String)> {
+    let (essence, params) = content_type.split_once(';')?;
+    let ct = essence.trim().parse::<Mime>().ok()?;
+    if ct.type_() != mime::MULTIPART {
+        return None;
+    }
+
+    // the parameter name must not be the tail of another name, like `x-boundary`
+    let lower = params.to_ascii_lowercase();
+    let (idx, _) = lower.match_indices("boundary=\"").find(|&(idx, _)| {
+        lower[..idx].chars().next_back().is_none_or(|c| c == ';' || c.is_ascii_whitespace())
+    })?;
+    let start = idx + "boundary=\"".len();
+    let len = params[start..].find('"')?;
+    Some((ct, params[start..start + len].to_owned()))
+}
+
+/// Check that `boundary` is a valid multipart boundary.
+///
+/// Per RFC 2046 a boundary is 1 to 70 characters long, made of letters, digits, spaces and
+/// `'()+_,-./:=?`, and does not end with a space. Fails with [`MultipartError::Boundary`].
+pub fn validate_boundary(boundary: &str) -> Result<(), MultipartError> {
+    let valid = (1..=70).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary.bytes().all(|b| b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b));
+
+    if valid { Ok(()) } else { Err(MultipartError::Boundary) }
+}
+
+/// Join obs-fold continuation lines, a CRLF followed by spaces or tabs, into a single space.
+fn unfold_headers(block: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(block.len());
+    let mut i = 0;
+    while i < block.len() {
+        if block[i..].starts_with(b"\r\n") && matches!(block.get(i + 2), Some(b' ' | b'\t')) {
+            i += 2;
+            while matches!(block.get(i), Some(b' ' | b'\t')) {
+                i += 1;
+            }
+            out.push(b' ');
+        } else {
+            out.push(block[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Headers whose parameters are checked by [`has_unterminated_quote`].
+const QUOTED_PARAM_HEADERS: [&str; 2] = ["content-disposition", "content-type"];
+
+/// Check that every quoted parameter value, a `"` following `=`, of the `Content-Disposition`
+/// and `Content-Type` headers is closed on its own line.
+///
+/// A quoted-string can not contain a CRLF, so an unclosed quote means the value was cut by a
+/// line break, possibly by the blank line that ends the header block. Other headers are not
+/// parameterized and may hold a lone quote.
+fn has_unterminated_quote(block: &[u8]) -> bool {
+    block.split(|&b| b == b'\n').any(|line| {
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        let name = line[..colon].trim_ascii();
+        if !QUOTED_PARAM_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h.as_bytes())) {
+            return false;
+        }
+
+        let mut bytes = line[colon + 1..].iter().copied();
+        let mut prev = 0;
+        while let Some(b) = bytes.next() {
+            if b == b'"' && prev == b'=' {
+                let mut escaped = false;
+                let closed = bytes.by_ref().any(|b| {
+                    let end = b == b'"' && !escaped;
+                    escaped = b == b'\\' && !escaped;
+                    end
+                });
+                if !closed {
+                    return true;
+                }
+            }
+            if !b.is_ascii_whitespace() {
+                prev = b;
+            }
+        }
+        false
+    })
+}
+
+/// Check a part header against the strict parsing rules.
+fn is_strict_header(headers: &HeaderMap, name: &HeaderName, value: &[u8]) -> bool {
+    let repeated = UNIQUE_HEADERS.contains(&name.as_str()) && headers.contains_key(name);
+    !repeated && value.iter().all(|c| matches!(c, b'\t' | b' '..=b'~'))
 }
 
 impl Multipart {
     /// Create multipart instance for boundary.
+    ///
+    /// With the `compress` feature, a body sent with a request-level `Content-Encoding` of
+    /// `gzip` or `deflate` is decompressed before parsing by the decoder of ntex, like its
+    /// `Json` and `Form` extractors do, see [`Multipart::max_decompressed_size`].
     pub fn new<S>(headers: &HeaderMap, stream: S) -> Multipart
     where
         S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
     {
-        match Self::boundary(headers) {
+        #[cfg(feature = "compress")]
+        let (stream, decompressed) = crate::decompress::decode(headers, stream);
+        let mut multipart = Self::with_content_type(Self::boundary(headers), stream);
+        #[cfg(feature = "compress")]
+        if let Some(ref inner) = multipart.inner {
+            inner.borrow().payload.buffer().decompressed = decompressed;
+        }
+        multipart.content_disposition =
+            headers.get(&header::CONTENT_DISPOSITION).and_then(|hv| {
+                ContentDisposition::parse_header(&ntex_files::header::Raw::from(hv.as_bytes()))
+                    .ok()
+            });
+        multipart
+    }
+
+    /// Create multipart instance for boundary, buffering the payload with a custom
+    /// [`BufPolicy`].
+    ///
+    /// This is meant for memory sensitive servers that need control over how the parser
+    /// buffer grows, [`Multipart::new`] uses the default policy.
+    pub fn new_with_policy<S, P>(headers: &HeaderMap, stream: S, policy: P) -> Multipart
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
+        P: BufPolicy + 'static,
+    {
+        let multipart = Self::new(headers, stream);
+        if let Some(inner) = &multipart.inner {
+            inner.borrow().payload.buffer().policy = Box::new(policy);
+        }
+        multipart
+    }
+
+    /// Create multipart instance from an already parsed Content-Type.
+    ///
+    /// The boundary is taken from `ct`, the request headers are not consulted.
+    pub fn from_mime<S>(ct: Mime, stream: S) -> Multipart
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
+    {
+        let boundary = Self::mime_boundary(&ct, ct.as_ref());
+        Self::with_content_type(boundary.map(|boundary| (ct, boundary)), stream)
+    }
+
+    fn with_content_type<S>(
+        content_type: Result<(Mime, String), MultipartError>,
+        stream: S,
+    ) -> Multipart
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
+    {
+        match content_type {
             Ok((ct, boundary)) => Multipart {
                 error: None,
                 safety: Safety::new(),
@@ -71,26 +282,359 @@ impl Multipart {
                     payload: PayloadRef::new(PayloadBuffer::new(Box::new(stream))),
                     state: InnerState::FirstBoundary,
                     item: InnerMultipartItem::None,
+                    settings: Settings::default(),
+                    depth: 0,
                 }))),
+                content_disposition: None,
+            },
+            Err(err) => Multipart {
+                error: Some(err),
+                safety: Safety::new(),
+                inner: None,
+                content_disposition: None,
             },
-            Err(err) => Multipart { error: Some(err), safety: Safety::new(), inner: None },
         }
     }
 
+    /// Create multipart instance parsing the body of a part with a `multipart/*` content
+    /// type, see [`Field::nested_multipart`].
+    ///
+    /// Errors of the field are put in `field_error` before it yields a payload error, and are
+    /// returned in place of that error.
+    pub(crate) fn nested<S>(
+        ct: Mime,
+        stream: S,
+        nesting: Nesting,
+        content_disposition: Option<ContentDisposition>,
+        field_error: Rc<Cell<Option<MultipartError>>>,
+    ) -> Multipart
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin + 'static,
+    {
+        let mut multipart = Self::from_mime(ct, stream);
+        if let Some(ref inner) = multipart.inner {
+            let mut inner = inner.borrow_mut();
+            inner.settings = nesting.settings;
+            inner.depth = nesting.depth + 1;
+            inner.payload.buffer().field_error = Some(field_error);
+        }
+        multipart.content_disposition = content_disposition;
+        multipart
+    }
+
+    /// Create multipart instance reading the body from an [`AsyncRead`] source.
+    ///
+    /// The reader is consumed in chunks of up to 8KiB.
+    pub fn from_async_read<R>(headers: &HeaderMap, reader: R) -> Multipart
+    where
+        R: AsyncRead + Unpin + 'static,
+    {
+        Self::from_async_read_with_chunk_size(headers, reader, DEFAULT_READ_CHUNK_SIZE)
+    }
+
+    /// Create multipart instance reading the body from an [`AsyncRead`] source in chunks of up
+    /// to `chunk_size` bytes.
+    pub fn from_async_read_with_chunk_size<R>(
+        headers: &HeaderMap,
+        reader: R,
+        chunk_size: usize,
+    ) -> Multipart
+    where
+        R: AsyncRead + Unpin + 'static,
+    {
+        let chunk_size = chunk_size.max(1);
+        let stream = stream::try_unfold(reader, move |mut reader| async move {
+            let mut buf = BytesMut::with_capacity(chunk_size);
+            buf.resize(chunk_size, 0);
+
+            let size = reader
+                .read(&mut buf)
+                .await
+                .map_err(|err| PayloadError::Incomplete(Some(err)))?;
+
+            if size == 0 {
+                Ok(None)
+            } else {
+                buf.truncate(size);
+                Ok(Some((buf.freeze(), reader)))
+            }
+        });
+
+        Self::new(headers, stream.boxed_local())
+    }
+
+    /// Read every field into memory, grouped by form field name.
+    ///
+    /// All field content counts against the memory limit of `limits`, which therefore bounds
+    /// the memory used by the whole call. Intended for small ad-hoc forms, uploads should be
+    /// streamed instead.
+    #[cfg(feature = "form")]
+    pub async fn collect_grouped(
+        mut self,
+        mut limits: Limits,
+    ) -> Result<HashMap<String, Vec<Bytes>>, MultipartError> {
+        let mut groups = HashMap::<String, Vec<Bytes>>::new();
+
+        while let Some(mut field) = self.try_next().await? {
+            let mut buf = BytesMut::new();
+            while let Some(chunk) = field.try_next().await? {
+                limits.try_consume_limits(chunk.len(), true)?;
+                buf.extend_from_slice(&chunk);
+            }
+
+            let name = std::mem::take(&mut field.form_field_name);
+            groups.entry(name).or_default().push(buf.freeze());
+        }
+
+        Ok(groups)
+    }
+
+    /// Turn the stream into a stream of [`Part`]s, each carrying the name, content type and
+    /// filename of its field next to the field itself.
+    pub fn parts(self) -> impl Stream<Item = Result<Part, MultipartError>> + Unpin {
+        self.map(|field| field.map(Part::new))
+    }
+
+    /// Advance to the first part named `name` and return it, or `None` if the stream ends
+    /// without one.
+    ///
+    /// Parts before it are skipped without being buffered, and the rest of the stream is left
+    /// unread, so the caller may keep polling `self` or drop it to stop parsing early.
+    pub async fn take_field(&mut self, name: &str) -> Result<Option<Field>, MultipartError> {
+        self.find_field(|field| field.name() == Some(name)).await
+    }
+
+    /// Advance to the first part matching `predicate` and return it, or `None` if the stream
+    /// ends without one.
+    ///
+    /// The predicate only sees the part headers, e.g. [`Field::name`] or
+    /// [`Field::content_type`], the body is not read before a part matches. Like
+    /// [`Multipart::take_field`], skipped parts are not buffered and the rest of the stream is
+    /// left unread.
+    pub async fn find_field<F>(
+        &mut self,
+        mut predicate: F,
+    ) -> Result<Option<Field>, MultipartError>
+    where
+        F: FnMut(&Field) -> bool,
+    {
+        while let Some(field) = self.next().await {
+            let field = field?;
+            if predicate(&field) {
+                return Ok(Some(field));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reject ambiguous part headers.
+    ///
+    /// In strict mode a part may not repeat `Content-Disposition`, `Content-Type`,
+    /// `Content-Length` or `Content-Transfer-Encoding`, and header values must consist of
+    /// visible ASCII characters only. Offending parts fail with [`DecodeError::Header`].
+    ///
+    /// Disabled by default.
+    pub fn strict_headers(self, strict: bool) -> Self {
+        self.configure(|settings| settings.strict_headers = strict)
+    }
+
+    /// Set the maximum number of headers a single part may carry.
+    ///
+    /// Parts with more headers fail with [`MultipartError::HeaderCountExceeded`]. By default
+    /// parts may carry up to 32 headers, more fail with [`MultipartError::TooManyPartHeaders`];
+    /// a limit above 32 raises that cap.
+    pub fn max_headers_per_part(self, max: usize) -> Self {
+        self.configure(|settings| settings.max_headers_per_part = Some(max))
+    }
+
+    /// Set the maximum length in bytes of a single part header value.
+    ///
+    /// A part with a longer value, like an oversized `Content-Disposition`, fails with
+    /// [`MultipartError::HeaderValueTooLong`] before the value is converted. Complements
+    /// [`Multipart::max_headers_per_part`], which does not bound the size of each header. No
+    /// limit by default.
+    pub fn max_header_value_len(self, max: usize) -> Self {
+        self.configure(|settings| settings.max_header_value_len = Some(max))
+    }
+
+    /// Keep the raw header section of every part.
+    ///
+    /// The bytes are available through [`Field::raw_headers`], exactly as received, for schemes
+    /// that sign the literal header block. Disabled by default.
+    pub fn raw_headers(self, retain: bool) -> Self {
+        self.configure(|settings| settings.raw_headers = retain)
+    }
+
+    /// Fail with [`MultipartError::Empty`] if the stream ends before any part was found.
+    ///
+    /// Disabled by default, in which case an empty stream simply yields no fields.
+    pub fn require_at_least_one_part(self, require: bool) -> Self {
+        self.configure(|settings| settings.require_part = require)
+    }
+
+    /// Call `f` with the field name whenever a field is dropped before being read to the end.
+    ///
+    /// Diagnostics only, helps to find handlers that leave fields partially read. Unconsumed
+    /// fields are still skipped as usual.
+    pub fn on_unconsumed_field(mut self, f: impl Fn(&str) + 'static) -> Self {
+        self.safety.on_unconsumed(Rc::new(f));
+        self
+    }
+
+    /// Accept part headers folded over several lines.
+    ///
+    /// Continuation lines starting with whitespace (obs-fold, deprecated by RFC 7230) are
+    /// joined to the previous line before parsing. Disabled by default, in which case folded
+    /// headers fail with [`DecodeError::Header`].
+    pub fn unfold_headers(self, unfold: bool) -> Self {
+        self.configure(|settings| settings.unfold_headers = unfold)
+    }
+
+    /// Set the content type reported by [`Field::content_type`] for parts without a
+    /// `Content-Type` header.
+    ///
+    /// RFC 2046 defines the default for such parts as `text/plain; charset=us-ascii`. Parts
+    /// with an unparsable header are not affected. Not set by default, in which case
+    /// `content_type()` returns `None` for these parts.
+    pub fn default_part_content_type(self, content_type: Mime) -> Self {
+        self.configure(|settings| settings.default_content_type = Some(content_type))
+    }
+
+    /// Set how deep multipart parts may be nested in each other.
+    ///
+    /// A part nested deeper fails with [`MultipartError::NestingTooDeep`], which guards
+    /// against bodies nesting parts to exhaust memory. The default of 4 allows the usual
+    /// `multipart/mixed` inside `multipart/form-data` layouts with room to spare.
+    pub fn max_nesting_depth(self, max: usize) -> Self {
+        self.configure(|settings| settings.max_nesting_depth = Some(max))
+    }
+
+    /// Use `name` as the form field name of `form-data` parts without a `name` parameter.
+    ///
+    /// Some clients send only `Content-Disposition: form-data; filename="..."`, which fails
+    /// with [`MultipartError::ContentDispositionNameMissing`] by default. [`Field::name`] still
+    /// returns `None` for such parts, only [`Field::form_field_name`] is affected.
+    pub fn fallback_field_name(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.configure(|settings| settings.fallback_field_name = Some(name))
+    }
+
+    /// Reject `form-data` parts with a malformed field name.
+    ///
+    /// A field name containing control characters, or a Content-Disposition that is not valid
+    /// UTF-8 so that the name can not be read from it, fails with
+    /// [`MultipartError::InvalidFieldName`] instead of passing the odd name on or reporting
+    /// a missing Content-Disposition. Disabled by default.
+    pub fn validate_field_names(self, validate: bool) -> Self {
+        self.configure(|settings| settings.validate_field_names = validate)
+    }
+
+    /// Reject parts whose declared `Content-Length` covers the boundary delimiter.
+    ///
+    /// A part with a `Content-Length` header is read by length, so a delimiter inside it is
+    /// taken as data, while parsers splitting on the boundary would end the part there. With
+    /// this enabled such bodies fail with [`MultipartError::BoundaryInBody`]. Disabled by
+    /// default; parts without a `Content-Length` are not affected.
+    pub fn reject_boundary_in_body(self, reject: bool) -> Self {
+        self.configure(|settings| settings.reject_boundary_in_body = reject)
+    }
+
+    /// Yield parts with a `multipart/*` content type as fields, instead of failing with
+    /// [`MultipartError::Nested`].
+    ///
+    /// RFC 7578 allows a form field to carry several files as a nested `multipart/mixed`
+    /// body, which [`Field::nested_multipart`] parses. Nested parts without a boundary and
+    /// parts nested deeper than [`Multipart::max_nesting_depth`] are still rejected. The
+    /// nested body is parsed with the settings of this one. Disabled by default.
+    pub fn allow_nested(self, allow: bool) -> Self {
+        self.configure(|settings| settings.allow_nested = allow)
+    }
+
+    fn configure(self, f: impl FnOnce(&mut Settings)) -> Self {
+        if let Some(ref inner) = self.inner {
+            f(&mut inner.borrow_mut().settings);
+        }
+        self
+    }
+
+    /// Set the maximum time allowed for parsing the whole multipart stream.
+    ///
+    /// The timer starts when this method is called. Once it elapses, polling the multipart
+    /// stream or any of its fields returns [`MultipartError::Timeout`].
+    pub fn timeout(self, timeout: Duration) -> Self {
+        if let Some(ref inner) = self.inner {
+            inner.borrow().payload.buffer().deadline = Some(Sleep::new(timeout.into()));
+        }
+        self
+    }
+
+    /// Bound how much payload data is buffered ahead of the consumer.
+    ///
+    /// By default every chunk the payload stream has ready is pulled into the buffer at once,
+    /// so a bursty producer can pile up a large backlog. With watermarks set, pulling stops
+    /// once `high` bytes are buffered and resumes when the consumer drained it below `low`, or
+    /// when the consumer cannot make progress without more data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low` is greater than `high`.
+    pub fn buffer_watermarks(self, low: usize, high: usize) -> Self {
+        assert!(low <= high, "low watermark must not exceed the high watermark");
+        if let Some(ref inner) = self.inner {
+            inner.borrow().payload.buffer().watermarks = Some((low, high));
+        }
+        self
+    }
+
+    /// Sets the maximum size of a request body sent with `Content-Encoding: gzip` or
+    /// `deflate`, after decompression. 64 MiB by default.
+    ///
+    /// [`Multipart::new`] decompresses such bodies before parsing them, a body growing past
+    /// the limit fails with [`PayloadError::Overflow`] and malformed compressed data with
+    /// [`MultipartError::Decompress`]. Has no effect on bodies that are not compressed.
+    #[cfg(feature = "compress")]
+    pub fn max_decompressed_size(self, limit: usize) -> Self {
+        if let Some(ref inner) = self.inner
+            && let Some(ref mut decompressed) = inner.borrow().payload.buffer().decompressed
+        {
+            decompressed.limit = limit;
+        }
+        self
+    }
+
+    /// Returns the state of the compressed stream, if the body is decompressed.
+    #[cfg(feature = "compress")]
+    pub(crate) fn compressed_source(&self) -> Option<std::rc::Rc<crate::decompress::Source>> {
+        let inner = self.inner.as_ref()?.borrow();
+        let decompressed = inner.payload.buffer().decompressed.as_ref()?.source.clone();
+        Some(decompressed)
+    }
+
+    /// Count parsed parts, consumed bytes and errors of this stream and its fields in
+    /// `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(self, metrics: std::sync::Arc<crate::MultipartMetrics>) -> Self {
+        self.with_recorder(crate::metrics::Recorder::new(metrics, true))
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn with_recorder(self, recorder: crate::metrics::Recorder) -> Self {
+        if let Some(ref inner) = self.inner {
+            inner.borrow().payload.buffer().metrics = Some(recorder);
+        }
+        self
+    }
+
     /// Extract boundary info from headers.
     pub(crate) fn boundary(headers: &HeaderMap) -> Result<(Mime, String), MultipartError> {
         if let Some(content_type) = headers.get(&header::CONTENT_TYPE) {
             if let Ok(content_type) = content_type.to_str() {
                 if let Ok(ct) = content_type.parse::<Mime>() {
-                    if ct.type_() == mime::MULTIPART {
-                        if let Some(boundary) = ct.get_param(mime::BOUNDARY) {
-                            Ok((ct.clone(), boundary.as_str().to_owned()))
-                        } else {
-                            Err(MultipartError::Boundary)
-                        }
-                    } else {
-                        Err(MultipartError::IncompatibleContentType)
-                    }
+                    let boundary = Self::mime_boundary(&ct, content_type)?;
+                    Ok((ct, boundary))
+                } else if let Some((ct, boundary)) = fallback_content_type(content_type) {
+                    validate_boundary(&boundary)?;
+                    Ok((ct, boundary))
                 } else {
                     Err(MultipartError::ParseContentType)
                 }
@@ -102,6 +646,59 @@ impl Multipart {
         }
     }
 
+    /// Returns the Content-Disposition of the request itself, if it had a valid one.
+    ///
+    /// Only available for instances created from request headers, not from a bare mime type.
+    pub fn content_disposition(&self) -> Option<&ContentDisposition> {
+        self.content_disposition.as_ref()
+    }
+
+    /// Extract the boundary of a multipart Content-Type, scanning `raw` if mime did not find it.
+    fn mime_boundary(ct: &Mime, raw: &str) -> Result<String, MultipartError> {
+        if ct.type_() != mime::MULTIPART {
+            Err(MultipartError::IncompatibleContentType)
+        } else {
+            let boundary = match ct.get_param(mime::BOUNDARY) {
+                Some(boundary) => boundary.as_str().to_owned(),
+                None => find_boundary(raw).ok_or(MultipartError::Boundary)?,
+            };
+            validate_boundary(&boundary)?;
+            Ok(boundary)
+        }
+    }
+
+    /// Returns the parameters of the request Content-Type, in the order they appeared.
+    ///
+    /// Names are lowercase and quoted values are unquoted, as parsed by [`Mime`], which also
+    /// lowercases a `charset=UTF-8` value. When the header could only be parsed leniently,
+    /// just the `boundary` is returned. Empty if the Content-Type was not valid.
+    pub fn content_type_params(&self) -> Vec<(String, String)> {
+        let Some(inner) = self.inner.as_ref() else {
+            return Vec::new();
+        };
+        let inner = inner.borrow();
+
+        let mut params: Vec<_> = inner
+            .content_type
+            .params()
+            .map(|(name, value)| (name.as_str().to_owned(), value.as_str().to_owned()))
+            .collect();
+        if !params.iter().any(|(name, _)| name == mime::BOUNDARY.as_str()) {
+            params.push((mime::BOUNDARY.as_str().to_owned(), inner.boundary.clone()));
+        }
+        params
+    }
+
+    /// Returns the boundary parameter of the request, if it was parsed.
+    pub(crate) fn boundary_param(&self) -> Option<String> {
+        self.inner.as_ref().map(|inner| inner.borrow().boundary.clone())
+    }
+
+    /// Returns the multipart subtype of the request, if it was parsed.
+    pub(crate) fn subtype(&self) -> Option<String> {
+        self.inner.as_ref().map(|inner| inner.borrow().content_type.subtype().to_string())
+    }
+
     /// Return requests parsed Content-Type or raise the stored error.
     pub(crate) fn content_type(&mut self) -> Result<Mime, MultipartError> {
         if let Some(err) = self.error.take() {
@@ -116,6 +713,25 @@ impl Stream for Multipart {
     type Item = Result<Field, MultipartError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let res = self.as_mut().poll_part(cx);
+
+        #[cfg(feature = "metrics")]
+        if let Poll::Ready(Some(ref res)) = res
+            && let Some(ref inner) = self.inner
+            && let Some(ref metrics) = inner.borrow().payload.buffer().metrics
+        {
+            metrics.part(res);
+        }
+
+        res
+    }
+}
+
+impl Multipart {
+    fn poll_part(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Field, MultipartError>>> {
         if let Some(err) = self.error.take() {
             Poll::Ready(Some(Err(err)))
         } else if self.safety.current() {
@@ -134,7 +750,20 @@ impl Stream for Multipart {
 }
 
 impl InnerMultipart {
-    fn read_headers(payload: &mut PayloadBuffer) -> Result<Option<HeaderMap>, MultipartError> {
+    fn read_headers(
+        payload: &mut PayloadBuffer,
+        settings: &Settings,
+    ) -> Result<Option<(HeaderMap, Bytes)>, MultipartError> {
+        // a part without headers starts with the blank line ending its empty header block,
+        // searching for the usual terminator would run into the body
+        if payload.buf.starts_with(b"\r\n") {
+            let bytes = payload.buf.split_to(2);
+            return Ok(Some((HeaderMap::new(), bytes)));
+        }
+        if payload.buf.len() < 2 && !payload.eof {
+            return Ok(None);
+        }
+
         match payload.read_until(b"\r\n\r\n")? {
             None => {
                 if payload.eof {
@@ -144,13 +773,54 @@ impl InnerMultipart {
                 }
             }
             Some(bytes) => {
-                let mut hdrs = [httparse::EMPTY_HEADER; MAX_HEADERS];
-                match httparse::parse_headers(&bytes, &mut hdrs) {
+                let unfolded;
+                let block = if settings.unfold_headers {
+                    unfolded = unfold_headers(&bytes);
+                    &unfolded[..]
+                } else {
+                    &bytes[..]
+                };
+
+                if has_unterminated_quote(block) {
+                    return Err(MultipartError::UnterminatedQuote);
+                }
+
+                let slots = settings
+                    .max_headers_per_part
+                    .map_or(MAX_HEADERS, |max| max.max(MAX_HEADERS));
+                let mut fixed = [httparse::EMPTY_HEADER; MAX_HEADERS];
+                let mut grown;
+                let hdrs: &mut [httparse::Header<'_>] = if slots > MAX_HEADERS {
+                    grown = vec![httparse::EMPTY_HEADER; slots];
+                    &mut grown
+                } else {
+                    &mut fixed
+                };
+
+                match httparse::parse_headers(block, hdrs) {
                     Ok(httparse::Status::Complete((_, hdrs))) => {
+                        if let Some(max) = settings.max_headers_per_part
+                            && hdrs.len() > max
+                        {
+                            return Err(MultipartError::HeaderCountExceeded);
+                        }
+
                         // convert headers
                         let mut headers = HeaderMap::with_capacity(hdrs.len());
                         for h in hdrs {
+                            if let Some(max) = settings.max_header_value_len
+                                && h.value.len() > max
+                            {
+                                return Err(MultipartError::HeaderValueTooLong);
+                            }
+
                             if let Ok(name) = HeaderName::try_from(h.name) {
+                                if settings.strict_headers
+                                    && !is_strict_header(&headers, &name, h.value)
+                                {
+                                    return Err(DecodeError::Header.into());
+                                }
+
                                 if let Ok(value) = HeaderValue::try_from(h.value) {
                                     headers.append(name, value);
                                 } else {
@@ -160,9 +830,17 @@ impl InnerMultipart {
                                 return Err(DecodeError::Header.into());
                             }
                         }
-                        Ok(Some(headers))
+                        Ok(Some((headers, bytes)))
                     }
                     Ok(httparse::Status::Partial) => Err(DecodeError::Header.into()),
+                    // the header slots ran out, this is not a malformed header block
+                    Err(httparse::Error::TooManyHeaders) => {
+                        if settings.max_headers_per_part.is_some() {
+                            Err(MultipartError::HeaderCountExceeded)
+                        } else {
+                            Err(MultipartError::TooManyPartHeaders)
+                        }
+                    }
                     Err(err) => Err(DecodeError::from(err).into()),
                 }
             }
@@ -190,10 +868,8 @@ impl InnerMultipart {
                     Err(MultipartError::Boundary)
                 } else if &chunk[boundary.len() + 2..] == b"\r\n" {
                     Ok(Some(false))
-                } else if &chunk[boundary.len() + 2..boundary.len() + 4] == b"--"
-                    && (chunk.len() == boundary.len() + 4
-                        || &chunk[boundary.len() + 4..] == b"\r\n")
-                {
+                } else if &chunk[boundary.len() + 2..boundary.len() + 4] == b"--" {
+                    // close delimiter, anything that follows it is epilogue and is ignored
                     Ok(Some(true))
                 } else {
                     Err(MultipartError::Boundary)
@@ -224,8 +900,12 @@ impl InnerMultipart {
                             continue;
                         }
                         let b: &[u8] = boundary.as_ref();
-                        if &chunk[..boundary.len()] == b
-                            && &chunk[boundary.len()..boundary.len() + 2] == b"--"
+                        if (&chunk[..boundary.len()] == b
+                            && &chunk[boundary.len()..boundary.len() + 2] == b"--")
+                            || (chunk.len() >= boundary.len() + 4
+                                && &chunk[..2] == b"--"
+                                && &chunk[2..boundary.len() + 2] == b
+                                && &chunk[boundary.len() + 2..boundary.len() + 4] == b"--")
                         {
                             eof = true;
                             break;
@@ -277,12 +957,24 @@ impl InnerMultipart {
                 }
             }
 
-            let headers = if let Some(mut payload) = self.payload.get_mut(safety) {
+            let (headers, raw_headers) = if let Some(mut payload) = self.payload.get_mut(safety)
+            {
                 match self.state {
                     // read until first boundary
                     InnerState::FirstBoundary => {
-                        match InnerMultipart::skip_until_boundary(&mut payload, &self.boundary)?
-                        {
+                        let res =
+                            InnerMultipart::skip_until_boundary(&mut payload, &self.boundary);
+                        let empty = match res {
+                            Ok(Some(true)) => true,
+                            Err(MultipartError::Incomplete) => payload.buf.is_empty(),
+                            _ => false,
+                        };
+                        if empty && self.settings.require_part {
+                            self.state = InnerState::Eof;
+                            return Poll::Ready(Some(Err(MultipartError::Empty)));
+                        }
+
+                        match res? {
                             Some(eof) => {
                                 if eof {
                                     self.state = InnerState::Eof;
@@ -313,9 +1005,11 @@ impl InnerMultipart {
 
                 // read field headers for next field
                 if self.state == InnerState::Headers {
-                    if let Some(headers) = InnerMultipart::read_headers(&mut payload)? {
+                    if let Some((headers, raw_headers)) =
+                        InnerMultipart::read_headers(&mut payload, &self.settings)?
+                    {
                         self.state = InnerState::Boundary;
-                        headers
+                        (headers, raw_headers)
                     } else {
                         return Poll::Pending;
                     }
@@ -327,10 +1021,12 @@ impl InnerMultipart {
                 return Poll::Pending;
             };
 
+            // parts of a nested body, e.g. the files of a `multipart/mixed` form field, may
+            // use any disposition type
             let field_content_disposition = if let Some(hv) =
                 headers.get(&header::CONTENT_DISPOSITION)
                 && let Ok(cd) = ContentDisposition::parse_header(&ntex_files::header::Raw::from(hv.as_bytes()))
-                && cd.disposition == DispositionType::FormData
+                && (cd.disposition == DispositionType::FormData || self.depth > 0)
             {
                 Some(cd)
             } else {
@@ -338,11 +1034,27 @@ impl InnerMultipart {
             };
 
             let form_field_name = if self.content_type.subtype() == mime::FORM_DATA {
+                if self.settings.validate_field_names
+                    && headers
+                        .get(&header::CONTENT_DISPOSITION)
+                        .is_some_and(|hv| str::from_utf8(hv.as_bytes()).is_err())
+                {
+                    return Poll::Ready(Some(Err(MultipartError::InvalidFieldName)));
+                }
+
                 let Some(cd) = &field_content_disposition else {
                     return Poll::Ready(Some(Err(MultipartError::ContentDispositionMissing)));
                 };
 
-                let Some(field_name) = cd.get_name() else {
+                if self.settings.validate_field_names
+                    && cd.get_name().is_some_and(|name| name.chars().any(char::is_control))
+                {
+                    return Poll::Ready(Some(Err(MultipartError::InvalidFieldName)));
+                }
+
+                let Some(field_name) =
+                    cd.get_name().or(self.settings.fallback_field_name.as_deref())
+                else {
                     return Poll::Ready(Some(Err(MultipartError::ContentDispositionNameMissing)));
                 };
 
@@ -351,40 +1063,56 @@ impl InnerMultipart {
                 None
             };
 
-            let field_content_type: Option<Mime> = if let Some(content_type) =
-                headers.get(&header::CONTENT_TYPE)
-                && let Ok(content_type) = content_type.to_str()
-                && let Ok(ct) = content_type.parse::<Mime>()
-            {
-                Some(ct)
-            } else {
-                None
-            };
+            let field_content_type: Option<Mime> =
+                if let Some(content_type) = headers.get(&header::CONTENT_TYPE) {
+                    content_type.to_str().ok().and_then(|ct| ct.parse::<Mime>().ok())
+                } else {
+                    self.settings.default_content_type.clone()
+                };
 
             self.state = InnerState::Boundary;
 
-            // nested multipart stream is not supported
+            // nested multipart streams are only parsed on request
+            let mut nesting = None;
             if let Some(mime) = &field_content_type
                 && mime.type_() == mime::MULTIPART
             {
-                return Poll::Ready(Some(Err(MultipartError::Nested)));
+                if mime.get_param(mime::BOUNDARY).is_none() {
+                    return Poll::Ready(Some(Err(MultipartError::NestedBoundaryMissing)));
+                }
+                let max_depth =
+                    self.settings.max_nesting_depth.unwrap_or(DEFAULT_MAX_NESTING_DEPTH);
+                if self.depth >= max_depth {
+                    return Poll::Ready(Some(Err(MultipartError::NestingTooDeep)));
+                }
+                if !self.settings.allow_nested {
+                    return Poll::Ready(Some(Err(MultipartError::Nested)));
+                }
+                nesting = Some(Nesting { settings: self.settings.clone(), depth: self.depth });
             }
 
-            let field = Rc::new(RefCell::new(InnerField::new(
-                self.payload.clone(),
-                self.boundary.clone(),
-                &headers,
-            )?));
+            let mut inner_field =
+                InnerField::new(self.payload.clone(), self.boundary.clone(), &headers)?;
+            if self.settings.reject_boundary_in_body {
+                inner_field.reject_boundary_in_body();
+            }
+            let field = Rc::new(RefCell::new(inner_field));
             self.item = InnerMultipartItem::Field(Rc::clone(&field));
 
-            Poll::Ready(Some(Ok(Field::new(
+            let mut field = Field::new(
                 safety.clone(cx),
                 headers,
                 field_content_type,
                 field_content_disposition,
                 form_field_name,
                 field,
-            ))))
+            );
+            if self.settings.raw_headers {
+                field.raw_headers = Some(raw_headers);
+            }
+            field.nesting = nesting;
+
+            Poll::Ready(Some(Ok(field)))
         }
     }
 }
@@ -400,9 +1128,7 @@ impl Drop for InnerMultipart {
 mod tests {
     use super::*;
     use crate::Field;
-    use futures::{StreamExt as _, stream};
     use futures_test::stream::StreamTestExt as _;
-    use ntex::util::BytesMut;
     use ntex::{channel::mpsc, util::Bytes};
 
     #[ntex::test]
@@ -442,6 +1168,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_boundary_param_casing() {
+        for ct in [
+            "multipart/form-data; Boundary=abbc761f",
+            "multipart/form-data; BOUNDARY=\"abbc761f\"",
+            "multipart/form-data; charset=utf-8; boundary=abbc761f",
+            "multipart/mixed; boundary=abbc761f; charset=utf-8",
+            "Multipart/Mixed; charset=\"utf-8\"; bOuNdArY=abbc761f",
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(ct).unwrap());
+            assert_eq!(Multipart::boundary(&headers).unwrap().1, "abbc761f", "{}", ct);
+        }
+
+        assert_eq!(find_boundary("multipart/mixed; charset=utf-8"), None);
+        assert_eq!(find_boundary("multipart/mixed; boundary=\"\""), None);
+    }
+
+    #[ntex::test]
+    async fn test_boundary_fallback() {
+        for (ct, boundary) in [
+            (
+                "multipart/mixed; boundary=\"----=_Part_0_12345.1700000000\"; charset=\"\"",
+                "----=_Part_0_12345.1700000000",
+            ),
+            ("multipart/alternative; Boundary=\"=_a.b_c\"; delsp", "=_a.b_c"),
+            (
+                "multipart/related; x=\"\"; boundary=\"uuid:0ca0e16e+id=1\"",
+                "uuid:0ca0e16e+id=1",
+            ),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(ct).unwrap());
+            let (mime, found) = Multipart::boundary(&headers).unwrap();
+            assert_eq!(found, boundary, "{ct}");
+            assert_eq!(mime.type_(), mime::MULTIPART);
+
+            let body = format!(
+                "--{boundary}\r\nContent-Type: text/plain\r\n\r\nhello\r\n--{boundary}--\r\n"
+            );
+            let mut multipart = Multipart::new(&headers, stream::iter([Ok(Bytes::from(body))]));
+            let field = multipart.next().await.unwrap().unwrap();
+            assert_eq!(field.collect_bytes(16).await.unwrap(), "hello");
+            assert!(multipart.next().await.is_none());
+        }
+
+        for ct in [
+            "multipart/mixed; boundary=----=_Part_0; charset=\"\"",
+            "text/plain; boundary=\"abc\"; charset=\"\"",
+            "multipart/mixed; charset=\"\"",
+            "multipart/mixed; x-boundary=\"abc\"; charset=\"\"",
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(ct).unwrap());
+            assert!(
+                matches!(Multipart::boundary(&headers), Err(MultipartError::ParseContentType)),
+                "{ct}"
+            );
+        }
+    }
+
     fn create_stream() -> (
         mpsc::Sender<Result<Bytes, PayloadError>>,
         impl Stream<Item = Result<Bytes, PayloadError>>,
@@ -500,6 +1287,28 @@ mod tests {
         }
     }
 
+    #[ntex::test]
+    async fn test_multipart_close_delimiter_endings() {
+        let endings: [&[u8]; 4] = [b"", b"\r\n", b"\r\nepilogue\r\n", b"trailing junk"];
+
+        for ending in endings {
+            let mut body = BytesMut::from(
+                &b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                   Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                   data\r\n\
+                   --abbc761f78ff4d7cb7573b5a23f96ef0--"[..],
+            );
+            body.extend_from_slice(ending);
+            let (_, headers) = create_simple_request_with_header();
+
+            let payload = stream::iter([Ok(body.freeze())]).interleave_pending();
+            let mut multipart = Multipart::new(&headers, payload);
+            let field = multipart.next().await.unwrap().unwrap();
+            assert_eq!(field.measure().await.unwrap(), 4);
+            assert!(multipart.next().await.is_none());
+        }
+    }
+
     #[ntex::test]
     async fn test_multipart() {
         let (sender, payload) = create_stream();
@@ -512,6 +1321,8 @@ mod tests {
             Some(Ok(mut field)) => {
                 assert_eq!(field.content_type().unwrap().type_(), mime::TEXT);
                 assert_eq!(field.content_type().unwrap().subtype(), mime::PLAIN);
+                assert_eq!(field.name(), Some("file"));
+                assert_eq!(field.content_disposition().unwrap().get_filename(), Some("fn.txt"));
 
                 match field.next().await.unwrap() {
                     Ok(chunk) => assert_eq!(chunk, "test"),
@@ -593,4 +1404,1140 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    fn create_request_without_length() -> (Bytes, HeaderMap) {
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"fn.txt\"\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n\
+             test\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n\
+             data\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/mixed; boundary=\"abbc761f78ff4d7cb7573b5a23f96ef0\"",
+            ),
+        );
+        (bytes, headers)
+    }
+
+    #[ntex::test]
+    async fn test_field_aligned_with_chunk_boundary() {
+        // a field ending misaligned would consume the following boundary line as unread data,
+        // losing the next part or the end of the stream
+        for (bytes, headers) in
+            [create_request_without_length(), create_simple_request_with_header()]
+        {
+            for split in 1..bytes.len() {
+                let chunks = vec![Ok(bytes.slice(..split)), Ok(bytes.slice(split..))];
+                let mut multipart =
+                    Multipart::new(&headers, stream::iter(chunks).interleave_pending());
+
+                let mut field = multipart.next().await.unwrap().unwrap();
+                assert_eq!(get_whole_field(&mut field).await, "test", "split at {}", split);
+                drop(field);
+
+                let mut field = multipart.next().await.unwrap().unwrap();
+                assert_eq!(get_whole_field(&mut field).await, "data", "split at {}", split);
+                drop(field);
+
+                assert!(multipart.next().await.is_none(), "split at {}", split);
+            }
+        }
+
+        // the unread rest of a body longer than its declared length is skipped
+        let (bytes, headers) = create_simple_request_with_header();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        let body = body.replacen("Content-Length: 4", "Content-Length: 2", 1);
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(Bytes::from(body))]));
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "te");
+        drop(field);
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "data");
+        drop(field);
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_stream_without_length() {
+        let (bytes, headers) = create_request_without_length();
+        let payload = stream::iter(bytes)
+            .map(|byte| Ok(Bytes::copy_from_slice(&[byte])))
+            .interleave_pending();
+
+        let mut multipart = Multipart::new(&headers, payload);
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "test");
+        drop(field);
+
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "data");
+        drop(field);
+
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_multipart_timeout() {
+        let (sender, payload) = create_stream();
+        let (bytes, headers) = create_simple_request_with_header();
+        sender.send(Ok(bytes.slice(..bytes.len() - 44))).unwrap();
+
+        let mut multipart =
+            Multipart::new(&headers, payload).timeout(Duration::from_millis(50));
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "test");
+        drop(field);
+
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert!(matches!(field.next().await, Some(Err(MultipartError::Timeout))));
+        drop(field);
+
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::Timeout))));
+        drop(sender);
+    }
+
+    #[ntex::test]
+    async fn test_field_measure() {
+        let (bytes, headers) = create_request_without_length();
+        let payload = stream::iter(bytes)
+            .map(|byte| Ok(Bytes::copy_from_slice(&[byte])))
+            .interleave_pending();
+
+        let mut multipart = Multipart::new(&headers, payload);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.measure().await.unwrap(), 4);
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.measure().await.unwrap(), 4);
+
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_field_for_test() {
+        let cd = ContentDisposition {
+            disposition: DispositionType::FormData,
+            parameters: vec![ntex_files::header::DispositionParam::Name("text".to_owned())],
+        };
+        let mut field = Field::for_test(
+            HeaderMap::new(),
+            Some(mime::TEXT_PLAIN),
+            Some(cd),
+            Bytes::from_static(b"hello\r\n--world"),
+        );
+        assert_eq!(field.name(), Some("text"));
+        assert_eq!(field.content_type(), Some(&mime::TEXT_PLAIN));
+        assert_eq!(get_whole_field(&mut field).await, "hello\r\n--world");
+    }
+
+    fn create_request_with_duplicate_header() -> (Bytes, HeaderMap) {
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"file\"\r\n\
+             Content-Disposition: form-data; name=\"other\"\r\n\r\n\
+             test\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+        (bytes, headers)
+    }
+
+    #[ntex::test]
+    async fn test_nested_multipart() {
+        let (_, headers) = create_simple_request_with_header();
+        for (content_type, nested_boundary) in
+            [("multipart/mixed; boundary=inner", true), ("multipart/mixed", false)]
+        {
+            let bytes = Bytes::from(format!(
+                "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"files\"\r\n\
+                 Content-Type: {content_type}\r\n\r\n\
+                 --inner--\r\n\
+                 --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n"
+            ));
+            let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+            match multipart.next().await {
+                Some(Err(MultipartError::Nested)) => assert!(nested_boundary),
+                Some(Err(MultipartError::NestedBoundaryMissing)) => assert!(!nested_boundary),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[ntex::test]
+    async fn test_nested_multipart_field() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+        let bytes = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"files\"\r\n\
+              Content-Type: multipart/mixed; boundary=inner\r\n\r\n\
+              --inner\r\n\
+              Content-Disposition: file; filename=\"a.txt\"\r\n\
+              Content-Type: text/plain\r\n\r\n\
+              first file\r\n\
+              --inner\r\n\
+              Content-Disposition: file; filename=\"b.txt\"\r\n\r\n\
+              second file\r\n\
+              --inner--\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"note\"\r\n\r\n\
+              plain\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)]).interleave_pending())
+                .allow_nested(true);
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.form_field_name(), "files");
+        let mut nested = field.nested_multipart().unwrap();
+        assert_eq!(nested.content_disposition().and_then(|cd| cd.get_name()), Some("files"));
+
+        let mut files = Vec::new();
+        while let Some(file) = nested.next().await {
+            let mut file = file.unwrap();
+            let name = file.filename().unwrap().into_owned();
+            files.push((name, get_whole_field(&mut file).await));
+        }
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "a.txt");
+        assert_eq!(files[0].1, "first file");
+        assert_eq!(files[1].0, "b.txt");
+        assert_eq!(files[1].1, "second file");
+        drop(nested);
+
+        // other fields are read as before
+        let field = multipart.next().await.unwrap().unwrap();
+        let mut field = match field.nested_multipart() {
+            Ok(_) => panic!("expected a plain field"),
+            Err(field) => field,
+        };
+        assert_eq!(field.form_field_name(), "note");
+        assert_eq!(get_whole_field(&mut field).await, "plain");
+        drop(field);
+        assert!(multipart.next().await.is_none());
+
+        // errors of the containing body keep their type
+        let bytes = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"files\"\r\n\
+              Content-Type: multipart/mixed; boundary=inner\r\n\r\n\
+              --inner\r\n\
+              Content-Disposition: file; filename=\"a.txt\"\r\n\r\n\
+              truncated",
+        );
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).allow_nested(true);
+        let field = multipart.next().await.unwrap().unwrap();
+        let mut nested = field.nested_multipart().unwrap();
+        let res = nested.next().await.unwrap();
+        assert!(matches!(res, Err(MultipartError::Incomplete)));
+    }
+
+    #[ntex::test]
+    async fn test_headerless_part() {
+        let (_, headers) = create_simple_request_with_header();
+        let body = b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                     \r\n\
+                     no headers\r\n\r\nat all\r\n\
+                     --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                     Content-Type: text/plain\r\n\r\n\
+                     typed\r\n\
+                     --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                     \r\n\
+                     \r\n\
+                     --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n";
+
+        // also split right after the boundary lines
+        for chunk_size in [body.len(), 1, 37] {
+            let chunks: Vec<_> =
+                body.chunks(chunk_size).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+            let mut multipart = Multipart::new(&headers, stream::iter(chunks));
+
+            let mut field = multipart.next().await.unwrap().unwrap();
+            assert!(field.headers().is_empty());
+            assert!(field.content_type().is_none());
+            assert_eq!(get_whole_field(&mut field).await, "no headers\r\n\r\nat all");
+            drop(field);
+
+            let mut field = multipart.next().await.unwrap().unwrap();
+            assert_eq!(field.content_type(), Some(&mime::TEXT_PLAIN));
+            assert_eq!(get_whole_field(&mut field).await, "typed");
+            drop(field);
+
+            let mut field = multipart.next().await.unwrap().unwrap();
+            assert!(field.headers().is_empty());
+            assert_eq!(get_whole_field(&mut field).await, "");
+            drop(field);
+
+            assert!(multipart.next().await.is_none());
+        }
+    }
+
+    #[ntex::test]
+    async fn test_max_nesting_depth() {
+        let (_, headers) = create_simple_request_with_header();
+        let bytes = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"files\"\r\n\
+              Content-Type: multipart/mixed; boundary=inner\r\n\r\n\
+              --inner--\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).max_nesting_depth(0);
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::NestingTooDeep))));
+    }
+
+    #[ntex::test]
+    async fn test_reject_boundary_in_body() {
+        let (_, headers) = create_simple_request_with_header();
+        let body = b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"file\"\r\n\
+              Content-Length: 48\r\n\r\n\
+              ab\r\n--abbc761f78ff4d7cb7573b5a23f96ef0\r\nxyz\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n";
+        let chunks = || {
+            let chunks: Vec<_> =
+                body.chunks(5).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+            stream::iter(chunks)
+        };
+
+        let mut multipart = Multipart::new(&headers, chunks());
+        let field = multipart.next().await.unwrap().unwrap();
+        let data = field.collect_bytes(1024).await.unwrap();
+        assert_eq!(data.len(), 48);
+
+        let mut multipart = Multipart::new(&headers, chunks()).reject_boundary_in_body(true);
+        let mut field = multipart.next().await.unwrap().unwrap();
+        let mut res = Ok(());
+        while let Some(chunk) = field.next().await {
+            if let Err(err) = chunk {
+                res = Err(err);
+                break;
+            }
+        }
+        assert!(matches!(res, Err(MultipartError::BoundaryInBody)));
+
+        let body = b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"file\"\r\n\
+              Content-Length: 10\r\n\r\n\
+              ab\r\n--abbc\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n";
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(Bytes::from_static(body))]))
+                .reject_boundary_in_body(true);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.collect_bytes(1024).await.unwrap(), "ab\r\n--abbc");
+    }
+
+    #[ntex::test]
+    async fn test_max_headers_per_part() {
+        let (bytes, headers) = create_simple_request_with_header();
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes.clone())])).max_headers_per_part(3);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        drop(field);
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).max_headers_per_part(2);
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::HeaderCountExceeded))
+        ));
+    }
+
+    #[ntex::test]
+    async fn test_max_header_value_len() {
+        let (bytes, headers) = create_simple_request_with_header();
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]))
+            .max_header_value_len(64);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        drop(field);
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).max_header_value_len(16);
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::HeaderValueTooLong))
+        ));
+    }
+
+    #[ntex::test]
+    async fn test_too_many_part_headers() {
+        let mut body = String::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"file\"\r\n",
+        );
+        for idx in 0..40 {
+            body.push_str(&format!("X-Extra-{idx}: {idx}\r\n"));
+        }
+        body.push_str("\r\ndata\r\n--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n");
+        let body = Bytes::from(body);
+        let (_, headers) = create_simple_request_with_header();
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body.clone())]));
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::TooManyPartHeaders))
+        ));
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(body.clone())])).max_headers_per_part(64);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.headers().len(), 41);
+        drop(field);
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(body)])).max_headers_per_part(40);
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::HeaderCountExceeded))
+        ));
+    }
+
+    #[ntex::test]
+    async fn test_crlf_in_quoted_header_value() {
+        let (_, headers) = create_simple_request_with_header();
+        for marker in ["--abbc\r\nX-Other: 1", "\r\n\r\n--abbc761f78ff4d7cb7573b5a23f96ef0"] {
+            let body = format!(
+                "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+                 Content-Disposition: form-data; name=\"file\"\r\n\
+                 Content-Type: application/x-foo; marker=\"{marker}\"\r\n\r\n\
+                 data\r\n--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n"
+            );
+            let mut multipart = Multipart::new(&headers, stream::iter([Ok(Bytes::from(body))]));
+            assert!(matches!(
+                multipart.next().await,
+                Some(Err(MultipartError::UnterminatedQuote))
+            ));
+        }
+
+        let body = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"file\"\r\n\
+              Content-Type: application/x-foo; marker=\"--abbc761f78ff4d7cb7573b5a23f96ef0 \\\"\"\r\n\r\n\
+              data\r\n--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body)]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.collect_bytes(1024).await.unwrap(), "data");
+
+        // only parameterized headers are checked
+        let body = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; name=\"file\"\r\n\
+              X-Note: size=\"5\r\n\r\n\
+              data\r\n--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body)]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.headers().get("x-note").unwrap(), "size=\"5");
+        assert_eq!(field.collect_bytes(1024).await.unwrap(), "data");
+    }
+
+    #[ntex::test]
+    async fn test_raw_headers() {
+        let (bytes, headers) = create_simple_request_with_header();
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert!(field.raw_headers().is_none());
+        drop(field);
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).raw_headers(true);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(
+            field.raw_headers().unwrap(),
+            "Content-Disposition: form-data; name=\"file\"; filename=\"fn.txt\"\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Length: 4\r\n\r\n"
+        );
+        drop(field);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(
+            field.raw_headers().unwrap(),
+            "Content-Type: text/plain; charset=utf-8\r\nContent-Length: 4\r\n\r\n"
+        );
+    }
+
+    #[ntex::test]
+    async fn test_require_at_least_one_part() {
+        let (_, headers) = create_simple_request_with_header();
+        let close = Bytes::from_static(b"--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n");
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(Bytes::new())]));
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::Incomplete))));
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(close.clone())]));
+        assert!(multipart.next().await.is_none());
+
+        for body in [Bytes::new(), close] {
+            let mut multipart = Multipart::new(&headers, stream::iter([Ok(body)]))
+                .require_at_least_one_part(true);
+            assert!(matches!(multipart.next().await, Some(Err(MultipartError::Empty))));
+            assert!(multipart.next().await.is_none());
+        }
+
+        let (bytes, headers) = create_simple_request_with_header();
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).require_at_least_one_part(true);
+        assert!(multipart.next().await.unwrap().is_ok());
+    }
+
+    #[ntex::test]
+    async fn test_on_unconsumed_field() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let dropped = Rc::new(RefCell::new(Vec::new()));
+
+        let tracked = dropped.clone();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]))
+            .on_unconsumed_field(move |name| tracked.borrow_mut().push(name.to_owned()));
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        drop(field);
+        assert_eq!(*dropped.borrow(), ["file"]);
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.measure().await.unwrap(), 4);
+        assert!(multipart.next().await.is_none());
+        assert_eq!(*dropped.borrow(), ["file"]);
+    }
+
+    #[ntex::test]
+    async fn test_from_mime() {
+        let (bytes, _) = create_simple_request_with_header();
+        let ct: Mime =
+            "multipart/mixed; boundary=abbc761f78ff4d7cb7573b5a23f96ef0".parse().unwrap();
+
+        let mut multipart = Multipart::from_mime(ct, stream::iter([Ok(bytes.clone())]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        drop(field);
+
+        let mut multipart =
+            Multipart::from_mime(mime::TEXT_PLAIN, stream::iter([Ok(bytes.clone())]));
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::IncompatibleContentType))
+        ));
+
+        let mut multipart =
+            Multipart::from_mime(mime::MULTIPART_FORM_DATA, stream::iter([Ok(bytes)]));
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::Boundary))));
+    }
+
+    #[ntex::test]
+    async fn test_unfold_headers() {
+        let (_, headers) = create_simple_request_with_header();
+        let bytes = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data;\r\n \t name=\"file\";\r\n\tfilename=\"fn.txt\"\r\n\
+              Content-Type: text/plain\r\n\r\n\
+              test\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::Decode(_)))));
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).unfold_headers(true);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert_eq!(field.content_disposition().unwrap().get_filename(), Some("fn.txt"));
+        assert_eq!(field.content_type(), Some(&mime::TEXT_PLAIN));
+        assert_eq!(field.measure().await.unwrap(), 4);
+    }
+
+    #[ntex::test]
+    async fn test_request_content_disposition() {
+        let (bytes, mut headers) = create_simple_request_with_header();
+
+        let multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        assert!(multipart.content_disposition().is_none());
+
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"bundle.bin\""),
+        );
+        let multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        let cd = multipart.content_disposition().unwrap();
+        assert!(cd.is_attachment());
+        assert_eq!(cd.get_filename(), Some("bundle.bin"));
+    }
+
+    #[test]
+    fn test_validate_boundary() {
+        assert!(validate_boundary("abbc761f78ff4d7cb7573b5a23f96ef0").is_ok());
+        assert!(validate_boundary("----WebKitFormBoundary7MA4YWxkTrZu0gW").is_ok());
+        assert!(validate_boundary("simple boundary").is_ok());
+        assert!(validate_boundary("'()+_,-./:=?").is_ok());
+        assert!(validate_boundary(&"a".repeat(70)).is_ok());
+
+        assert!(validate_boundary("").is_err());
+        assert!(validate_boundary(&"a".repeat(71)).is_err());
+        assert!(validate_boundary("trailing ").is_err());
+        assert!(validate_boundary("semi;colon").is_err());
+        assert!(validate_boundary("quote\"").is_err());
+        assert!(validate_boundary("caf\u{e9}").is_err());
+    }
+
+    #[ntex::test]
+    async fn test_invalid_boundary() {
+        let mut headers = HeaderMap::new();
+        let content_type = format!("multipart/mixed; boundary={}", "a".repeat(71));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(&content_type).unwrap());
+
+        let mut multipart = Multipart::new(&headers, stream::empty());
+        assert!(matches!(multipart.next().await, Some(Err(MultipartError::Boundary))));
+    }
+
+    #[ntex::test]
+    async fn test_field_collect_bytes() {
+        let (bytes, headers) = create_request_without_length();
+        let payload = stream::iter(bytes)
+            .map(|byte| Ok(Bytes::copy_from_slice(&[byte])))
+            .interleave_pending();
+
+        let mut multipart = Multipart::new(&headers, payload);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.collect_bytes(4).await.unwrap(), "test");
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert!(matches!(
+            field.collect_bytes(3).await,
+            Err(MultipartError::Payload(PayloadError::Overflow))
+        ));
+
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_default_part_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/mixed; boundary=abbc"),
+        );
+        let body = Bytes::from_static(
+            b"--abbc\r\nContent-Disposition: inline\r\n\r\nplain\r\n\
+              --abbc\r\nContent-Type: application/json\r\n\r\n{}\r\n\
+              --abbc--\r\n",
+        );
+        let default: Mime = "text/plain; charset=us-ascii".parse().unwrap();
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body.clone())]))
+            .default_part_content_type(default.clone());
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.content_type(), Some(&default));
+        drop(field);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.content_type(), Some(&mime::APPLICATION_JSON));
+        drop(field);
+        assert!(multipart.next().await.is_none());
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body)]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.content_type(), None);
+    }
+
+    #[cfg(all(feature = "form", feature = "tempfile"))]
+    #[ntex::test]
+    async fn test_field_write_to() {
+        let (bytes, headers) = create_request_without_length();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+
+        let mut limits = Limits::new(6, 0);
+        let mut sink = Vec::new();
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.write_to(&mut sink, &mut limits).await.unwrap(), 4);
+        assert_eq!(sink, b"test");
+
+        let field = multipart.next().await.unwrap().unwrap();
+        assert!(matches!(
+            field.write_to(&mut sink, &mut limits).await,
+            Err(MultipartError::Payload(PayloadError::Overflow))
+        ));
+    }
+
+    #[ntex::test]
+    async fn test_new_with_policy() {
+        struct Counting(Rc<std::cell::Cell<usize>>);
+
+        impl BufPolicy for Counting {
+            fn append(
+                &mut self,
+                buf: &mut ntex::util::BytesMut,
+                chunk: Bytes,
+            ) -> Result<(), PayloadError> {
+                self.0.set(self.0.get() + chunk.len());
+                buf.extend_from_slice(&chunk);
+                Ok(())
+            }
+        }
+
+        let (bytes, headers) = create_simple_request_with_header();
+        let appended = Rc::new(std::cell::Cell::new(0));
+        let policy = Counting(appended.clone());
+        let len = bytes.len();
+
+        let mut multipart =
+            Multipart::new_with_policy(&headers, stream::iter([Ok(bytes)]), policy);
+        let mut count = 0;
+        while let Some(field) = multipart.next().await {
+            let mut field = field.unwrap();
+            while field.next().await.is_some() {}
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        assert_eq!(appended.get(), len);
+    }
+
+    #[ntex::test]
+    async fn test_strict_headers() {
+        let (bytes, headers) = create_request_with_duplicate_header();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        drop(field);
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).strict_headers(true);
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::Decode(DecodeError::Header)))
+        ));
+
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"f\u{e9}\"\r\n\r\n\
+             test\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).strict_headers(true);
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::Decode(DecodeError::Header)))
+        ));
+    }
+
+    #[ntex::test]
+    async fn test_multipart_from_async_read() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let reader = futures::io::Cursor::new(bytes.to_vec());
+
+        let mut multipart = Multipart::from_async_read_with_chunk_size(&headers, reader, 3);
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "test");
+        drop(field);
+
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(get_whole_field(&mut field).await, "data");
+        drop(field);
+
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[cfg(feature = "digest")]
+    #[ntex::test]
+    async fn test_field_hashed() {
+        use sha2::Digest;
+
+        let (bytes, headers) = create_simple_request_with_header();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+
+        let mut field = multipart.next().await.unwrap().unwrap().hashed(sha2::Sha256::new());
+        assert_eq!(field.field().name(), Some("file"));
+        assert_eq!(field.next().await.unwrap().unwrap(), "test");
+        assert!(field.next().await.is_none());
+        assert_eq!(field.finish(), sha2::Sha256::digest(b"test").to_vec());
+    }
+
+    #[ntex::test]
+    async fn test_field_inspect() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+
+        let mut seen = Vec::new();
+        let mut field = multipart.next().await.unwrap().unwrap().inspect(|chunk| {
+            seen.extend_from_slice(chunk);
+            Ok(())
+        });
+        assert_eq!(field.field().name(), Some("file"));
+        assert_eq!(field.next().await.unwrap().unwrap(), "test");
+        assert!(field.next().await.is_none());
+        drop(field);
+        assert_eq!(seen, b"test");
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        let mut field = multipart.next().await.unwrap().unwrap().inspect(|chunk| {
+            if chunk.contains(&b'e') { Err(MultipartError::Incomplete) } else { Ok(()) }
+        });
+        assert!(matches!(field.next().await, Some(Err(MultipartError::Incomplete))));
+        assert!(field.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_field_map_chunks() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+
+        let mut field = multipart
+            .next()
+            .await
+            .unwrap()
+            .unwrap()
+            .map_chunks(|chunk| Ok(Bytes::from(chunk.to_ascii_uppercase())))
+            .map_chunks(|chunk| {
+                let mut wrapped = BytesMut::from("<");
+                wrapped.extend_from_slice(&chunk);
+                wrapped.extend_from_slice(b">");
+                Ok(wrapped.freeze())
+            });
+        assert_eq!(field.field().name(), Some("file"));
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        // every chunk is wrapped, however the body was split
+        let data = String::from_utf8(data).unwrap();
+        assert!(data.starts_with('<') && data.ends_with('>'));
+        assert_eq!(data.replace(['<', '>'], ""), "TEST");
+        drop(field);
+
+        let mut field = multipart
+            .next()
+            .await
+            .unwrap()
+            .unwrap()
+            .map_chunks(|_| Err(MultipartError::Incomplete));
+        assert!(matches!(field.next().await, Some(Err(MultipartError::Incomplete))));
+        assert!(field.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_field_with_max_chunks() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let one_byte_chunks = || {
+            let chunks: Vec<_> =
+                bytes.chunks(1).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+            stream::iter(chunks).interleave_pending()
+        };
+
+        let mut multipart = Multipart::new(&headers, one_byte_chunks());
+        let mut field = multipart.next().await.unwrap().unwrap().with_max_chunks(4);
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(data, b"test");
+        drop(field);
+
+        let mut multipart = Multipart::new(&headers, one_byte_chunks());
+        let mut field = multipart.next().await.unwrap().unwrap().with_max_chunks(3);
+        for _ in 0..3 {
+            assert_eq!(field.next().await.unwrap().unwrap().len(), 1);
+        }
+        assert!(matches!(field.next().await, Some(Err(MultipartError::TooManyChunks))));
+        assert!(field.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_field_chunked() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let chunks: Vec<_> = bytes.chunks(1).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+
+        let mut multipart = Multipart::new(&headers, stream::iter(chunks));
+        let mut field = multipart.next().await.unwrap().unwrap().chunked(3);
+        assert_eq!(field.field().name(), Some("file"));
+        assert_eq!(field.next().await.unwrap().unwrap(), "tes");
+        assert_eq!(field.next().await.unwrap().unwrap(), "t");
+        assert!(field.next().await.is_none());
+        drop(field);
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        let mut field = multipart.next().await.unwrap().unwrap().chunked(2);
+        assert_eq!(field.next().await.unwrap().unwrap(), "te");
+        assert_eq!(field.next().await.unwrap().unwrap(), "st");
+        assert!(field.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_buffer_watermarks() {
+        let (bytes, headers) = create_simple_request_with_header();
+        let chunks: Vec<_> = bytes.chunks(3).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter(chunks)).buffer_watermarks(4, 8);
+        let read = async {
+            let mut fields = 0;
+            while let Some(field) = multipart.next().await {
+                let mut field = field.unwrap();
+                while let Some(chunk) = field.next().await {
+                    chunk.unwrap();
+                }
+                fields += 1;
+            }
+            fields
+        };
+        let fields = ntex::time::timeout(Duration::from_secs(5), read).await.unwrap();
+        assert_eq!(fields, 2);
+    }
+
+    #[test]
+    fn test_filename_decoding() {
+        use crate::FilenameDecoding;
+        use ntex_files::header::Raw;
+
+        let cd = |raw: &'static str| ContentDisposition::parse_header(&Raw::from(raw)).unwrap();
+        let plain = cd("form-data; name=\"f\"; filename=\"caf%C3%A9.txt\"");
+        let ext =
+            cd("form-data; name=\"f\"; filename=\"cafe.txt\"; filename*=UTF-8''caf%C3%A9.txt");
+
+        assert_eq!(FilenameDecoding::Literal.decode(&plain).unwrap(), "caf%C3%A9.txt");
+        assert_eq!(FilenameDecoding::PercentDecode.decode(&plain).unwrap(), "caf\u{e9}.txt");
+        assert_eq!(FilenameDecoding::Auto.decode(&plain).unwrap(), "caf%C3%A9.txt");
+        assert_eq!(FilenameDecoding::Literal.decode(&ext).unwrap(), "cafe.txt");
+        assert_eq!(FilenameDecoding::Auto.decode(&ext).unwrap(), "caf\u{e9}.txt");
+
+        let mut field = Field::for_test(HeaderMap::new(), None, Some(ext), Bytes::new());
+        assert_eq!(field.filename().unwrap(), "cafe.txt");
+        field.filename_decoding = FilenameDecoding::Auto;
+        assert_eq!(field.filename().unwrap(), "caf\u{e9}.txt");
+    }
+
+    #[ntex::test]
+    async fn test_fallback_field_name() {
+        let bytes = Bytes::from_static(
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              Content-Disposition: form-data; filename=\"x.txt\"\r\n\r\n\
+              data\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        assert!(matches!(
+            multipart.next().await,
+            Some(Err(MultipartError::ContentDispositionNameMissing))
+        ));
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(bytes)])).fallback_field_name("upload");
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), None);
+        assert_eq!(field.form_field_name(), "upload");
+    }
+
+    #[ntex::test]
+    async fn test_validate_field_names() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+        let body = |cd: &[u8]| {
+            let mut body = BytesMut::new();
+            body.extend_from_slice(b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n");
+            body.extend_from_slice(cd);
+            body.extend_from_slice(b"\r\n\r\ndata\r\n--abbc761f78ff4d7cb7573b5a23f96ef0--\r\n");
+            body.freeze()
+        };
+
+        for cd in [
+            &b"Content-Disposition: form-data; name=\"a\tb\""[..],
+            b"Content-Disposition: form-data; name=\"\xff\xfe\"",
+        ] {
+            let mut multipart = Multipart::new(&headers, stream::iter([Ok(body(cd))]))
+                .validate_field_names(true);
+            assert!(matches!(
+                multipart.next().await,
+                Some(Err(MultipartError::InvalidFieldName))
+            ));
+        }
+
+        // the tab is passed on by default
+        let cd = b"Content-Disposition: form-data; name=\"a\tb\"";
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(body(cd))]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("a\tb"));
+        drop(field);
+
+        let cd = b"Content-Disposition: form-data; name=\"caf\xc3\xa9\"";
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(body(cd))])).validate_field_names(true);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("caf\u{e9}"));
+    }
+
+    #[test]
+    fn test_content_type_params() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; charset=UTF-8; boundary=\"abbc\"; x-note=1",
+            ),
+        );
+        let multipart = Multipart::new(&headers, stream::empty());
+        assert_eq!(
+            multipart.content_type_params(),
+            [
+                ("charset".to_owned(), "utf-8".to_owned()),
+                ("boundary".to_owned(), "abbc".to_owned()),
+                ("x-note".to_owned(), "1".to_owned()),
+            ]
+        );
+
+        let headers = HeaderMap::new();
+        let multipart = Multipart::new(&headers, stream::empty());
+        assert!(multipart.content_type_params().is_empty());
+    }
+
+    #[ntex::test]
+    async fn test_take_field() {
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"payload\"\r\n\r\n\
+             data\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"signature\"\r\n\r\n\
+             sig\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"rest\"\r\n\r\n\
+             more\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        let mut field = multipart.take_field("signature").await.unwrap().unwrap();
+        assert_eq!(field.next().await.unwrap().unwrap(), "sig");
+        assert!(field.next().await.is_none());
+        drop(field);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("rest"));
+        drop(field);
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        assert!(multipart.take_field("missing").await.unwrap().is_none());
+    }
+
+    #[ntex::test]
+    async fn test_find_field() {
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"meta\"\r\n\
+             Content-Type: application/json\r\n\r\n\
+             {}\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\n\
+             Content-Type: image/png\r\n\r\n\
+             png\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"note\"\r\n\r\n\
+             text\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        let mut field = multipart
+            .find_field(|field| {
+                field.content_type().is_some_and(|ct| ct.type_() == mime::IMAGE)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.name(), Some("image"));
+        assert_eq!(field.next().await.unwrap().unwrap(), "png");
+        drop(field);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("note"));
+        drop(field);
+
+        let mut multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        let field = multipart.find_field(|field| field.name() == Some("note")).await.unwrap();
+        assert_eq!(field.unwrap().name(), Some("note"));
+        assert!(multipart.find_field(|_| true).await.unwrap().is_none());
+    }
+
+    #[cfg(feature = "form")]
+    #[ntex::test]
+    async fn test_collect_grouped() {
+        let bytes = Bytes::from(
+            "--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"tag\"\r\n\r\n\
+             web\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             ntex\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+             Content-Disposition: form-data; name=\"tag\"\r\n\r\n\
+             async\r\n\
+             --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=abbc761f78ff4d7cb7573b5a23f96ef0",
+            ),
+        );
+
+        let multipart = Multipart::new(&headers, stream::iter([Ok(bytes.clone())]));
+        let groups = multipart.collect_grouped(Limits::new(1024, 1024)).await.unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["tag"], vec![Bytes::from("web"), Bytes::from("async")]);
+        assert_eq!(groups["title"], vec![Bytes::from("ntex")]);
+
+        let multipart = Multipart::new(&headers, stream::iter([Ok(bytes)]));
+        let res = multipart.collect_grouped(Limits::new(1024, 8)).await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
 }
@@ -1,9 +1,13 @@
-use crate::form::Limits;
 use crate::form::State;
-use crate::{Field, MultipartError};
-use derive_more::{Deref, DerefMut};
+use crate::form::{FieldKind, FormContext, Limits};
+use crate::{Field, FilenameDecoding, Multipart, MultipartError};
+use bytesize::ByteSize;
+use derive_more::{Deref, DerefMut, Display, Error};
 use futures::future::LocalBoxFuture;
+use ntex::http::{HeaderMap, Payload};
+use ntex::util::Bytes;
 use ntex::web::{Error, HttpRequest};
+use std::borrow::Cow;
 use std::sync::Arc;
 
 #[cfg(feature = "derive")]
@@ -17,10 +21,16 @@ pub trait MultipartCollect: Sized {
     /// across all fields sharing the same name.
     fn limit(field_name: &str) -> Option<usize>;
 
+    /// The kind of part expected for a given field name, used by
+    /// [`MultipartFormConfig::reject_inconsistent_parts`].
+    fn field_kind(_field_name: &str) -> FieldKind {
+        FieldKind::Any
+    }
+
     /// The extractor will call this function for each incoming field, the state can be updated
     /// with the processed field data.
     fn handle_field<'t>(
-        req: &'t HttpRequest,
+        ctx: &'t FormContext,
         field: Field,
         limits: &'t mut Limits,
         state: &'t mut State,
@@ -51,9 +61,82 @@ impl<T: MultipartCollect> MultipartForm<T> {
     }
 }
 
+/// Typed `multipart/form-data` extractor that also keeps the content of every part.
+///
+/// Works like [`struct@MultipartForm`], and additionally collects the name and content of each
+/// part in `raw`, in the order received, e.g. to hand file parts to a separate pipeline. Every
+/// part is read into memory before `T` processes a copy of it. The raw copy counts against the
+/// total limit, the copy of `T` against the memory limit if it is kept in memory.
+pub struct MultipartFormWithRaw<T: MultipartCollect> {
+    /// The typed form.
+    pub form: T,
+
+    /// Name and content of every part.
+    pub raw: Vec<(String, Bytes)>,
+}
+
+impl<T: MultipartCollect> MultipartFormWithRaw<T> {
+    /// Unwrap into the typed form and the raw parts.
+    pub fn into_inner(self) -> (T, Vec<(String, Bytes)>) {
+        (self.form, self.raw)
+    }
+}
+
+/// Typed `multipart/form-data` extractor that also reports how much data the form consumed.
+///
+/// Works like [`struct@MultipartForm`], and additionally records the size of every part in
+/// [`FormStats`], e.g. for billing or quota accounting.
+pub struct MultipartFormWithStats<T: MultipartCollect> {
+    /// The typed form.
+    pub form: T,
+
+    /// Sizes of the form parts.
+    pub stats: FormStats,
+}
+
+impl<T: MultipartCollect> MultipartFormWithStats<T> {
+    /// Unwrap into the typed form and its stats.
+    pub fn into_inner(self) -> (T, FormStats) {
+        (self.form, self.stats)
+    }
+}
+
+/// Amount of data read by a [`MultipartFormWithStats`] extractor.
+///
+/// Sizes are the content bytes of each part, whether the form read it or skipped it, e.g. an
+/// unknown field. Part headers and boundaries are not included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormStats {
+    /// Total content bytes of all parts.
+    pub total_bytes: usize,
+
+    /// Number of parts received.
+    pub parts: usize,
+
+    /// Field name and content size of every part, in the order received.
+    pub field_sizes: Vec<(String, usize)>,
+}
+
+/// Names of the fields cut short by their field limit.
+///
+/// Inserted into the request extensions by [`struct@MultipartForm`] when
+/// [`MultipartFormConfig::partial_on_limit`] is enabled and a limit was hit.
+#[derive(Debug, Clone, Deref)]
+pub struct TruncatedFields(pub Vec<String>);
+
+/// Names of the processed fields in the order they were received, one entry per field.
+///
+/// Inserted into the request extensions by [`struct@MultipartForm`] when
+/// [`MultipartFormConfig::ordered_state`] is enabled.
+#[derive(Debug, Clone, Deref)]
+pub struct FieldOrder(pub Vec<String>);
+
 type MultipartFormErrorHandler =
     Option<Arc<dyn Fn(MultipartError, &HttpRequest) -> Error + Send + Sync>>;
 
+type FieldNamesValidator =
+    Option<Arc<dyn Fn(&[&str]) -> Result<(), MultipartError> + Send + Sync>>;
+
 /// [`struct@MultipartForm`] extractor configuration.
 ///
 /// Add to your app data to have it picked up by [`struct@MultipartForm`] extractors.
@@ -61,33 +144,288 @@ type MultipartFormErrorHandler =
 pub struct MultipartFormConfig {
     pub(crate) total_limit: usize,
     pub(crate) memory_limit: usize,
+    pub(crate) decompressed_limit: Option<usize>,
+    pub(crate) count_decompressed: bool,
+    pub(crate) refund_replaced: bool,
+    pub(crate) separate_memory_limit: bool,
+    pub(crate) partial_on_limit: bool,
+    pub(crate) ordered_state: bool,
+    pub(crate) max_empty_parts: Option<usize>,
+    pub(crate) empty_part_size: usize,
+    pub(crate) trim_field_names: bool,
+    pub(crate) filename_decoding: FilenameDecoding,
+    pub(crate) allow_missing_field_name: bool,
+    pub(crate) missing_field_name: Cow<'static, str>,
+    pub(crate) reject_inconsistent_parts: bool,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Option<std::sync::Arc<crate::MultipartMetrics>>,
     pub(crate) err_handler: MultipartFormErrorHandler,
+    pub(crate) field_validator: FieldNamesValidator,
 }
 
 impl MultipartFormConfig {
     /// Sets maximum accepted payload size for the entire form. By default this limit is 50MiB.
+    ///
+    /// Fields read into memory count against this limit too, unless
+    /// [`separate_memory_limit`](Self::separate_memory_limit) is enabled.
     pub fn total_limit(mut self, total_limit: usize) -> Self {
         self.total_limit = total_limit;
         self
     }
 
+    /// Sets maximum accepted payload size for the entire form from a human-readable size.
+    ///
+    /// The size is parsed using [`bytesize`], like the `#[multipart(limit = "...")]` attribute
+    /// of the derive macro, e.g. `"50MiB"`, `"1.5 MiB"`, `"2MB"` or `"1024"`.
+    ///
+    /// [`bytesize`]: https://docs.rs/bytesize/2
+    pub fn total_limit_str(self, total_limit: &str) -> Result<Self, ParseSizeError> {
+        Ok(self.total_limit(parse_size(total_limit)?))
+    }
+
     /// Sets maximum accepted data that will be read into memory. By default this limit is 2MiB.
+    ///
+    /// Files written to disk do not count against this limit.
     pub fn memory_limit(mut self, memory_limit: usize) -> Self {
         self.memory_limit = memory_limit;
         self
     }
 
-    /// Extracts payload config from app data. Check both `T` and `Data<T>`, in that order, and fall
-    /// back to the default payload config.
-    pub(crate) fn from_req(req: &HttpRequest) -> &Self {
-        req.app_state::<Self>().unwrap_or(&DEFAULT_CONFIG)
+    /// Sets maximum decompressed size of the fields read by the form. Unlimited by default.
+    ///
+    /// Only applies to a body sent with a request-level `Content-Encoding`, which is
+    /// decompressed with the `compress` feature, see `Multipart::max_decompressed_size`.
+    pub fn decompressed_limit(mut self, decompressed_limit: usize) -> Self {
+        self.decompressed_limit = Some(decompressed_limit);
+        self
+    }
+
+    /// Sets whether decompressed fields count against the total, memory and field limits with
+    /// their decompressed size, rather than their size on the wire. Enabled by default.
+    ///
+    /// On the wire, a field is charged with the compressed bytes received since the previous
+    /// chunk was read, which includes part headers and skipped parts.
+    pub fn count_decompressed(mut self, count_decompressed: bool) -> Self {
+        self.count_decompressed = count_decompressed;
+        self
+    }
+
+    /// Sets whether a field replaced under `#[multipart(duplicate_field = "replace")]` gives
+    /// back the memory and field limits it consumed. Disabled by default.
+    ///
+    /// See [`Limits::refund_replaced`] for how the limits are accounted.
+    ///
+    /// [`Limits::refund_replaced`]: crate::form::Limits::refund_replaced
+    pub fn refund_replaced(mut self, refund_replaced: bool) -> Self {
+        self.refund_replaced = refund_replaced;
+        self
+    }
+
+    /// Sets whether fields read into memory count against the memory limit only, instead of
+    /// against both the memory and the total limit. Disabled by default.
+    ///
+    /// When enabled, files written to disk and in-memory fields like JSON have independent
+    /// budgets, the total limit and the memory limit respectively, so a large upload can not
+    /// starve the other fields of the form. See [`Limits::separate_memory_limit`].
+    ///
+    /// [`Limits::separate_memory_limit`]: crate::form::Limits::separate_memory_limit
+    pub fn separate_memory_limit(mut self, separate_memory_limit: bool) -> Self {
+        self.separate_memory_limit = separate_memory_limit;
+        self
+    }
+
+    /// Sets whether a field name that exceeds its `#[multipart(limit)]` keeps the fields
+    /// already read.
+    ///
+    /// When enabled, the field that crossed the limit and any later field with the same name
+    /// are skipped instead of failing the request, so e.g. a `Vec<TempFile>` holds the files
+    /// completed before the limit was hit. The skipped names are listed in the
+    /// [`TruncatedFields`] request extension. The total and memory limits still fail the
+    /// request. Disabled by default.
+    pub fn partial_on_limit(mut self, partial_on_limit: bool) -> Self {
+        self.partial_on_limit = partial_on_limit;
+        self
+    }
+
+    /// Sets whether the form state records the order fields were received in.
+    ///
+    /// When enabled, the field names are listed in arrival order in the [`FieldOrder`] request
+    /// extension, e.g. for a handler where one field configures how a later one is handled.
+    /// This costs an extra allocation per field, so it is disabled by default.
+    pub fn ordered_state(mut self, ordered_state: bool) -> Self {
+        self.ordered_state = ordered_state;
+        self
+    }
+
+    /// Sets the maximum number of empty parts accepted in a form. Unlimited by default.
+    ///
+    /// A body made of millions of bare boundaries is cheap to send but makes the extractor
+    /// spin through a field for each of them. With this limit, the request fails with
+    /// [`MultipartError::TooManyEmptyParts`] once more than `max_empty_parts` parts are seen
+    /// whose body is at most [`empty_part_size`](Self::empty_part_size) bytes, whether the form
+    /// reads them or skips them. Keep the limit well above the number of legitimately empty
+    /// fields of the form.
+    pub fn max_empty_parts(mut self, max_empty_parts: usize) -> Self {
+        self.max_empty_parts = Some(max_empty_parts);
+        self
+    }
+
+    /// Sets the size in bytes up to which a part counts as empty for
+    /// [`max_empty_parts`](Self::max_empty_parts). By default only parts without any data do.
+    pub fn empty_part_size(mut self, empty_part_size: usize) -> Self {
+        self.empty_part_size = empty_part_size;
+        self
+    }
+
+    /// Sets whether leading and trailing whitespace is trimmed from field names.
+    ///
+    /// Helps with clients sending names like `name=" upload "`. The trimmed name is used for
+    /// routing fields to the form, for field limits and for the form state. Disabled by
+    /// default, in which case names must match exactly.
+    pub fn trim_field_names(mut self, trim_field_names: bool) -> Self {
+        self.trim_field_names = trim_field_names;
+        self
+    }
+
+    /// Sets how filenames are decoded for all fields of the form.
+    ///
+    /// Applies to [`Field::filename`] and therefore to the `file_name` of the form readers. By
+    /// default filenames are used literally, see [`FilenameDecoding`] for the alternatives.
+    pub fn filename_decoding(mut self, filename_decoding: FilenameDecoding) -> Self {
+        self.filename_decoding = filename_decoding;
+        self
+    }
+
+    /// Sets whether parts without a `name` in their Content-Disposition are accepted.
+    ///
+    /// Such parts, usually files sent by malformed clients, fail the form with
+    /// [`MultipartError::ContentDispositionNameMissing`] by default. When allowed, they are
+    /// handled as fields named after [`MultipartFormConfig::missing_field_name`], so they can
+    /// be collected by a catch-all field of the form.
+    pub fn allow_missing_field_name(mut self, allow_missing_field_name: bool) -> Self {
+        self.allow_missing_field_name = allow_missing_field_name;
+        self
+    }
+
+    /// Sets whether parts that do not match the kind of their form field are rejected.
+    ///
+    /// Each field reader declares a [`FieldKind`]: readers of data such as [`Json`] or
+    /// [`Text`] expect [`FieldKind::Data`], readers writing files such as [`TempFile`] expect
+    /// [`FieldKind::File`], and others like [`Bytes`] accept either. When enabled, a part with a
+    /// `filename` in its Content-Disposition routed to a data field, e.g. a file smuggled into a
+    /// JSON field, or a part without one routed to a file field fails with
+    /// [`MultipartError::InconsistentPart`]. The part Content-Type is not considered. Disabled
+    /// by default.
+    ///
+    /// [`Json`]: crate::form::json::Json
+    /// [`Text`]: crate::form::text::Text
+    /// [`TempFile`]: crate::form::temp_file::TempFile
+    /// [`Bytes`]: crate::form::bytes::Bytes
+    pub fn reject_inconsistent_parts(mut self, reject_inconsistent_parts: bool) -> Self {
+        self.reject_inconsistent_parts = reject_inconsistent_parts;
+        self
+    }
+
+    /// Count parsed parts, consumed bytes and rejected forms of the extractors in `metrics`.
+    ///
+    /// A rejected form counts once, with the [`RejectReason`](crate::RejectReason) of the
+    /// error it failed with.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: std::sync::Arc<crate::MultipartMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the field name given to parts without one, `"file"` by default.
+    ///
+    /// Only used with [`MultipartFormConfig::allow_missing_field_name`] enabled.
+    pub fn missing_field_name(mut self, missing_field_name: impl Into<String>) -> Self {
+        self.missing_field_name = Cow::Owned(missing_field_name.into());
+        self
+    }
+
+    /// Sets a check run on the names of the received fields once all parts are read, before
+    /// the form is built with [`MultipartCollect::from_state`].
+    ///
+    /// `validator` is given every field name received at least once, sorted, including fields
+    /// the form does not declare. Returning an error rejects the form with it, which allows
+    /// cross-field requirements, e.g. that exactly one of `email` or `phone` is present, to be
+    /// checked in one place.
+    pub fn validate_fields<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&[&str]) -> Result<(), MultipartError> + Send + Sync + 'static,
+    {
+        self.field_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Extracts the config from the form context, falling back to the default config.
+    pub(crate) fn from_ctx(ctx: &FormContext) -> &Self {
+        ctx.config::<Self>().unwrap_or(&DEFAULT_CONFIG)
+    }
+
+    /// Create the multipart stream of a form body.
+    pub(crate) fn multipart(&self, headers: &HeaderMap, payload: &mut Payload) -> Multipart {
+        let mut multipart = Multipart::new(headers, payload.take());
+        if self.allow_missing_field_name {
+            multipart = multipart.fallback_field_name(self.missing_field_name.clone());
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(ref metrics) = self.metrics {
+            multipart =
+                multipart.with_recorder(crate::metrics::Recorder::new(metrics.clone(), false));
+        }
+        multipart
+    }
+
+    /// Create the limits the body of `multipart` is read with.
+    #[cfg_attr(not(feature = "compress"), allow(unused_variables))]
+    pub(crate) fn limits(&self, multipart: &Multipart) -> Limits {
+        let mut limits = Limits::new(self.total_limit, self.memory_limit)
+            .count_decompressed(self.count_decompressed)
+            .refund_replaced(self.refund_replaced)
+            .separate_memory_limit(self.separate_memory_limit);
+        limits.decompressed_limit_remaining = self.decompressed_limit;
+        #[cfg(feature = "compress")]
+        {
+            limits.compressed = multipart.compressed_source().map(|source| (source, 0));
+        }
+        limits
+    }
+
+    /// Apply the settings of the form to a part, before it is matched by its name.
+    pub(crate) fn prepare_field(&self, field: &mut Field) {
+        field.filename_decoding = self.filename_decoding;
+
+        if self.trim_field_names {
+            let trimmed = field.form_field_name.trim();
+            if trimmed.len() != field.form_field_name.len() {
+                field.form_field_name = trimmed.to_owned();
+            }
+        }
     }
 }
 
 const DEFAULT_CONFIG: MultipartFormConfig = MultipartFormConfig {
     total_limit: 52_428_800, // 50 MiB
     memory_limit: 2_097_152, // 2 MiB
+    decompressed_limit: None,
+    count_decompressed: true,
+    refund_replaced: false,
+    separate_memory_limit: false,
+    partial_on_limit: false,
+    ordered_state: false,
+    max_empty_parts: None,
+    empty_part_size: 0,
+    trim_field_names: false,
+    filename_decoding: FilenameDecoding::Literal,
+    allow_missing_field_name: false,
+    missing_field_name: Cow::Borrowed("file"),
+    reject_inconsistent_parts: false,
+    #[cfg(feature = "metrics")]
+    metrics: None,
     err_handler: None,
+    field_validator: None,
 };
 
 impl Default for MultipartFormConfig {
@@ -95,3 +433,43 @@ impl Default for MultipartFormConfig {
         DEFAULT_CONFIG
     }
 }
+
+/// Error returned when a human-readable size can not be parsed.
+#[derive(Debug, Display, Error)]
+#[display("Invalid size: {:?}", _0)]
+pub struct ParseSizeError(#[error(not(source))] String);
+
+fn parse_size(size: &str) -> Result<usize, ParseSizeError> {
+    let err = || ParseSizeError(size.to_owned());
+
+    let ByteSize(size) = size.parse::<ByteSize>().map_err(|_| err())?;
+    usize::try_from(size).map_err(|_| err())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10B").unwrap(), 10);
+        assert_eq!(parse_size("2MB").unwrap(), 2_000_000);
+        assert_eq!(parse_size("50MiB").unwrap(), 52_428_800);
+        assert_eq!(parse_size("4 kib").unwrap(), 4096);
+        assert_eq!(parse_size("1GiB").unwrap(), 1_073_741_824);
+        assert_eq!(parse_size("1.5 MiB").unwrap(), 1_572_864);
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MiB").is_err());
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn test_total_limit_str() {
+        let config = MultipartFormConfig::default().total_limit_str("50MiB").unwrap();
+        assert_eq!(config.total_limit, 52_428_800);
+        assert!(MultipartFormConfig::default().total_limit_str("fifty").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! Parts of a multipart stream bundled with their metadata
+
+use crate::Field;
+use mime::Mime;
+
+/// A part yielded by [`Multipart::parts`], with the metadata handler loops usually look at
+/// taken out of its headers.
+///
+/// [`Multipart::parts`]: crate::Multipart::parts
+#[derive(Debug)]
+pub struct Part {
+    /// The `name` parameter of the Content-Disposition.
+    pub name: Option<String>,
+
+    /// The parsed Content-Type.
+    pub content_type: Option<Mime>,
+
+    /// The filename, see [`Field::filename`].
+    pub filename: Option<String>,
+
+    /// The part content, the other headers are available through it as well.
+    pub body: Field,
+}
+
+impl Part {
+    pub(crate) fn new(body: Field) -> Self {
+        Part {
+            name: body.name().map(ToOwned::to_owned),
+            content_type: body.content_type().cloned(),
+            filename: body.filename().map(|filename| filename.into_owned()),
+            body,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Multipart, MultipartError};
+    use futures::{StreamExt, TryStreamExt, stream};
+    use ntex::http::HeaderMap;
+    use ntex::http::header::{self, HeaderValue};
+    use ntex::util::Bytes;
+
+    #[ntex::test]
+    async fn test_parts() {
+        let body = Bytes::from_static(
+            b"--abbc\r\n\
+              Content-Disposition: form-data; name=\"meta\"\r\n\r\n\
+              {}\r\n\
+              --abbc\r\n\
+              Content-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\
+              Content-Type: image/png\r\n\r\n\
+              png\r\n\
+              --abbc--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+        );
+
+        let mut parts = Multipart::new(&headers, stream::iter([Ok(body)])).parts();
+        let mut seen = Vec::new();
+        while let Some(mut part) = parts.try_next().await.unwrap() {
+            let data = part.body.next().await.unwrap().unwrap();
+            seen.push((part.name, part.filename, part.content_type, data));
+        }
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].0.as_deref(), Some("meta"));
+        assert_eq!(seen[0].1, None);
+        assert_eq!(seen[0].3, "{}");
+        assert_eq!(seen[1].1.as_deref(), Some("a.png"));
+        assert_eq!(seen[1].2, Some(mime::IMAGE_PNG));
+
+        let mut parts =
+            Multipart::new(&HeaderMap::new(), stream::iter([Ok(Bytes::new())])).parts();
+        assert!(matches!(parts.next().await, Some(Err(MultipartError::NoContentType))));
+    }
+}
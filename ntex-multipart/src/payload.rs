@@ -3,8 +3,9 @@ use crate::safety::Safety;
 use futures::stream::LocalBoxStream;
 use futures::{Stream, StreamExt};
 use ntex::http::error::PayloadError;
+use ntex::time::Sleep;
 use ntex::util::{Bytes, BytesMut};
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
@@ -24,6 +25,11 @@ impl PayloadRef {
     {
         if s.current() { Some(self.payload.borrow_mut()) } else { None }
     }
+
+    /// Access the buffer regardless of the current task, only meant for configuration.
+    pub(crate) fn buffer(&self) -> RefMut<'_, PayloadBuffer> {
+        self.payload.borrow_mut()
+    }
 }
 
 impl Clone for PayloadRef {
@@ -32,11 +38,59 @@ impl Clone for PayloadRef {
     }
 }
 
+/// Strategy for how the multipart payload buffer stores incoming data.
+///
+/// The parser keeps not yet consumed payload data in a single [`BytesMut`]. A policy decides
+/// how that buffer grows when new chunks arrive and when unprocessed data is put back, e.g. to
+/// reserve memory up front or to cap the buffer size. See [`Multipart::new_with_policy`].
+///
+/// [`Multipart::new_with_policy`]: crate::Multipart::new_with_policy
+pub trait BufPolicy {
+    /// Append a chunk received from the payload stream to the end of `buf`.
+    ///
+    /// Returning an error fails the multipart stream with it.
+    fn append(&mut self, buf: &mut BytesMut, chunk: Bytes) -> Result<(), PayloadError> {
+        buf.extend_from_slice(&chunk);
+        Ok(())
+    }
+
+    /// Put `data` that was read but not processed back in front of `buf`.
+    fn prepend(&mut self, buf: &mut BytesMut, data: Bytes) {
+        let rest = std::mem::replace(buf, BytesMut::from(data.as_ref()));
+        buf.extend_from_slice(&rest);
+    }
+}
+
+/// The default [`BufPolicy`], growing the buffer as needed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultBufPolicy;
+
+impl BufPolicy for DefaultBufPolicy {}
+
 /// Payload buffer
 pub(crate) struct PayloadBuffer {
     pub(crate) eof: bool,
     pub(crate) buf: BytesMut,
     pub(crate) stream: LocalBoxStream<'static, Result<Bytes, PayloadError>>,
+    pub(crate) deadline: Option<Sleep>,
+    pub(crate) policy: Box<dyn BufPolicy>,
+    /// Low and high watermark of buffered data, see [`Multipart::buffer_watermarks`].
+    ///
+    /// [`Multipart::buffer_watermarks`]: crate::Multipart::buffer_watermarks
+    pub(crate) watermarks: Option<(usize, usize)>,
+    /// Buffer length when pulling from the stream was last paused.
+    paused_at: Option<usize>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Option<crate::metrics::Recorder>,
+    /// Size accounting of a compressed body, see [`Multipart::max_decompressed_size`].
+    ///
+    /// [`Multipart::max_decompressed_size`]: crate::Multipart::max_decompressed_size
+    #[cfg(feature = "compress")]
+    pub(crate) decompressed: Option<crate::decompress::Decompressed>,
+    /// Error of the field a nested body is read from, see [`Field::nested_multipart`].
+    ///
+    /// [`Field::nested_multipart`]: crate::Field::nested_multipart
+    pub(crate) field_error: Option<Rc<Cell<Option<MultipartError>>>>,
 }
 
 impl PayloadBuffer {
@@ -45,14 +99,77 @@ impl PayloadBuffer {
     where
         S: Stream<Item = Result<Bytes, PayloadError>> + 'static,
     {
-        PayloadBuffer { eof: false, buf: BytesMut::new(), stream: stream.boxed_local() }
+        PayloadBuffer {
+            eof: false,
+            buf: BytesMut::new(),
+            stream: stream.boxed_local(),
+            deadline: None,
+            policy: Box::new(DefaultBufPolicy),
+            watermarks: None,
+            paused_at: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "compress")]
+            decompressed: None,
+            field_error: None,
+        }
     }
 
-    pub(crate) fn poll_stream(&mut self, cx: &mut Context) -> Result<(), PayloadError> {
+    pub(crate) fn poll_stream(&mut self, cx: &mut Context) -> Result<(), MultipartError> {
+        if let Some(ref deadline) = self.deadline
+            && deadline.poll_elapsed(cx).is_ready()
+        {
+            return Err(MultipartError::Timeout);
+        }
+
+        let mut stalled = false;
+        if let Some((low, _)) = self.watermarks
+            && let Some(paused_at) = self.paused_at.take()
+        {
+            // stay paused while the consumer drains the backlog, resume once it is below the
+            // low watermark or the consumer stopped making progress and needs more data
+            let len = self.buf.len();
+            if len > low && len < paused_at {
+                self.paused_at = Some(len);
+                cx.waker().wake_by_ref();
+                return Ok(());
+            }
+            stalled = len >= paused_at;
+        }
+
         loop {
+            if let Some((_, high)) = self.watermarks
+                && self.buf.len() >= high
+                && !stalled
+            {
+                self.paused_at = Some(self.buf.len());
+                cx.waker().wake_by_ref();
+                return Ok(());
+            }
+
             match Pin::new(&mut self.stream).poll_next(cx) {
-                Poll::Ready(Some(Ok(data))) => self.buf.extend_from_slice(&data),
-                Poll::Ready(Some(Err(e))) => return Err(e),
+                Poll::Ready(Some(Ok(data))) => {
+                    #[cfg(feature = "metrics")]
+                    if let Some(ref metrics) = self.metrics {
+                        metrics.bytes(data.len());
+                    }
+                    #[cfg(feature = "compress")]
+                    if let Some(ref mut decompressed) = self.decompressed {
+                        decompressed.consume(data.len())?;
+                    }
+                    self.policy.append(&mut self.buf, data)?;
+                    stalled = false;
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    if let Some(err) = self.field_error.as_ref().and_then(|err| err.take()) {
+                        return Err(err);
+                    }
+                    #[cfg(feature = "compress")]
+                    if let Some(ref decompressed) = self.decompressed {
+                        return Err(decompressed.error(e));
+                    }
+                    return Err(e.into());
+                }
                 Poll::Ready(None) => {
                     self.eof = true;
                     return Ok(());
@@ -102,9 +219,7 @@ impl PayloadBuffer {
 
     /// Put unprocessed data back to the buffer
     pub(crate) fn unprocessed(&mut self, data: Bytes) {
-        let buf = BytesMut::from(data.as_ref());
-        let buf = std::mem::replace(&mut self.buf, buf);
-        self.buf.extend_from_slice(&buf);
+        self.policy.prepend(&mut self.buf, data);
     }
 }
 
@@ -141,6 +256,32 @@ mod tests {
         assert!(payload.eof);
     }
 
+    #[ntex::test]
+    async fn test_watermarks() {
+        let (sender, payload) = bstream::channel();
+        let mut payload = PayloadBuffer::new(payload);
+        payload.watermarks = Some((4, 8));
+
+        for _ in 0..5 {
+            sender.feed_data(Bytes::from("abcd"));
+        }
+        lazy(|cx| payload.poll_stream(cx)).await.unwrap();
+        assert_eq!(payload.buf.len(), 8);
+
+        // draining above the low watermark keeps the stream paused
+        payload.read_max(2).unwrap();
+        lazy(|cx| payload.poll_stream(cx)).await.unwrap();
+        assert_eq!(payload.buf.len(), 6);
+
+        // no progress since the last poll, more data is pulled
+        lazy(|cx| payload.poll_stream(cx)).await.unwrap();
+        assert_eq!(payload.buf.len(), 10);
+
+        payload.read_max(7).unwrap();
+        lazy(|cx| payload.poll_stream(cx)).await.unwrap();
+        assert_eq!(payload.buf.len(), 11);
+    }
+
     #[ntex::test]
     async fn test_err() {
         let (sender, payload) = bstream::channel();
@@ -202,4 +343,33 @@ mod tests {
         assert_eq!(Some(Bytes::from("1line2")), payload.read_until(b"2").unwrap());
         assert_eq!(payload.buf.len(), 0);
     }
+
+    /// Refuses to buffer more than a fixed number of bytes.
+    struct Capped(usize);
+
+    impl BufPolicy for Capped {
+        fn append(&mut self, buf: &mut BytesMut, chunk: Bytes) -> Result<(), PayloadError> {
+            if buf.len() + chunk.len() > self.0 {
+                return Err(PayloadError::Overflow);
+            }
+            buf.extend_from_slice(&chunk);
+            Ok(())
+        }
+    }
+
+    #[ntex::test]
+    async fn test_policy() {
+        let (sender, payload) = bstream::channel();
+        let mut payload = PayloadBuffer::new(payload);
+        payload.policy = Box::new(Capped(8));
+
+        sender.feed_data(Bytes::from("line1"));
+        lazy(|cx| payload.poll_stream(cx)).await.unwrap();
+        payload.unprocessed(Bytes::from("0"));
+        assert_eq!(&payload.buf[..], b"0line1");
+
+        sender.feed_data(Bytes::from("line2"));
+        let res = lazy(|cx| payload.poll_stream(cx)).await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
 }
@@ -1,5 +1,6 @@
 use ntex::task::LocalWaker;
 use std::cell::Cell;
+use std::fmt;
 use std::marker::PhantomData;
 use std::rc::Rc;
 use std::task::Context;
@@ -7,14 +8,21 @@ use std::task::Context;
 /// Counter. It tracks of number of clones of payloads and give access to
 /// payload only to top most task panics if Safety get destroyed and it not top
 /// most task.
-#[derive(Debug)]
 pub(crate) struct Safety {
     task: LocalWaker,
     level: usize,
     payload: Rc<PhantomData<bool>>,
     clean: Rc<Cell<bool>>,
+    on_unconsumed: Option<UnconsumedHook>,
+    probe: Option<Probe>,
 }
 
+/// Callback reporting the name of a field dropped before being fully read.
+pub(crate) type UnconsumedHook = Rc<dyn Fn(&str)>;
+
+/// Check returning the field name if the field was not fully read.
+pub(crate) type Probe = Box<dyn Fn() -> Option<String>>;
+
 impl Safety {
     pub(crate) fn new() -> Safety {
         let payload = Rc::new(PhantomData);
@@ -23,9 +31,25 @@ impl Safety {
             level: Rc::strong_count(&payload),
             clean: Rc::new(Cell::new(true)),
             payload,
+            on_unconsumed: None,
+            probe: None,
         }
     }
 
+    /// Set the callback reporting fields dropped before being fully read.
+    pub(crate) fn on_unconsumed(&mut self, f: UnconsumedHook) {
+        self.on_unconsumed = Some(f);
+    }
+
+    pub(crate) fn has_unconsumed_hook(&self) -> bool {
+        self.on_unconsumed.is_some()
+    }
+
+    /// Set the check run on drop, returning the field name if it was not fully read.
+    pub(crate) fn probe(&mut self, probe: Probe) {
+        self.probe = Some(probe);
+    }
+
     pub(crate) fn current(&self) -> bool {
         Rc::strong_count(&self.payload) == self.level && self.clean.get()
     }
@@ -41,14 +65,34 @@ impl Safety {
             level: Rc::strong_count(&payload),
             clean: self.clean.clone(),
             payload,
+            on_unconsumed: self.on_unconsumed.clone(),
+            probe: None,
         };
         s.task.register(cx.waker());
         s
     }
 }
 
+impl fmt::Debug for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Safety")
+            .field("task", &self.task)
+            .field("level", &self.level)
+            .field("payload", &self.payload)
+            .field("clean", &self.clean)
+            .finish()
+    }
+}
+
 impl Drop for Safety {
     fn drop(&mut self) {
+        // diagnostics only, does not affect the state below
+        if let (Some(probe), Some(on_unconsumed)) = (&self.probe, &self.on_unconsumed)
+            && let Some(name) = probe()
+        {
+            on_unconsumed(&name);
+        }
+
         // parent task is dead
         if Rc::strong_count(&self.payload) != self.level {
             self.clean.set(true);
@@ -0,0 +1,152 @@
+//! Metadata scan of a buffered multipart body
+
+use crate::{Multipart, MultipartError};
+use futures::stream::{self, Stream, StreamExt};
+use mime::Mime;
+use ntex::http::HeaderMap;
+use ntex::http::error::PayloadError;
+use ntex::util::{Bytes, BytesMut};
+
+/// A multipart body buffered in memory, along with the metadata of its parts.
+///
+/// Meant for middleware that routes on the shape of a form, e.g. which fields it has or how
+/// large its files are, and then hands the untouched body to the actual handler. The body is
+/// scanned once for part headers and sizes, the data stays available through
+/// [`ScannedMultipart::body`] or as a fresh [`Multipart`] via
+/// [`ScannedMultipart::into_multipart`].
+#[derive(Debug, Clone)]
+pub struct ScannedMultipart {
+    parts: Vec<PartMetadata>,
+    body: Bytes,
+}
+
+/// Headers and size of a part found by [`ScannedMultipart::scan`].
+#[derive(Debug, Clone)]
+pub struct PartMetadata {
+    /// The `name` parameter of the Content-Disposition.
+    pub name: Option<String>,
+
+    /// The `filename` parameter of the Content-Disposition.
+    pub filename: Option<String>,
+
+    /// The parsed Content-Type.
+    pub content_type: Option<Mime>,
+
+    /// The length declared by a `Content-Length` header.
+    pub declared_len: Option<u64>,
+
+    /// The actual size of the part content.
+    pub size: usize,
+
+    /// All part headers.
+    pub headers: HeaderMap,
+}
+
+impl ScannedMultipart {
+    /// Buffer the multipart body from `stream` and scan its parts.
+    ///
+    /// Fails with [`PayloadError::Overflow`] if the body is larger than `limit` bytes, and with
+    /// the parse error if the body is not a valid multipart body for `headers`.
+    pub async fn scan<S>(
+        headers: &HeaderMap,
+        mut stream: S,
+        limit: usize,
+    ) -> Result<Self, MultipartError>
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
+    {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if buf.len() + chunk.len() > limit {
+                return Err(PayloadError::Overflow.into());
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        let body = buf.freeze();
+
+        let mut parts = Vec::new();
+        let mut multipart = Multipart::new(headers, stream::iter([Ok(body.clone())]));
+        while let Some(field) = multipart.next().await {
+            let field = field?;
+            let cd = field.content_disposition();
+            let mut part = PartMetadata {
+                name: cd.and_then(|cd| cd.get_name()).map(ToOwned::to_owned),
+                filename: cd.and_then(|cd| cd.get_filename()).map(ToOwned::to_owned),
+                content_type: field.content_type().cloned(),
+                declared_len: field.content_length(),
+                size: 0,
+                headers: field.headers().clone(),
+            };
+            part.size = field.measure().await?;
+            parts.push(part);
+        }
+
+        Ok(ScannedMultipart { parts, body })
+    }
+
+    /// Returns the metadata of every part, in the order they appear in the body.
+    pub fn parts(&self) -> &[PartMetadata] {
+        &self.parts
+    }
+
+    /// Returns the buffered body, exactly as received.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Unwrap into the buffered body.
+    pub fn into_body(self) -> Bytes {
+        self.body
+    }
+
+    /// Parse the buffered body again, from the start.
+    pub fn into_multipart(self, headers: &HeaderMap) -> Multipart {
+        Multipart::new(headers, stream::iter([Ok(self.body)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ntex::http::header::{self, HeaderValue};
+
+    #[ntex::test]
+    async fn test_scan() {
+        let body = Bytes::from_static(
+            b"--abbc\r\n\
+              Content-Disposition: form-data; name=\"meta\"\r\n\r\n\
+              {}\r\n\
+              --abbc\r\n\
+              Content-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\
+              Content-Type: image/png\r\n\
+              Content-Length: 4\r\n\r\n\
+              \x89PNG\r\n\
+              --abbc--\r\n",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=abbc"),
+        );
+        let chunks: Vec<_> = body.chunks(7).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+
+        let scanned =
+            ScannedMultipart::scan(&headers, stream::iter(chunks.clone()), 1024).await.unwrap();
+        assert_eq!(scanned.parts().len(), 2);
+        assert_eq!(scanned.parts()[0].name.as_deref(), Some("meta"));
+        assert_eq!(scanned.parts()[0].size, 2);
+        assert_eq!(scanned.parts()[1].filename.as_deref(), Some("a.png"));
+        assert_eq!(scanned.parts()[1].content_type, Some(mime::IMAGE_PNG));
+        assert_eq!(scanned.parts()[1].declared_len, Some(4));
+        assert_eq!(scanned.parts()[1].size, 4);
+        assert_eq!(scanned.body(), &body);
+
+        let mut multipart = scanned.into_multipart(&headers);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("meta"));
+
+        let res = ScannedMultipart::scan(&headers, stream::iter(chunks), 16).await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
+}
@@ -0,0 +1,143 @@
+//! In-memory multipart structure for round-trip testing
+
+use crate::{Multipart, MultipartError, MultipartWriter};
+use futures::TryStreamExt;
+use ntex::http::HeaderMap;
+use ntex::util::{Bytes, BytesMut};
+
+/// A fully collected multipart body.
+///
+/// Parsing a well-formed body, one without preamble or epilogue, and serializing it again with
+/// [`ParsedMultipart::to_bytes`] yields the original bytes. Meant for round-trip and
+/// differential testing of the parser against [`MultipartWriter`].
+#[derive(Debug, Clone)]
+pub struct ParsedMultipart {
+    /// The boundary delimiting parts.
+    pub boundary: String,
+
+    /// The parts in the order they were received.
+    pub parts: Vec<ParsedPart>,
+}
+
+/// A single part of a [`ParsedMultipart`].
+#[derive(Debug, Clone)]
+pub struct ParsedPart {
+    /// The parsed part headers.
+    pub headers: HeaderMap,
+
+    /// The header section exactly as received, including the blank line ending it.
+    pub raw_headers: Bytes,
+
+    /// The part body.
+    pub body: Bytes,
+}
+
+impl ParsedMultipart {
+    /// Read every part of `multipart` into memory.
+    pub async fn from_multipart(multipart: Multipart) -> Result<Self, MultipartError> {
+        let boundary = multipart.boundary_param().unwrap_or_default();
+        let mut multipart = multipart.raw_headers(true);
+
+        let mut parts = Vec::new();
+        while let Some(mut field) = multipart.try_next().await? {
+            let mut body = BytesMut::new();
+            while let Some(chunk) = field.try_next().await? {
+                body.extend_from_slice(&chunk);
+            }
+
+            parts.push(ParsedPart {
+                headers: field.headers().clone(),
+                raw_headers: field.raw_headers().cloned().unwrap_or_default(),
+                body: body.freeze(),
+            });
+        }
+
+        Ok(ParsedMultipart { boundary, parts })
+    }
+
+    /// Serialize the parts back into a multipart body, using the raw part headers.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        for part in &self.parts {
+            buf.extend_from_slice(b"--");
+            buf.extend_from_slice(self.boundary.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+            buf.extend_from_slice(&part.raw_headers);
+            buf.extend_from_slice(&part.body);
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf.extend_from_slice(b"--");
+        buf.extend_from_slice(self.boundary.as_bytes());
+        buf.extend_from_slice(b"--\r\n");
+        buf.freeze()
+    }
+
+    /// Build a writer producing the same parts from their parsed headers.
+    pub fn to_writer(&self) -> Result<MultipartWriter, MultipartError> {
+        let writer = MultipartWriter::with_boundary(self.boundary.clone())?;
+        Ok(self.parts.iter().fold(writer, |writer, part| {
+            writer.part_bytes(part.headers.clone(), part.body.clone())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use ntex::http::header::{self, HeaderValue};
+
+    fn multipart(content_type: &str, body: Bytes) -> Multipart {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        let chunks: Vec<_> = body.chunks(3).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        Multipart::new(&headers, stream::iter(chunks))
+    }
+
+    #[ntex::test]
+    async fn test_round_trip() {
+        let body = Bytes::from_static(
+            b"--abbc\r\n\
+              Content-Disposition: form-data; name=\"a\"\r\n\
+              Content-Type: text/plain\r\n\r\n\
+              first\r\n\r\n\
+              --abbc\r\n\
+              Content-Disposition: form-data; name=\"b\"; filename=\"b.bin\"\r\n\r\n\
+              \r\n--abb-\r\n\
+              --abbc--\r\n",
+        );
+
+        let parsed = ParsedMultipart::from_multipart(multipart(
+            "multipart/form-data; boundary=abbc",
+            body.clone(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(parsed.parts.len(), 2);
+        assert_eq!(parsed.parts[0].body, "first\r\n");
+        assert_eq!(parsed.parts[1].body, "\r\n--abb-");
+        assert_eq!(parsed.to_bytes(), body);
+    }
+
+    #[ntex::test]
+    async fn test_writer_differential() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let writer = MultipartWriter::new()
+            .part(headers.clone(), stream::iter([Ok(Bytes::from_static(b"one"))]))
+            .part(headers, stream::iter([Ok(Bytes::from_static(b"two\r\n"))]));
+
+        let content_type = writer.content_type("mixed");
+        let body: Vec<Bytes> = writer.into_stream().try_collect().await.unwrap();
+        let body = Bytes::from(body.concat());
+
+        let parsed = ParsedMultipart::from_multipart(multipart(&content_type, body.clone()))
+            .await
+            .unwrap();
+        assert_eq!(parsed.to_bytes(), body);
+
+        let rewritten: Vec<Bytes> =
+            parsed.to_writer().unwrap().into_stream().try_collect().await.unwrap();
+        assert_eq!(Bytes::from(rewritten.concat()), body);
+    }
+}
@@ -0,0 +1,318 @@
+//! Multipart body encoder
+
+use crate::{MultipartError, validate_boundary};
+use futures::future::ready;
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use ntex::http::HeaderMap;
+use ntex::http::error::PayloadError;
+use ntex::util::{Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Encoder for `multipart/*` bodies.
+///
+/// Parts are written in the order they were added, the resulting body is produced by
+/// [`MultipartWriter::into_stream`].
+///
+/// ```rust
+/// use futures::stream;
+/// use ntex::http::{HeaderMap, header};
+/// use ntex::util::Bytes;
+/// use ntex_multipart::MultipartWriter;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(
+///     header::CONTENT_DISPOSITION,
+///     header::HeaderValue::from_static("form-data; name=\"text\""),
+/// );
+///
+/// let writer = MultipartWriter::new()
+///     .part(headers, stream::iter([Ok(Bytes::from_static(b"hello"))]));
+/// let content_type = writer.content_type("form-data");
+/// let body = writer.into_stream();
+/// ```
+///
+/// The body is produced without knowing its total size, so it can always be sent with chunked
+/// transfer encoding. When the length of every part is known, see
+/// [`MultipartWriter::part_sized`], [`MultipartWriter::content_length`] returns the body size
+/// for a `Content-Length` header instead.
+pub struct MultipartWriter {
+    boundary: String,
+    parts: Vec<WriterPart>,
+}
+
+struct WriterPart {
+    headers: HeaderMap,
+    len: Option<u64>,
+    body: LocalBoxStream<'static, Result<Bytes, MultipartError>>,
+}
+
+impl MultipartWriter {
+    /// Create writer with a randomly generated boundary.
+    pub fn new() -> Self {
+        MultipartWriter { boundary: generate_boundary(), parts: Vec::new() }
+    }
+
+    /// Create writer with the provided boundary.
+    ///
+    /// Fails with [`MultipartError::Boundary`] if the boundary is not valid, see
+    /// [`validate_boundary`].
+    pub fn with_boundary(boundary: impl Into<String>) -> Result<Self, MultipartError> {
+        let boundary = boundary.into();
+        validate_boundary(&boundary)?;
+        Ok(MultipartWriter { boundary, parts: Vec::new() })
+    }
+
+    /// Returns the boundary used to delimit parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns Content-Type value for the body, e.g. `multipart/form-data; boundary=...`.
+    pub fn content_type(&self, subtype: &str) -> String {
+        content_type(subtype, &self.boundary)
+    }
+
+    /// Append a part with the given headers and body.
+    pub fn part<S>(mut self, headers: HeaderMap, body: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, MultipartError>> + 'static,
+    {
+        self.parts.push(WriterPart { headers, len: None, body: body.boxed_local() });
+        self
+    }
+
+    /// Append a part whose body is known to be `len` bytes long.
+    ///
+    /// If `body` yields a different number of bytes, the stream fails with
+    /// [`MultipartError::Incomplete`] or [`PayloadError::Overflow`] rather than producing a
+    /// body that does not match [`MultipartWriter::content_length`].
+    pub fn part_sized<S>(mut self, headers: HeaderMap, len: u64, body: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, MultipartError>> + 'static,
+    {
+        self.parts.push(WriterPart { headers, len: Some(len), body: checked_len(body, len) });
+        self
+    }
+
+    /// Append a part with an in-memory body.
+    pub fn part_bytes(self, headers: HeaderMap, body: Bytes) -> Self {
+        let len = body.len() as u64;
+        self.part_sized(headers, len, stream::once(ready(Ok(body))))
+    }
+
+    /// Returns the size of the whole body, if the length of every part is known.
+    ///
+    /// Use it to set `Content-Length`, or send the body with chunked transfer encoding when it
+    /// is `None`.
+    pub fn content_length(&self) -> Option<u64> {
+        self.parts.iter().try_fold(encode_end(&self.boundary).len() as u64, |total, part| {
+            let head = encode_head(&self.boundary, &part.headers).len() as u64;
+            Some(total + head + part.len? + 2)
+        })
+    }
+
+    /// Convert writer into a stream of body chunks.
+    pub fn into_stream(self) -> LocalBoxStream<'static, Result<Bytes, MultipartError>> {
+        let boundary = self.boundary;
+        let end = encode_end(&boundary);
+
+        stream::iter(self.parts)
+            .flat_map(move |WriterPart { headers, body, .. }| {
+                let head = encode_head(&boundary, &headers);
+                stream::once(ready(Ok(head)))
+                    .chain(body)
+                    .chain(stream::once(ready(Ok(Bytes::from_static(b"\r\n")))))
+            })
+            .chain(stream::once(ready(Ok(end))))
+            .boxed_local()
+    }
+}
+
+impl Default for MultipartWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fail `body` if it does not yield exactly `len` bytes.
+fn checked_len<S>(body: S, len: u64) -> LocalBoxStream<'static, Result<Bytes, MultipartError>>
+where
+    S: Stream<Item = Result<Bytes, MultipartError>> + 'static,
+{
+    let body = body.map(Some).chain(stream::once(ready(None)));
+    body.scan(0u64, move |written, item| {
+        let item = match item {
+            Some(Ok(chunk)) => {
+                *written += chunk.len() as u64;
+                if *written > len {
+                    Some(Err(PayloadError::Overflow.into()))
+                } else {
+                    Some(Ok(chunk))
+                }
+            }
+            Some(Err(err)) => Some(Err(err)),
+            None if *written < len => Some(Err(MultipartError::Incomplete)),
+            None => None,
+        };
+        ready(item)
+    })
+    .boxed_local()
+}
+
+/// Encode delimiter and headers that precede a part body.
+pub(crate) fn encode_head(boundary: &str, headers: &HeaderMap) -> Bytes {
+    let mut buf = BytesMut::with_capacity(boundary.len() + 64);
+    buf.extend_from_slice(b"--");
+    buf.extend_from_slice(boundary.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    for (name, value) in headers.iter() {
+        buf.extend_from_slice(name.as_str().as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf.extend_from_slice(b"\r\n");
+    buf.freeze()
+}
+
+/// Format a multipart Content-Type, quoting the boundary when it contains special characters.
+pub(crate) fn content_type(subtype: &str, boundary: &str) -> String {
+    if boundary.bytes().all(|b| b.is_ascii_alphanumeric() || b"'+_-.".contains(&b)) {
+        format!("multipart/{subtype}; boundary={boundary}")
+    } else {
+        format!("multipart/{subtype}; boundary=\"{boundary}\"")
+    }
+}
+
+/// Encode the closing delimiter.
+pub(crate) fn encode_end(boundary: &str) -> Bytes {
+    Bytes::from(format!("--{}--\r\n", boundary))
+}
+
+/// Generate a random boundary.
+pub(crate) fn generate_boundary() -> String {
+    let state = RandomState::new();
+    let mut boundary = String::with_capacity(32);
+    for idx in 0..2u64 {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(idx);
+        boundary.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    boundary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Multipart;
+    use futures::TryStreamExt;
+    use ntex::http::header::{self, HeaderValue};
+
+    #[ntex::test]
+    async fn test_writer() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("form-data; name=\"text\""),
+        );
+
+        let writer =
+            MultipartWriter::with_boundary("abbc761f78ff4d7cb7573b5a23f96ef0").unwrap().part(
+                headers,
+                stream::iter([Ok(Bytes::from_static(b"hel")), Ok(Bytes::from_static(b"lo"))]),
+            );
+        let body: Vec<Bytes> = writer.into_stream().try_collect().await.unwrap();
+        assert_eq!(
+            body.concat(),
+            b"--abbc761f78ff4d7cb7573b5a23f96ef0\r\n\
+              content-disposition: form-data; name=\"text\"\r\n\r\n\
+              hello\r\n\
+              --abbc761f78ff4d7cb7573b5a23f96ef0--\r\n"
+        );
+    }
+
+    #[ntex::test]
+    async fn test_writer_roundtrip() {
+        let mut part_headers = HeaderMap::new();
+        part_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        let writer = MultipartWriter::new()
+            .part(part_headers.clone(), stream::iter([Ok(Bytes::from_static(b"first"))]))
+            .part(part_headers, stream::empty());
+        assert_eq!(writer.boundary().len(), 32);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(&writer.content_type("mixed")).unwrap(),
+        );
+        let body: Vec<Bytes> = writer.into_stream().try_collect().await.unwrap();
+
+        let mut multipart =
+            Multipart::new(&headers, stream::iter([Ok(Bytes::from(body.concat()))]));
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.measure().await.unwrap(), 5);
+        let field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.measure().await.unwrap(), 0);
+        assert!(multipart.next().await.is_none());
+    }
+
+    #[ntex::test]
+    async fn test_writer_content_length() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        let writer = MultipartWriter::new()
+            .part_bytes(headers.clone(), Bytes::from_static(b"first"))
+            .part_sized(
+                headers.clone(),
+                6,
+                stream::iter([Ok(Bytes::from_static(b"sec")), Ok(Bytes::from_static(b"ond"))]),
+            );
+        let len = writer.content_length().unwrap();
+        let body: Vec<Bytes> = writer.into_stream().try_collect().await.unwrap();
+        assert_eq!(body.concat().len() as u64, len);
+
+        // a part of unknown length is still streamed, without a known total
+        let writer = MultipartWriter::new()
+            .part_bytes(headers.clone(), Bytes::from_static(b"first"))
+            .part(headers.clone(), stream::iter([Ok(Bytes::from_static(b"second"))]));
+        assert_eq!(writer.content_length(), None);
+        let end = encode_end(writer.boundary());
+        let body: Vec<Bytes> = writer.into_stream().try_collect().await.unwrap();
+        let body = body.concat();
+        assert!(body.ends_with(&end));
+        assert!(body.windows(10).any(|w| w == b"second\r\n--"));
+
+        let writer = MultipartWriter::new().part_sized(
+            headers.clone(),
+            4,
+            stream::iter([Ok(Bytes::from_static(b"abc"))]),
+        );
+        let res: Result<Vec<Bytes>, _> = writer.into_stream().try_collect().await;
+        assert!(matches!(res, Err(MultipartError::Incomplete)));
+
+        let writer = MultipartWriter::new().part_sized(
+            headers,
+            2,
+            stream::iter([Ok(Bytes::from_static(b"abc"))]),
+        );
+        let res: Result<Vec<Bytes>, _> = writer.into_stream().try_collect().await;
+        assert!(matches!(res, Err(MultipartError::Payload(PayloadError::Overflow))));
+    }
+
+    #[test]
+    fn test_writer_boundary() {
+        assert!(matches!(
+            MultipartWriter::with_boundary("not;valid"),
+            Err(MultipartError::Boundary)
+        ));
+        assert!(MultipartWriter::with_boundary("a".repeat(71)).is_err());
+
+        let writer = MultipartWriter::with_boundary("simple").unwrap();
+        assert_eq!(writer.content_type("mixed"), "multipart/mixed; boundary=simple");
+        let writer = MultipartWriter::with_boundary("with space:").unwrap();
+        assert_eq!(writer.content_type("mixed"), "multipart/mixed; boundary=\"with space:\"");
+    }
+}